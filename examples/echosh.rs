@@ -46,6 +46,8 @@ impl shelgon::command::Execute for Executor {
             return shelgon::command::Prepare {
                 command: cmd.to_string(),
                 stdin_required: true,
+                stdin_prompt: Some(">".to_string()),
+                ..Default::default()
             };
         }
 
@@ -53,6 +55,7 @@ impl shelgon::command::Execute for Executor {
         shelgon::command::Prepare {
             command: cmd.to_string(),
             stdin_required: false,
+            ..Default::default()
         }
     }
 
@@ -68,6 +71,7 @@ impl shelgon::command::Execute for Executor {
             stdin: cmd.stdin.unwrap_or_default(),
             stdout: vec![cmd.command],
             stderr: Vec::new(),
+            ..Default::default()
         };
         Ok(shelgon::command::OutputAction::Command(output))
     }
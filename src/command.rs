@@ -4,9 +4,21 @@
 //! shells. The primary components are:
 //!
 //! - [`Execute`]: The main trait for implementing shell command execution
+//! - [`TypedExecute`]: An alternative to [`Execute`], for executors with a domain-specific error
+//!   type
 //! - [`CommandInput`]: Input data structure passed to command executors
 //! - [`CommandOutput`]: Output data structure for command results
+//! - [`OutputLine`]: A line of the output, tagged with its stream (for interleaved output)
 //! - [`OutputAction`]: Enum controlling shell behavior after command execution
+//! - [`OutputWriter`]: Handle for streaming the output of a command, line by line
+//! - [`KeyReader`]: Handle for reading single key presses while a command is executed
+//! - [`Notifier`]: Handle for pushing outputs into the history, outside of the commands
+//! - [`PauseHandle`]: Handle for pausing the shell, while another UI takes over the terminal
+//! - [`CompletionProvider`]: An additional source of completions, merged with
+//!   [`Execute::completion`]
+//! - [`CompletionResult`]: A completion that replaces a range of the command (see
+//!   [`Execute::completion_result`])
+//! - [`complete_path`]: A helper for completing file paths in [`Execute::completion`]
 //!
 //! # Architecture
 //!
@@ -37,6 +49,7 @@
 //!         command::Prepare {
 //!             command: cmd.to_string(),
 //!             stdin_required: false,
+//!             ..Default::default()
 //!         }
 //!     }
 //!
@@ -51,6 +64,7 @@
 //!             stdin: Vec::new(),
 //!             stdout: vec![format!("Executed: {}", input.command)],
 //!             stderr: Vec::new(),
+//!             ..Default::default()
 //!         }))
 //!     }
 //! }
@@ -60,10 +74,16 @@
 //!
 //! - **tokio**: Enables async runtime support via [`tokio::runtime::Runtime`] in [`CommandInput`]
 
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crossterm::event::{Event, KeyEvent, KeyEventKind};
+use ratatui::style::Style;
+use ratatui::text::Line;
 #[cfg(feature = "tokio")]
-use std::sync::Arc;
-#[cfg(feature = "tokio")]
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
 
 ///
 /// [`CommandOutput`] is the output supplied to the renderer by the [`Execute`] trait.
@@ -71,7 +91,17 @@ use tokio::runtime::Runtime;
 /// `prompt` & `command` are the prompt and command that were executed.
 /// `stdin` is the input that was supplied to the command. (optional)
 /// `stdout` & `stderr` are the output of the command.
+/// `duration` is the time that the command took to execute, measured by the renderer. (optional)
+/// `timestamp` is when the command was executed, recorded by the renderer. (optional)
+/// `layout` is how the stdout is laid out. (optional)
+/// `output` is the stdout and stderr, in the order they were produced. (optional, see
+/// [`CommandOutput::interleaved`])
+/// `modified` is whether the command modified the state. (optional)
+/// `collapsed` is whether the output is collapsed into a summary line. (optional)
 ///
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct CommandOutput {
     /// The prompt that was displayed.
     pub prompt: String,
@@ -83,6 +113,114 @@ pub struct CommandOutput {
     pub stdout: Vec<String>,
     /// The error output of the command. (optional)
     pub stderr: Vec<String>,
+    /// The (wall-clock) time that [`Execute::execute`] took. This is set by the renderer, and
+    /// can be left as `None` by the executor. (optional)
+    pub duration: Option<Duration>,
+    /// The (wall-clock) time at which the command was executed. This is set by the renderer, and
+    /// can be left as `None` by the executor. (optional)
+    pub timestamp: Option<SystemTime>,
+    /// The layout of the output. (default: [`OutputLayout::Lines`])
+    pub layout: OutputLayout,
+    /// The lines of the stdout and stderr, in the order they were produced. When this is
+    /// non-empty, the output is rendered in this order, rather than all of the stdout followed by
+    /// all of the stderr. This is expected to hold the same lines as `stdout` and `stderr`.
+    /// (optional)
+    pub output: Vec<OutputLine>,
+    /// Whether the command modified the state (i.e. had side effects). The modifying commands are
+    /// rendered with a marker (see [`crate::App::with_modified_marker`]). (default: `false`)
+    pub modified: bool,
+    /// Whether the output is collapsed, i.e. rendered as a summary line (e.g. `… 120 lines`),
+    /// until it's expanded with `Ctrl+O`. (see [`crate::App::with_auto_collapse_threshold`])
+    /// (default: `false`)
+    pub collapsed: bool,
+    /// The group (e.g. a tab or section) that the output belongs to. When the groups are enabled,
+    /// only the outputs of the active group are rendered, along with the outputs without a group.
+    /// (see [`crate::App::with_groups`]) (optional)
+    pub group: Option<String>,
+}
+
+impl CommandOutput {
+    /// Create the output from the lines of the stdout and stderr, in the order they were
+    /// produced. The `stdout` and `stderr` are filled from the lines as well, so that the
+    /// consumers reading those keep working.
+    ///
+    /// ```rust
+    /// use shelgon::command::{CommandOutput, OutputLine};
+    ///
+    /// let output = CommandOutput::interleaved(
+    ///     "$".to_string(),
+    ///     "make".to_string(),
+    ///     vec![
+    ///         OutputLine::Stdout("compiling".to_string()),
+    ///         OutputLine::Stderr("warning: unused".to_string()),
+    ///         OutputLine::Stdout("done".to_string()),
+    ///     ],
+    /// );
+    ///
+    /// assert_eq!(output.stdout, ["compiling", "done"]);
+    /// assert_eq!(output.stderr, ["warning: unused"]);
+    /// ```
+    pub fn interleaved(prompt: String, command: String, output: Vec<OutputLine>) -> Self {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        for line in &output {
+            match line {
+                OutputLine::Stdout(line) => stdout.push(line.clone()),
+                OutputLine::Stderr(line) => stderr.push(line.clone()),
+            }
+        }
+        Self {
+            prompt,
+            command,
+            stdout,
+            stderr,
+            output,
+            ..Default::default()
+        }
+    }
+
+    /// The lines of the stdout and stderr, in the order they are rendered. This is `output` if
+    /// it's non-empty, otherwise all of the stdout followed by all of the stderr.
+    pub fn lines(&self) -> Vec<OutputLine> {
+        match self.output.is_empty() {
+            false => self.output.clone(),
+            true => self
+                .stdout
+                .iter()
+                .cloned()
+                .map(OutputLine::Stdout)
+                .chain(self.stderr.iter().cloned().map(OutputLine::Stderr))
+                .collect(),
+        }
+    }
+}
+
+///
+/// [`OutputLine`] is a line of the output of a command, tagged with the stream it was written to.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OutputLine {
+    /// A line of the stdout.
+    Stdout(String),
+    /// A line of the stderr.
+    Stderr(String),
+}
+
+///
+/// [`OutputLayout`] is how the stdout of a [`CommandOutput`] is laid out by the renderer.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OutputLayout {
+    /// Every line is rendered as is.
+    #[default]
+    Lines,
+    /// Every line is an item, and the items are laid out in columns (like `ls`), to fit the
+    /// width of the terminal. The columns are filled top to bottom, then left to right.
+    Columns,
 }
 
 ///
@@ -93,6 +231,7 @@ pub struct CommandOutput {
 /// the [`Execute`] trait.
 ///
 ///
+#[derive(Clone)]
 pub enum OutputAction {
     /// Render the output of the command.
     Command(CommandOutput),
@@ -100,6 +239,391 @@ pub enum OutputAction {
     Exit,
     /// Clear the screen.
     Clear,
+    /// Render the command as pending, until the output is resolved. (see [`PendingOutput`])
+    Pending(PendingOutput),
+    /// Render the items in columns. This is a shorthand for [`OutputAction::Command`], with the
+    /// items as the stdout, and the [`OutputLayout::Columns`] layout.
+    Columns(Vec<String>),
+    /// Redraw the whole screen right away, without changing the history. This is useful when
+    /// the executor changes the state that the rendering depends on (e.g. the prompt), or when
+    /// something else has written to the terminal.
+    Redraw,
+    /// Render the image (PNG bytes). The image is rendered as a placeholder line, and is
+    /// displayed through the terminal graphics protocol, if one is selected (see
+    /// [`crate::App::with_graphics_protocol`]).
+    Image(Vec<u8>),
+    /// The command is unknown to the executor. The output is produced by
+    /// [`Execute::not_found`], or is the uniform `<name>: command not found` error.
+    NotFound,
+}
+
+/// The slot that a [`PendingOutput`] is resolved into.
+type Slot = Arc<Mutex<Option<anyhow::Result<CommandOutput>>>>;
+
+///
+/// [`PendingOutput`] is the output of a command that is still being computed (e.g. by an async
+/// task). This allows [`Execute::execute`] to return quickly, keeping the shell responsive.
+///
+/// The command is rendered as pending in the history, and is updated in place once the output is
+/// resolved (via the [`PendingResolver`]). If the output is resolved to an error, the error is
+/// rendered as the stderr of the command.
+///
+/// Pressing `Ctrl+C` while outputs are pending cancels them: the tasks spawned via
+/// [`PendingOutput::spawn`] are aborted, and `^C` is rendered as the output.
+///
+#[derive(Clone)]
+pub struct PendingOutput {
+    /// The slot that the output is resolved into.
+    slot: Slot,
+    #[cfg(feature = "tokio")]
+    /// The handle that aborts the task resolving the output, if it was spawned.
+    abort: Option<tokio::task::AbortHandle>,
+}
+
+///
+/// [`PendingResolver`] resolves the output of the corresponding [`PendingOutput`].
+///
+pub struct PendingResolver {
+    /// The slot that the output is resolved into.
+    slot: Slot,
+}
+
+impl PendingOutput {
+    /// Create a new [`PendingOutput`], along with the [`PendingResolver`] that resolves it.
+    pub fn new() -> (Self, PendingResolver) {
+        let slot = Slot::default();
+        let pending = Self {
+            slot: slot.clone(),
+            #[cfg(feature = "tokio")]
+            abort: None,
+        };
+        (pending, PendingResolver { slot })
+    }
+
+    /// Create a new [`PendingOutput`] that is resolved by the `future`, spawned on the runtime.
+    #[cfg(feature = "tokio")]
+    pub fn spawn<F>(handle: &Handle, future: F) -> Self
+    where
+        F: std::future::Future<Output = anyhow::Result<CommandOutput>> + Send + 'static,
+    {
+        let (pending, resolver) = Self::new();
+        let task = handle.spawn(async move { resolver.resolve(future.await) });
+        Self {
+            abort: Some(task.abort_handle()),
+            ..pending
+        }
+    }
+
+    /// Whether the output has been resolved (and not yet taken by the renderer).
+    pub fn is_resolved(&self) -> bool {
+        self.slot.lock().map(|slot| slot.is_some()).unwrap_or(false)
+    }
+
+    /// Abort the task that resolves the output, if it was spawned.
+    pub(crate) fn abort(&self) {
+        #[cfg(feature = "tokio")]
+        if let Some(abort) = self.abort.as_ref() {
+            abort.abort();
+        }
+    }
+
+    /// Take the output, if it has been resolved.
+    pub(crate) fn take(&self) -> Option<anyhow::Result<CommandOutput>> {
+        self.slot.lock().ok().and_then(|mut slot| slot.take())
+    }
+}
+
+///
+/// [`OutputWriter`] streams the output of a command to the renderer, line by line. This is passed
+/// to [`Execute::execute_streaming`].
+///
+/// The lines are rendered live while the output is pending (see [`OutputAction::Pending`]), e.g.
+/// from the task that resolves the output. The lines come before the output that the command
+/// resolves to, so the writing is expected to be done by then: the lines that are written after
+/// the output is rendered are ignored.
+///
+/// Like in a terminal, a line starting with `\r` (carriage return) replaces the last line instead
+/// of being appended, e.g. for a progress percentage that is updated in place. (see
+/// [`OutputWriter::update_last_line`])
+///
+/// The written lines are buffered, and are taken by the renderer once per tick (i.e. at most
+/// every 100ms). So the lines that are written in between are rendered together, in a single
+/// frame: writing many lines in a burst doesn't cause a redraw per line, and there is no need to
+/// batch the writes.
+///
+#[derive(Clone, Default)]
+pub struct OutputWriter {
+    /// The lines that are written, until they are taken by the renderer.
+    written: Arc<Mutex<Written>>,
+}
+
+/// The lines written to an [`OutputWriter`] since they were last taken by the renderer.
+#[derive(Default)]
+pub(crate) struct Written {
+    /// The number of lines, taken earlier, that are replaced by the written lines.
+    pub(crate) replaced: usize,
+    /// The written lines.
+    pub(crate) lines: Vec<OutputLine>,
+}
+
+impl OutputWriter {
+    /// Write a line to the stdout.
+    pub fn stdout_line(&self, line: &str) {
+        let (replace, line) = carriage_return(line);
+        self.write(OutputLine::Stdout(line.to_string()), replace);
+    }
+
+    /// Write a line to the stderr.
+    pub fn stderr_line(&self, line: &str) {
+        let (replace, line) = carriage_return(line);
+        self.write(OutputLine::Stderr(line.to_string()), replace);
+    }
+
+    /// Replace the last line of the output (of either stream) with a stdout line, or write it if
+    /// nothing is written yet.
+    pub fn update_last_line(&self, line: &str) {
+        self.write(OutputLine::Stdout(line.to_string()), true);
+    }
+
+    /// Write the line, replacing the last line if `replace` is set.
+    fn write(&self, line: OutputLine, replace: bool) {
+        if let Ok(mut written) = self.written.lock() {
+            // the last line is replaced where it is: still in the writer, or already taken.
+            if replace && written.lines.pop().is_none() {
+                written.replaced += 1;
+            }
+            written.lines.push(line);
+        }
+    }
+
+    /// Take the lines that are written since the last call.
+    pub(crate) fn take(&self) -> Written {
+        self.written
+            .lock()
+            .map(|mut written| std::mem::take(&mut *written))
+            .unwrap_or_default()
+    }
+}
+
+/// Split the carriage return off the line: whether the line starts with `\r`, and the text that
+/// remains visible after the last `\r`.
+fn carriage_return(line: &str) -> (bool, &str) {
+    let text = line
+        .rsplit('\r')
+        .find(|part| !part.is_empty())
+        .unwrap_or("");
+    (line.starts_with('\r'), text)
+}
+
+///
+/// [`KeyReader`] reads single key presses, from the same terminal events that the shell reads.
+/// This is passed to [`Execute::execute`] (see [`CommandInput::keys`]).
+///
+/// This lets an interactive command (e.g. a confirmation) wait for a key press, without going
+/// through the stdin collection. The shell doesn't read the terminal events while the command is
+/// executed, so the key is only seen by the command. The keys are taken from the replay first,
+/// and recorded (see [`crate::App::replay`] and [`crate::App::with_record`]), while the other
+/// events (e.g. pastes) are left to the shell, once the command is done.
+///
+/// The reader can be mocked with [`KeyReader::new`], e.g. for testing the executor:
+///
+/// ```rust
+/// use crossterm::event::{KeyCode, KeyEvent};
+/// use shelgon::command::KeyReader;
+///
+/// // the confirmation of a command, answered with `y` (or anything else).
+/// fn confirm(keys: &KeyReader) -> anyhow::Result<&'static str> {
+///     match keys.read_key()?.code {
+///         KeyCode::Char('y') => Ok("removed"),
+///         _ => Ok("cancelled"),
+///     }
+/// }
+///
+/// let mut answers = vec![KeyEvent::from(KeyCode::Char('n')), KeyEvent::from(KeyCode::Char('y'))]
+///     .into_iter();
+/// let keys = KeyReader::new(move || {
+///     answers.next().ok_or_else(|| anyhow::anyhow!("no more keys"))
+/// });
+///
+/// assert_eq!(confirm(&keys)?, "cancelled");
+/// assert_eq!(confirm(&keys)?, "removed");
+/// assert!(keys.read_key().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+#[derive(Clone)]
+pub struct KeyReader {
+    /// The source of the key presses.
+    read: Arc<Mutex<KeySource>>,
+}
+
+/// The source of the key presses of a [`KeyReader`].
+type KeySource = dyn FnMut() -> anyhow::Result<KeyEvent> + Send;
+
+impl KeyReader {
+    /// Create a [`KeyReader`] that reads the key presses from `read`, instead of the terminal.
+    pub fn new(read: impl FnMut() -> anyhow::Result<KeyEvent> + Send + 'static) -> Self {
+        Self {
+            read: Arc::new(Mutex::new(read)),
+        }
+    }
+
+    /// Block until a key is pressed, returning it.
+    pub fn read_key(&self) -> anyhow::Result<KeyEvent> {
+        let mut read = self
+            .read
+            .lock()
+            .map_err(|_| anyhow::anyhow!("the key reader is poisoned"))?;
+        read()
+    }
+}
+
+impl Default for KeyReader {
+    /// The reader of the key presses from the terminal (skipping the other events).
+    fn default() -> Self {
+        Self::new(|| loop {
+            // the key releases (reported on windows) are skipped, like in the shell.
+            if let Event::Key(key) = crossterm::event::read()? {
+                if key.kind != KeyEventKind::Release {
+                    return Ok(key);
+                }
+            }
+        })
+    }
+}
+
+///
+/// [`Notifier`] pushes outputs into the history of the shell, outside of the commands, e.g. from a
+/// background task that is spawned when the executor is created (see [`New::new`]). The shell
+/// receives the notifications once it's configured with [`crate::App::with_notifier`].
+///
+#[cfg_attr(feature = "tokio", doc = "```rust")]
+#[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+/// use shelgon::command::{self, CommandInput, CommandOutput, Execute, New, Notifier, OutputAction};
+/// use shelgon::App;
+///
+/// struct Watch {
+///     notifier: Notifier,
+/// }
+///
+/// impl Execute for Watch {
+///     type Context = ();
+///
+///     fn prompt(&self, _: &Self::Context) -> String {
+///         "$".to_string()
+///     }
+///
+///     fn prepare(&self, cmd: &str) -> command::Prepare {
+///         command::Prepare {
+///             command: cmd.to_string(),
+///             stdin_required: false,
+///             ..Default::default()
+///         }
+///     }
+///
+///     fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+///         Ok(OutputAction::Exit)
+///     }
+/// }
+///
+/// impl New for Watch {
+///     fn new() -> anyhow::Result<(Self, Self::Context)> {
+///         let notifier = Notifier::default();
+///         // the task is spawned on the runtime of the shell.
+///         let task = notifier.clone();
+///         tokio::spawn(async move {
+///             task.notify(CommandOutput {
+///                 prompt: "!".to_string(),
+///                 command: "watch".to_string(),
+///                 stdout: vec!["the file has changed".to_string()],
+///                 ..Default::default()
+///             });
+///         });
+///         Ok((Self { notifier }, ()))
+///     }
+/// }
+///
+/// let app = App::<Watch>::new(tokio::runtime::Runtime::new()?)?;
+/// let notifier = app.executor().notifier.clone();
+/// let app = app.with_notifier(notifier);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct Notifier {
+    /// The outputs that are notified, until they are taken by the renderer.
+    outputs: Arc<Mutex<Vec<CommandOutput>>>,
+}
+
+impl Notifier {
+    /// Push the output into the history of the shell.
+    pub fn notify(&self, output: CommandOutput) {
+        if let Ok(mut outputs) = self.outputs.lock() {
+            outputs.push(output);
+        }
+    }
+
+    /// Take the outputs that are notified since the last call.
+    pub(crate) fn take(&self) -> Vec<CommandOutput> {
+        self.outputs
+            .lock()
+            .map(|mut outputs| std::mem::take(&mut *outputs))
+            .unwrap_or_default()
+    }
+}
+
+///
+/// [`PauseHandle`] pauses (and resumes) the shell from another thread, e.g. while another UI takes
+/// over the terminal. The shell is paused once it's configured with
+/// [`crate::App::with_pause_handle`].
+///
+/// While paused, the shell restores the terminal (see [`crate::App::with_manage_terminal`]),
+/// and neither renders nor reads the events, so that the keys go to the other UI. Once resumed,
+/// the terminal is set up again, and the shell is redrawn in full.
+///
+/// ```rust
+/// use shelgon::command::PauseHandle;
+///
+/// let handle = PauseHandle::default();
+/// // the shell runs on its own thread, with a clone of the handle.
+/// let shell = handle.clone();
+///
+/// handle.pause();
+/// assert!(shell.is_paused());
+/// handle.resume();
+/// assert!(!shell.is_paused());
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct PauseHandle {
+    /// Whether the shell is paused.
+    paused: Arc<AtomicBool>,
+}
+
+impl PauseHandle {
+    /// Pause the shell, restoring the terminal.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume the shell, setting up the terminal again.
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether the shell is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+impl PendingResolver {
+    /// Resolve the output of the command.
+    pub fn resolve(self, output: anyhow::Result<CommandOutput>) {
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = Some(output);
+        }
+    }
 }
 
 ///
@@ -115,10 +639,20 @@ pub struct CommandInput {
     pub command: String,
     /// The input that is supplied to the command. (optional)
     pub stdin: Option<Vec<String>>,
+    /// The position of the cursor on the screen, as `(row, column)`, when the command was
+    /// submitted. This is useful for rendering inline widgets. (optional)
+    pub cursor_position: Option<(u16, u16)>,
+    /// The reader of single key presses from the terminal, e.g. for confirmations or menus.
+    pub keys: KeyReader,
     #[cfg(feature = "tokio")]
     /// Supplying [`tokio::runtime::Runtime`] to the [`Execute`] trait. This is to facilitate
     /// executing [`std::future::Future`]s, creating [`tokio::task::JoinHandle`]s, etc.
     pub runtime: Arc<Runtime>,
+    #[cfg(feature = "tokio")]
+    /// The handle of the runtime that commands should use for spawning tasks, or blocking on
+    /// [`std::future::Future`]s. This is the handle of `runtime`, unless the shell is configured
+    /// with a shared runtime (see [`crate::App::with_runtime_handle`]).
+    pub handle: Handle,
 }
 
 ///
@@ -129,13 +663,71 @@ pub struct CommandInput {
 ///
 /// Supported behaviors:
 /// - `stdin_required`: If the command requires stdin, the renderer should prompt the user for input.
+/// - `stdin_prompt`: The prompt rendered before the stdin lines, while they are collected.
+///   (optional)
+/// - `mask_stdin`: The character that the stdin is masked with, e.g. for passwords. (optional)
 ///
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Prepare {
     /// The command that is to be executed.
     pub command: String,
     /// If the command requires stdin.
     pub stdin_required: bool,
+    /// The prompt that is rendered before each stdin line, while the stdin is collected (e.g.
+    /// `>` for a heredoc). When this is `None`, the lines are prefixed with the gutter instead
+    /// (see [`crate::App::with_gutter`]). (optional)
+    pub stdin_prompt: Option<String>,
+    /// The character that each character of the stdin is rendered as (e.g. `*` for a password),
+    /// while the stdin is collected, and in the history. The stdin that is passed to the
+    /// executor (via [`CommandInput::stdin`]) isn't masked. When this is `None`, the stdin is
+    /// rendered as typed. (optional)
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Login;
+    ///
+    /// impl Execute for Login {
+    ///     // the password that is received.
+    ///     type Context = String;
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: true,
+    ///             mask_stdin: Some('*'),
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, password: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         let stdin = input.stdin.unwrap_or_default();
+    ///         *password = stdin.concat();
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             stdin,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Login, String::new());
+    /// app.run_command("login", Some(vec!["hunter2".to_string()]))?;
+    ///
+    /// assert_eq!(app.context(), "hunter2");
+    /// assert_eq!(app.history()[0].stdin, vec!["*******"]);
+    /// assert!(!app.transcript_html().contains("hunter2"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub mask_stdin: Option<char>,
 }
 
 ///
@@ -166,6 +758,127 @@ pub trait Execute {
     ///
     fn prompt(&self, ctx: &Self::Context) -> String;
 
+    ///
+    /// This is the prompt symbol, e.g. `#` for root and `$` otherwise. This is optional, and is
+    /// rendered (in bold) after the prompt, separated by a space. The symbol is part of the prompt
+    /// that is recorded in the history (and passed as [`CommandInput::prompt`]).
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Sudo;
+    ///
+    /// impl Execute for Sudo {
+    ///     // whether the shell is elevated.
+    ///     type Context = bool;
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "~/src".to_string()
+    ///     }
+    ///
+    ///     fn prompt_symbol(&self, elevated: &Self::Context) -> Option<char> {
+    ///         Some(if *elevated { '#' } else { '$' })
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, elevated: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         *elevated = input.command == "sudo -s";
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Sudo, false);
+    /// app.run_command("sudo -s", None)?;
+    /// app.run_command("id", None)?;
+    ///
+    /// assert_eq!(app.history()[0].prompt, "~/src $");
+    /// assert_eq!(app.history()[1].prompt, "~/src #");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    fn prompt_symbol(&self, _ctx: &Self::Context) -> Option<char> {
+        None
+    }
+
+    ///
+    /// These are the segments of the prompt context, e.g. the working directory or the git
+    /// branch, rendered (in their style) after the prompt and before the prompt symbol, separated
+    /// by spaces. This saves crafting the whole prompt string for showing such information. Like
+    /// the prompt symbol, the segments are part of the prompt that is recorded in the history
+    /// (without their styles).
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use ratatui::style::{Style, Stylize};
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Sh;
+    ///
+    /// impl Execute for Sh {
+    ///     // the working directory.
+    ///     type Context = String;
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "sh".to_string()
+    ///     }
+    ///
+    ///     fn prompt_context(&self, cwd: &Self::Context) -> Vec<(String, Style)> {
+    ///         vec![(cwd.clone(), Style::default().cyan())]
+    ///     }
+    ///
+    ///     fn prompt_symbol(&self, _: &Self::Context) -> Option<char> {
+    ///         Some('$')
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, cwd: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         if let Some(dir) = input.command.strip_prefix("cd ") {
+    ///             *cwd = dir.to_string();
+    ///         }
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Sh, "~".to_string());
+    /// app.run_command("cd ~/src", None)?;
+    /// app.run_command("ls", None)?;
+    ///
+    /// assert_eq!(app.history()[0].prompt, "sh ~ $");
+    /// assert_eq!(app.history()[1].prompt, "sh ~/src $");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    fn prompt_context(&self, _ctx: &Self::Context) -> Vec<(String, Style)> {
+        Vec::new()
+    }
+
     ///
     /// This is the completion that is displayed to the user. This is displayed when the user
     /// presses the `Tab` key. This is optional, and can be left empty.
@@ -178,13 +891,96 @@ pub trait Execute {
     /// The deterministic completion is applied to the command, and the non-deterministic is shown
     /// below the command.
     ///
+    /// By default, the command names (from [`Execute::commands`]) are completed.
+    ///
     fn completion(
         &self,
-        _ctx: &Self::Context,
-        _incomplete_command: &str,
+        ctx: &Self::Context,
+        incomplete_command: &str,
     ) -> anyhow::Result<(String, Vec<String>)> {
-        // completion + branches
-        Ok((String::new(), Vec::new()))
+        Ok(complete_commands(self.commands(ctx), incomplete_command))
+    }
+
+    ///
+    /// This is the structured completion of the `buffer` (the whole command) at the `cursor` (a
+    /// byte offset into the `buffer`). Unlike [`Execute::completion`], which can only extend the
+    /// text before the cursor, this can replace any range of the buffer, e.g. the whole token
+    /// under the cursor (see [`CompletionResult`]).
+    ///
+    /// By default, the text up to the cursor is completed via [`Execute::completion`], i.e. the
+    /// completion is inserted at the cursor.
+    ///
+    /// ```rust
+    /// use shelgon::command::{self, CommandInput, CompletionResult, Execute, OutputAction};
+    ///
+    /// struct Git;
+    ///
+    /// impl Execute for Git {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     // the branch name under the cursor is replaced, e.g. `mai` with `main`.
+    ///     fn completion_result(
+    ///         &self,
+    ///         _: &Self::Context,
+    ///         buffer: &str,
+    ///         cursor: usize,
+    ///     ) -> anyhow::Result<CompletionResult> {
+    ///         let start = buffer[..cursor].rfind(' ').map_or(0, |space| space + 1);
+    ///         let end = buffer[cursor..].find(' ').map_or(buffer.len(), |space| cursor + space);
+    ///         let insert = match "main".starts_with(&buffer[start..end]) {
+    ///             true => "main".to_string(),
+    ///             false => buffer[start..end].to_string(),
+    ///         };
+    ///         Ok(CompletionResult { range: start..end, insert, candidates: Vec::new() })
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::Exit)
+    ///     }
+    /// }
+    ///
+    /// // the cursor is on `mai`, in the middle of the command.
+    /// let buffer = "git log mai --oneline";
+    /// let result = Git.completion_result(&(), buffer, 9)?;
+    /// assert_eq!(result.apply(buffer), Some(("git log main --oneline".to_string(), 12)));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    fn completion_result(
+        &self,
+        ctx: &Self::Context,
+        buffer: &str,
+        cursor: usize,
+    ) -> anyhow::Result<CompletionResult> {
+        let (insert, candidates) = self.completion(ctx, &buffer[..cursor])?;
+        Ok(CompletionResult {
+            range: cursor..cursor,
+            insert,
+            candidates,
+        })
+    }
+
+    ///
+    /// These are the names of the commands that are supported. This is optional, and can be left
+    /// empty.
+    ///
+    /// When provided, the default [`Execute::completion`] completes the command names, and the
+    /// renderer ranks the more frequently executed commands higher in the completion candidates.
+    ///
+    fn commands(&self, _ctx: &Self::Context) -> Vec<String> {
+        Vec::new()
     }
 
     ///
@@ -194,11 +990,549 @@ pub trait Execute {
     /// This can be used to check if the command requires stdin, and prompt the user for input.
     ///
     fn prepare(&self, cmd: &str) -> Prepare;
+
+    ///
+    /// This is the preview. This is rendered (dimmed) below the command, as it is being typed.
+    /// This is optional, and is only used when enabled on the renderer (see
+    /// [`crate::App::with_preview`]).
+    ///
+    /// This can be used to give live feedback without executing the command, e.g. showing what
+    /// a command with substitutions expands to.
+    ///
+    fn preview(&self, _ctx: &Self::Context, _buffer: &str) -> Option<Vec<Line<'static>>> {
+        None
+    }
+
+    ///
+    /// This is the inline help. This is rendered below the command, as it is being typed. This is
+    /// optional, and is only used when enabled on the renderer (see
+    /// [`crate::App::with_inline_help`]).
+    ///
+    /// Unlike the completion, this documents the command that is being typed, e.g. its usage and
+    /// its options. The help is rendered as is, so it can be styled.
+    ///
+    fn help(&self, _ctx: &Self::Context, _buffer: &str) -> Option<Vec<Line<'static>>> {
+        None
+    }
+
+    ///
+    /// This is the stdin status. This is rendered below the stdin lines, while the stdin of the
+    /// command (`prepare`) is being collected. This is optional, and nothing is rendered by
+    /// default.
+    ///
+    /// This can be used to show a hint, or the number of lines that have been collected so far.
+    ///
+    fn stdin_status(
+        &self,
+        _ctx: &Self::Context,
+        _prepare: &Prepare,
+        _collected: &[String],
+    ) -> Option<Line<'static>> {
+        None
+    }
+
+    ///
+    /// This is the stdin completion. This is called when the user presses the `Tab` key, while
+    /// the stdin of the command (`prepare`) is being collected. This is optional, and nothing is
+    /// completed by default.
+    ///
+    /// This works the same way as [`Execute::completion`], for the stdin line that is being typed
+    /// (`partial`): the deterministic completion is appended to the line, and the
+    /// non-deterministic completion is shown below it.
+    ///
+    fn stdin_completion(
+        &self,
+        _ctx: &Self::Context,
+        _prepare: &Prepare,
+        _partial: &str,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        Ok((String::new(), Vec::new()))
+    }
+    ///
+    /// This is the transform method. This is called for every stdout and stderr line of the
+    /// history, right before it is rendered. This is optional, and renders the line as is by
+    /// default.
+    ///
+    /// This can be used for cross-cutting transformations of the output, like redacting secrets
+    /// or highlighting matches. The stderr lines are additionally styled as errors, unless the
+    /// returned spans specify their own color.
+    ///
+    fn transform_output(&self, line: &str) -> Line<'static> {
+        Line::raw(line.to_string())
+    }
+
+    ///
+    /// This is the exit check. This is called before the shell exits, either with `Ctrl+C` /
+    /// `Ctrl+D` or with [`OutputAction::Exit`]. Returning `false` cancels the exit, and the shell
+    /// keeps running. This is optional, and always allows the exit by default.
+    ///
+    /// This can be used to keep the shell from exiting with unsaved state.
+    ///
+    fn should_exit(&self, _ctx: &Self::Context) -> bool {
+        true
+    }
+
+    ///
+    /// This is the clear check. This is called before the history is cleared, either with
+    /// `Ctrl+L` or with [`OutputAction::Clear`]. Returning `false` cancels the clear. This is
+    /// optional, and always allows the clear by default.
+    ///
+    fn should_clear(&self, _ctx: &Self::Context) -> bool {
+        true
+    }
+
+    ///
+    /// This is the command-not-found handler. This is called when [`Execute::execute`] returns
+    /// [`OutputAction::NotFound`], with the unknown `command`, and the returned action is performed
+    /// instead. This is optional, and renders `<name>: command not found` (as the stderr) by
+    /// default, or when `None` is returned.
+    ///
+    /// This can be used to present the unknown commands uniformly, e.g. with suggestions.
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Git;
+    ///
+    /// impl Execute for Git {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::NotFound)
+    ///     }
+    ///
+    ///     fn not_found(&self, ctx: &Self::Context, command: &str) -> Option<OutputAction> {
+    ///         Some(OutputAction::Command(CommandOutput {
+    ///             prompt: self.prompt(ctx),
+    ///             command: command.to_string(),
+    ///             stderr: vec![format!("git: '{}' is not a git command", command)],
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Git, ());
+    /// app.run_command("pul", None)?;
+    ///
+    /// assert_eq!(app.history()[0].stderr, ["git: 'pul' is not a git command"]);
+    /// // the stderr is rendered in red.
+    /// assert!(app.transcript_html().contains("is not a git command</span>"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    fn not_found(&self, _ctx: &Self::Context, _command: &str) -> Option<OutputAction> {
+        None
+    }
+
+    ///
+    /// This is the stdin preprocessing. This is called with the stdin that is collected for the
+    /// command (or passed to [`crate::App::run_command`]), before the command is executed. This is
+    /// optional, and keeps the stdin as is by default.
+    ///
+    /// This can be used to clean up the stdin (e.g. trimming it, or decoding it), so that every
+    /// command receives it in the same shape. The preprocessed stdin is recorded in the history.
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Cat;
+    ///
+    /// impl Execute for Cat {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: true,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     // the trailing blank lines are stripped.
+    ///     fn preprocess_stdin(&self, _: &Self::Context, mut stdin: Vec<String>) -> Vec<String> {
+    ///         while stdin.last().is_some_and(|line| line.trim().is_empty()) {
+    ///             stdin.pop();
+    ///         }
+    ///         stdin
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             stdout: input.stdin.unwrap_or_default(),
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Cat, ());
+    /// let stdin = ["hello", "", "world", "", " "].map(String::from).to_vec();
+    /// app.run_command("cat", Some(stdin))?;
+    ///
+    /// assert_eq!(app.history()[0].stdout, ["hello", "", "world"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    ///
+    fn preprocess_stdin(&self, _ctx: &Self::Context, stdin: Vec<String>) -> Vec<String> {
+        stdin
+    }
+
     ///
     /// This is the execute method. This is called to execute the command. This is where the
     /// command is executed. This is where the command is executed, and the output is returned.
     ///
     fn execute(&self, ctx: &mut Self::Context, cmd: CommandInput) -> anyhow::Result<OutputAction>;
+
+    ///
+    /// This is the streaming variant of [`Execute::execute`], which is what the renderer calls.
+    /// The lines written to the `writer` are rendered live, as the output of the command. This is
+    /// optional, and executes the command via [`Execute::execute`] by default.
+    ///
+    /// The lines are rendered while the output is pending, so the streaming commands are expected
+    /// to return [`OutputAction::Pending`], writing the lines (e.g. from a spawned task) until
+    /// the output is resolved. (see [`OutputWriter`])
+    ///
+    fn execute_streaming(
+        &self,
+        ctx: &mut Self::Context,
+        cmd: CommandInput,
+        _writer: OutputWriter,
+    ) -> anyhow::Result<OutputAction> {
+        self.execute(ctx, cmd)
+    }
+}
+
+///
+/// [`TypedExecute`] is an alternative to [`Execute`], for executors that have a domain-specific
+/// error type, instead of [`anyhow::Error`].
+///
+/// Every [`TypedExecute`] is an [`Execute`] (via a blanket implementation), so it can be used
+/// wherever an [`Execute`] is expected. The errors returned by [`TypedExecute::execute`] are not
+/// fatal: they are rendered (via [`std::fmt::Display`]) as the stderr of the command. The errors
+/// returned by the other methods are wrapped in [`anyhow::Error`], so they can be downcast back.
+///
+/// The methods are the same as the ones of [`Execute`], except for the error type.
+///
+/// # Example
+///
+/// ```rust
+/// use shelgon::command::{self, CommandInput, CommandOutput, OutputAction, TypedExecute};
+///
+/// #[derive(Debug)]
+/// enum DbError {
+///     NoSuchTable(String),
+/// }
+///
+/// impl std::fmt::Display for DbError {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         match self {
+///             DbError::NoSuchTable(table) => write!(f, "no such table: {}", table),
+///         }
+///     }
+/// }
+///
+/// impl std::error::Error for DbError {}
+///
+/// struct Db;
+///
+/// impl TypedExecute for Db {
+///     type Context = ();
+///     type Error = DbError;
+///
+///     fn prompt(&self, _: &Self::Context) -> String {
+///         "db>".to_string()
+///     }
+///
+///     fn prepare(&self, cmd: &str) -> command::Prepare {
+///         command::Prepare {
+///             command: cmd.to_string(),
+///             stdin_required: false,
+///             ..Default::default()
+///         }
+///     }
+///
+///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> Result<OutputAction, DbError> {
+///         // `select * from users` renders `no such table: users` as the stderr.
+///         let table = input.command.rsplit(' ').next().unwrap_or_default();
+///         Err(DbError::NoSuchTable(table.to_string()))
+///     }
+/// }
+///
+/// fn assert_execute(_: &impl command::Execute) {}
+///
+/// assert_execute(&Db);
+/// ```
+///
+pub trait TypedExecute {
+    /// The context that is maintained by the `App` struct. (see [`Execute::Context`])
+    type Context;
+
+    /// The error that is returned by the executor. This is rendered via [`std::fmt::Display`].
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// The prompt that is displayed to the user. (see [`Execute::prompt`])
+    fn prompt(&self, ctx: &Self::Context) -> String;
+
+    /// The prompt symbol, e.g. `#` for root. (see [`Execute::prompt_symbol`])
+    fn prompt_symbol(&self, _ctx: &Self::Context) -> Option<char> {
+        None
+    }
+
+    /// The segments of the prompt context, e.g. the working directory. (see
+    /// [`Execute::prompt_context`])
+    fn prompt_context(&self, _ctx: &Self::Context) -> Vec<(String, Style)> {
+        Vec::new()
+    }
+
+    /// The completion of the command. (see [`Execute::completion`])
+    fn completion(
+        &self,
+        ctx: &Self::Context,
+        incomplete_command: &str,
+    ) -> Result<(String, Vec<String>), Self::Error> {
+        Ok(complete_commands(self.commands(ctx), incomplete_command))
+    }
+
+    /// The structured completion of the command at the cursor. (see
+    /// [`Execute::completion_result`])
+    fn completion_result(
+        &self,
+        ctx: &Self::Context,
+        buffer: &str,
+        cursor: usize,
+    ) -> Result<CompletionResult, Self::Error> {
+        let (insert, candidates) = self.completion(ctx, &buffer[..cursor])?;
+        Ok(CompletionResult {
+            range: cursor..cursor,
+            insert,
+            candidates,
+        })
+    }
+
+    /// The names of the commands that are supported. (see [`Execute::commands`])
+    fn commands(&self, _ctx: &Self::Context) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Prepare the command for execution. (see [`Execute::prepare`])
+    fn prepare(&self, cmd: &str) -> Prepare;
+
+    /// The preview of the command, as it is being typed. (see [`Execute::preview`])
+    fn preview(&self, _ctx: &Self::Context, _buffer: &str) -> Option<Vec<Line<'static>>> {
+        None
+    }
+
+    /// The help of the command, as it is being typed. (see [`Execute::help`])
+    fn help(&self, _ctx: &Self::Context, _buffer: &str) -> Option<Vec<Line<'static>>> {
+        None
+    }
+
+    /// The status rendered while the stdin is being collected. (see [`Execute::stdin_status`])
+    fn stdin_status(
+        &self,
+        _ctx: &Self::Context,
+        _prepare: &Prepare,
+        _collected: &[String],
+    ) -> Option<Line<'static>> {
+        None
+    }
+
+    /// The completion of the stdin line. (see [`Execute::stdin_completion`])
+    fn stdin_completion(
+        &self,
+        _ctx: &Self::Context,
+        _prepare: &Prepare,
+        _partial: &str,
+    ) -> Result<(String, Vec<String>), Self::Error> {
+        Ok((String::new(), Vec::new()))
+    }
+
+    /// Transform the output line, before it is rendered. (see [`Execute::transform_output`])
+    fn transform_output(&self, line: &str) -> Line<'static> {
+        Line::raw(line.to_string())
+    }
+
+    /// Whether the shell may exit. (see [`Execute::should_exit`])
+    fn should_exit(&self, _ctx: &Self::Context) -> bool {
+        true
+    }
+
+    /// Whether the history may be cleared. (see [`Execute::should_clear`])
+    fn should_clear(&self, _ctx: &Self::Context) -> bool {
+        true
+    }
+
+    /// The output for an unknown command. (see [`Execute::not_found`])
+    fn not_found(&self, _ctx: &Self::Context, _command: &str) -> Option<OutputAction> {
+        None
+    }
+
+    /// Preprocess the stdin, before the command is executed. (see [`Execute::preprocess_stdin`])
+    fn preprocess_stdin(&self, _ctx: &Self::Context, stdin: Vec<String>) -> Vec<String> {
+        stdin
+    }
+
+    /// Execute the command. (see [`Execute::execute`])
+    fn execute(
+        &self,
+        ctx: &mut Self::Context,
+        cmd: CommandInput,
+    ) -> Result<OutputAction, Self::Error>;
+
+    /// Execute the command, streaming the output. (see [`Execute::execute_streaming`])
+    fn execute_streaming(
+        &self,
+        ctx: &mut Self::Context,
+        cmd: CommandInput,
+        _writer: OutputWriter,
+    ) -> Result<OutputAction, Self::Error> {
+        self.execute(ctx, cmd)
+    }
+}
+
+impl<T: TypedExecute> Execute for T {
+    type Context = T::Context;
+
+    fn prompt(&self, ctx: &Self::Context) -> String {
+        TypedExecute::prompt(self, ctx)
+    }
+
+    fn prompt_symbol(&self, ctx: &Self::Context) -> Option<char> {
+        TypedExecute::prompt_symbol(self, ctx)
+    }
+
+    fn prompt_context(&self, ctx: &Self::Context) -> Vec<(String, Style)> {
+        TypedExecute::prompt_context(self, ctx)
+    }
+
+    fn completion(
+        &self,
+        ctx: &Self::Context,
+        incomplete_command: &str,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        TypedExecute::completion(self, ctx, incomplete_command).map_err(anyhow::Error::new)
+    }
+
+    fn completion_result(
+        &self,
+        ctx: &Self::Context,
+        buffer: &str,
+        cursor: usize,
+    ) -> anyhow::Result<CompletionResult> {
+        TypedExecute::completion_result(self, ctx, buffer, cursor).map_err(anyhow::Error::new)
+    }
+
+    fn commands(&self, ctx: &Self::Context) -> Vec<String> {
+        TypedExecute::commands(self, ctx)
+    }
+
+    fn prepare(&self, cmd: &str) -> Prepare {
+        TypedExecute::prepare(self, cmd)
+    }
+
+    fn preview(&self, ctx: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+        TypedExecute::preview(self, ctx, buffer)
+    }
+
+    fn help(&self, ctx: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+        TypedExecute::help(self, ctx, buffer)
+    }
+
+    fn stdin_status(
+        &self,
+        ctx: &Self::Context,
+        prepare: &Prepare,
+        collected: &[String],
+    ) -> Option<Line<'static>> {
+        TypedExecute::stdin_status(self, ctx, prepare, collected)
+    }
+
+    fn stdin_completion(
+        &self,
+        ctx: &Self::Context,
+        prepare: &Prepare,
+        partial: &str,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        TypedExecute::stdin_completion(self, ctx, prepare, partial).map_err(anyhow::Error::new)
+    }
+
+    fn transform_output(&self, line: &str) -> Line<'static> {
+        TypedExecute::transform_output(self, line)
+    }
+
+    fn should_exit(&self, ctx: &Self::Context) -> bool {
+        TypedExecute::should_exit(self, ctx)
+    }
+
+    fn should_clear(&self, ctx: &Self::Context) -> bool {
+        TypedExecute::should_clear(self, ctx)
+    }
+
+    fn not_found(&self, ctx: &Self::Context, command: &str) -> Option<OutputAction> {
+        TypedExecute::not_found(self, ctx, command)
+    }
+
+    fn preprocess_stdin(&self, ctx: &Self::Context, stdin: Vec<String>) -> Vec<String> {
+        TypedExecute::preprocess_stdin(self, ctx, stdin)
+    }
+
+    fn execute(&self, ctx: &mut Self::Context, cmd: CommandInput) -> anyhow::Result<OutputAction> {
+        let (prompt, command, stdin) = (cmd.prompt.clone(), cmd.command.clone(), cmd.stdin.clone());
+        Ok(TypedExecute::execute(self, ctx, cmd)
+            .unwrap_or_else(|e| error_output(prompt, command, stdin, e)))
+    }
+
+    fn execute_streaming(
+        &self,
+        ctx: &mut Self::Context,
+        cmd: CommandInput,
+        writer: OutputWriter,
+    ) -> anyhow::Result<OutputAction> {
+        let (prompt, command, stdin) = (cmd.prompt.clone(), cmd.command.clone(), cmd.stdin.clone());
+        Ok(TypedExecute::execute_streaming(self, ctx, cmd, writer)
+            .unwrap_or_else(|e| error_output(prompt, command, stdin, e)))
+    }
+}
+
+/// The output that renders the error of the command as the stderr. The error is a result of the
+/// command (see [`TypedExecute`]), so it's rendered rather than ending the shell.
+fn error_output(
+    prompt: String,
+    command: String,
+    stdin: Option<Vec<String>>,
+    error: impl std::error::Error,
+) -> OutputAction {
+    OutputAction::Command(CommandOutput {
+        prompt,
+        command,
+        stdin: stdin.unwrap_or_default(),
+        stdout: Vec::new(),
+        stderr: error.to_string().lines().map(String::from).collect(),
+        ..Default::default()
+    })
 }
 
 ///
@@ -207,7 +1541,339 @@ pub trait Execute {
 ///
 pub trait New: Execute {
     /// This is the new method that is used to create a new instance of the command.
+    ///
+    /// This is called within the runtime of the shell (with the `tokio` feature), so that the
+    /// background tasks can be spawned here, e.g. feeding the shell via a [`Notifier`].
     fn new() -> anyhow::Result<(Self, Self::Context)>
     where
         Self: Sized;
 }
+
+///
+/// [`CompletionResult`] is a completion that replaces the `range` of the command with the
+/// `insert` text, returned by [`Execute::completion_result`]. This allows the completions that
+/// don't just extend the text before the cursor, e.g. replacing the whole token under the cursor.
+///
+/// Once applied, the cursor is placed after the inserted text, and the `candidates` are listed
+/// as the branches to be inserted there, like the ones of [`Execute::completion`].
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionResult {
+    /// The range of the command (in bytes) that is replaced.
+    pub range: std::ops::Range<usize>,
+    /// The text that replaces the range.
+    pub insert: String,
+    /// The candidates that are listed, to be inserted after the inserted text.
+    pub candidates: Vec<String>,
+}
+
+impl CompletionResult {
+    /// Apply the completion to the `buffer`, returning the completed buffer and the cursor, or
+    /// `None` if the range isn't within the buffer (on character boundaries).
+    pub fn apply(&self, buffer: &str) -> Option<(String, usize)> {
+        buffer.get(self.range.clone())?;
+        let mut buffer = buffer.to_string();
+        buffer.replace_range(self.range.clone(), &self.insert);
+        Some((buffer, self.range.start + self.insert.len()))
+    }
+}
+
+///
+/// [`CompletionProvider`] is an additional source of completions, registered via
+/// [`crate::App::with_completion_provider`]. The completions of all the providers, starting with
+/// the executor's own (see [`Execute::completion`]), are merged, and the duplicate candidates are
+/// removed.
+///
+/// This allows composing the completion sources (e.g. the command names, the file paths, the
+/// variables) without folding all of them into a single [`Execute::completion`].
+///
+#[cfg_attr(feature = "tokio", doc = "```rust")]
+#[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+/// use shelgon::command::{self, CommandInput, CompletionProvider, Execute, OutputAction};
+/// use shelgon::App;
+///
+/// struct Shell;
+///
+/// impl Execute for Shell {
+///     type Context = ();
+///
+///     fn prompt(&self, _: &Self::Context) -> String {
+///         "$".to_string()
+///     }
+///
+///     fn commands(&self, _: &Self::Context) -> Vec<String> {
+///         vec!["echo".to_string(), "exit".to_string()]
+///     }
+///
+///     fn prepare(&self, cmd: &str) -> command::Prepare {
+///         command::Prepare {
+///             command: cmd.to_string(),
+///             stdin_required: false,
+///             ..Default::default()
+///         }
+///     }
+///
+///     fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+///         Ok(OutputAction::Exit)
+///     }
+/// }
+///
+/// /// Completes the aliases, along with the commands.
+/// struct Aliases(Vec<String>);
+///
+/// impl CompletionProvider for Aliases {
+///     type Context = ();
+///
+///     fn complete(
+///         &self,
+///         _: &Self::Context,
+///         input: &str,
+///         cursor: usize,
+///     ) -> anyhow::Result<(String, Vec<String>)> {
+///         let typed = &input[..cursor];
+///         let branches = self.0.iter().filter_map(|alias| alias.strip_prefix(typed));
+///         Ok((String::new(), branches.map(String::from).collect()))
+///     }
+/// }
+///
+/// let aliases = Aliases(vec!["edit".to_string(), "echo".to_string()]);
+/// let (_, branches) = aliases.complete(&(), "e", 1)?;
+/// assert_eq!(branches, ["dit", "cho"]);
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+/// // `e<Tab>` lists `echo` and `exit` (of the executor), along with `edit` (`echo` only once).
+/// let app = App::new_with_executor(runtime, Shell, ()).with_completion_provider(aliases);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+pub trait CompletionProvider {
+    /// The context that is maintained by the `App` struct. (see [`Execute::Context`])
+    type Context;
+
+    /// Complete the `input` (the whole command) at the `cursor` (a byte offset into the `input`),
+    /// returning the completion and the branches, as expected from [`Execute::completion`].
+    fn complete(
+        &self,
+        ctx: &Self::Context,
+        input: &str,
+        cursor: usize,
+    ) -> anyhow::Result<(String, Vec<String>)>;
+}
+
+/// Merge the completions (as returned by [`Execute::completion`]) of multiple sources, removing
+/// the duplicate candidates.
+pub(crate) fn merge_completions(completions: Vec<(String, Vec<String>)>) -> (String, Vec<String>) {
+    let mut branches = Vec::<String>::new();
+    for (completion, variable) in completions {
+        let merged = match variable.is_empty() {
+            true => vec![completion]
+                .into_iter()
+                .filter(|branch| !branch.is_empty())
+                .collect(),
+            false => variable
+                .iter()
+                .map(|branch| format!("{}{}", completion, branch))
+                .collect::<Vec<_>>(),
+        };
+        for branch in merged {
+            if !branches.contains(&branch) {
+                branches.push(branch);
+            }
+        }
+    }
+    split_branches(branches)
+}
+
+/// Complete the command name (the first word) from the `commands`, returning the completion and
+/// the remaining branches. (see [`Execute::completion`])
+fn complete_commands(commands: Vec<String>, incomplete_command: &str) -> (String, Vec<String>) {
+    // only the command name (the first word) is completed.
+    if incomplete_command.contains(char::is_whitespace) {
+        return (String::new(), Vec::new());
+    }
+
+    let branches = commands
+        .into_iter()
+        .filter_map(|name| {
+            name.strip_prefix(incomplete_command)
+                .map(ToString::to_string)
+        })
+        .collect::<Vec<_>>();
+
+    split_branches(branches)
+}
+
+///
+/// Complete the file path `input`, relative to `cwd` (unless it's absolute), returning the
+/// completion and the branches, as expected from [`Execute::completion`].
+///
+/// The directories are completed with a trailing `/`. The dotfiles are only completed when the
+/// file name being completed starts with a `.`. Paths that cannot be read have no completions.
+///
+/// ```rust
+/// use shelgon::command::complete_path;
+///
+/// let cwd = std::env::temp_dir().join(format!("shelgon-complete-path-{}", std::process::id()));
+/// std::fs::create_dir_all(cwd.join("src"))?;
+/// std::fs::write(cwd.join("src/main.rs"), "")?;
+/// std::fs::write(cwd.join("src/model.rs"), "")?;
+/// std::fs::write(cwd.join(".env"), "")?;
+///
+/// // a single candidate is completed, directories with a trailing `/`.
+/// assert_eq!(complete_path("s", &cwd), ("rc/".to_string(), vec![]));
+/// // the common prefix is completed, and the rest are the candidates.
+/// assert_eq!(
+///     complete_path("src/m", &cwd),
+///     ("".to_string(), vec!["ain.rs".to_string(), "odel.rs".to_string()])
+/// );
+/// // dotfiles are only completed when asked for.
+/// assert_eq!(complete_path("", &cwd), ("src/".to_string(), vec![]));
+/// assert_eq!(complete_path(".", &cwd), ("env".to_string(), vec![]));
+///
+/// std::fs::remove_dir_all(&cwd)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+pub fn complete_path(input: &str, cwd: &Path) -> (String, Vec<String>) {
+    let (directory, prefix) = match input.rfind('/') {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    // joining an absolute path replaces `cwd`.
+    let Ok(entries) = std::fs::read_dir(cwd.join(directory)) else {
+        return (String::new(), Vec::new());
+    };
+
+    let mut branches = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !prefix.starts_with('.') {
+                return None;
+            }
+            let mut branch = name.strip_prefix(prefix)?.to_string();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                branch.push('/');
+            }
+            Some(branch)
+        })
+        .collect::<Vec<_>>();
+    branches.sort();
+
+    split_branches(branches)
+}
+
+/// Split the branches into the completion (their common prefix), and the rest of the branches.
+/// A single branch is completed entirely.
+fn split_branches(branches: Vec<String>) -> (String, Vec<String>) {
+    let completion = common_prefix(&branches).to_string();
+    let branches = match branches.len() {
+        1 => Vec::new(),
+        _ => branches
+            .iter()
+            .map(|branch| branch[completion.len()..].to_string())
+            .collect(),
+    };
+    (completion, branches)
+}
+
+/// The longest common prefix of the strings.
+fn common_prefix(strings: &[String]) -> &str {
+    let Some((first, rest)) = strings.split_first() else {
+        return "";
+    };
+
+    let length = rest.iter().fold(first.len(), |length, string| {
+        first[..length]
+            .char_indices()
+            .zip(string.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(length.min(string.len()), |((index, _), _)| index)
+    });
+    &first[..length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The errors of a key-value store, with the key that failed.
+    #[derive(Debug, PartialEq)]
+    enum KvError {
+        Missing(String),
+        ReadOnly,
+    }
+
+    impl std::fmt::Display for KvError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                KvError::Missing(key) => write!(f, "key not found: {}\n(try `keys`)", key),
+                KvError::ReadOnly => write!(f, "the store is read-only"),
+            }
+        }
+    }
+
+    impl std::error::Error for KvError {}
+
+    /// A read-only key-value store, with no keys.
+    struct Kv;
+
+    impl TypedExecute for Kv {
+        type Context = ();
+        type Error = KvError;
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "kv>".to_string()
+        }
+
+        fn completion(&self, _: &Self::Context, _: &str) -> Result<(String, Vec<String>), KvError> {
+            Err(KvError::ReadOnly)
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> Result<OutputAction, KvError> {
+            let key = input.command.trim_start_matches("get ");
+            Err(KvError::Missing(key.to_string()))
+        }
+    }
+
+    /// The error of the command is rendered via its `Display` as the stderr, and the errors of the
+    /// other methods keep their type.
+    #[test]
+    fn typed_errors_render_and_downcast() -> anyhow::Result<()> {
+        #[cfg(feature = "tokio")]
+        let runtime = Arc::new(tokio::runtime::Builder::new_current_thread().build()?);
+        let input = CommandInput {
+            prompt: Execute::prompt(&Kv, &()),
+            command: "get color".to_string(),
+            stdin: Some(vec!["ignored".to_string()]),
+            cursor_position: None,
+            keys: Default::default(),
+            #[cfg(feature = "tokio")]
+            handle: runtime.handle().clone(),
+            #[cfg(feature = "tokio")]
+            runtime,
+        };
+        let OutputAction::Command(output) = Execute::execute(&Kv, &mut (), input)? else {
+            anyhow::bail!("the error isn't rendered as the output");
+        };
+        assert_eq!(output.prompt, "kv>");
+        assert_eq!(output.stdin, ["ignored"]);
+        assert_eq!(output.stderr, ["key not found: color", "(try `keys`)"]);
+
+        let error = Execute::completion(&Kv, &(), "ge").err();
+        let error = error.as_ref().and_then(|e| e.downcast_ref::<KvError>());
+        assert_eq!(error, Some(&KvError::ReadOnly));
+        Ok(())
+    }
+}
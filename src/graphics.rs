@@ -0,0 +1,160 @@
+//! Inline images, via the terminal graphics protocols.
+//!
+//! The shell renders text into the cells of the terminal, so the images are shown as a
+//! placeholder line. When a protocol is selected (see [`crate::App::with_graphics_protocol`]), the
+//! escape sequence that displays the image is emitted in the transcript (see
+//! [`crate::App::with_output_tee`]) instead, so that replaying the transcript in a supporting
+//! terminal shows the image.
+
+use crate::renderer::GraphicsProtocol;
+
+/// The size of the chunks that the image is transmitted in, for the Kitty protocol.
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// The signature at the start of every PNG.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The placeholder line that is rendered for the image.
+pub(crate) fn placeholder(png: &[u8]) -> String {
+    match dimensions(png) {
+        Some((width, height)) => format!("[image: {}x{} PNG]", width, height),
+        None => format!("[image: {} bytes]", png.len()),
+    }
+}
+
+/// The escape sequence that displays the image with the `protocol`, if any.
+pub(crate) fn escape(protocol: GraphicsProtocol, png: &[u8]) -> Option<String> {
+    let encoded = base64(png);
+    match protocol {
+        GraphicsProtocol::None => None,
+        GraphicsProtocol::Kitty => {
+            // the payload is split in chunks, where all but the last one are marked with `m=1`.
+            let chunks = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE);
+            let count = chunks.len();
+            let escape = chunks
+                .enumerate()
+                .map(|(index, chunk)| {
+                    let more = u8::from(index + 1 < count);
+                    let control = match index {
+                        0 => format!("a=T,f=100,m={}", more),
+                        _ => format!("m={}", more),
+                    };
+                    // infallible: the chunk is base64, which is ASCII.
+                    let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                    format!("\x1b_G{};{}\x1b\\", control, chunk)
+                })
+                .collect();
+            Some(escape)
+        }
+        GraphicsProtocol::Iterm2 => Some(format!(
+            "\x1b]1337;File=inline=1;size={}:{}\x07",
+            png.len(),
+            encoded
+        )),
+    }
+}
+
+/// The dimensions of the PNG (from its header), as `(width, height)`.
+fn dimensions(png: &[u8]) -> Option<(u32, u32)> {
+    // the signature is followed by the `IHDR` chunk: length, type, width, height, ...
+    if !png.starts_with(PNG_SIGNATURE) || png.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(png.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(png.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Encode the bytes as (padded) base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => {
+                    let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                    encoded.push(char::from(ALPHABET[sextet as usize]));
+                }
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of a PNG with the dimensions, followed by `padding` zero bytes.
+    fn png(width: u32, height: u32, padding: usize) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(b"\0\0\0\x0dIHDR");
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png.resize(png.len() + padding, 0);
+        png
+    }
+
+    /// The encoding matches the test vectors of RFC 4648, with the padding.
+    #[test]
+    fn base64_matches_rfc_vectors() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+    }
+
+    /// The placeholder has the dimensions from the header, or only the size if it isn't a PNG.
+    #[test]
+    fn placeholder_reads_dimensions() {
+        assert_eq!(placeholder(&png(640, 480, 0)), "[image: 640x480 PNG]");
+        assert_eq!(placeholder(&png(640, 480, 0)[..20]), "[image: 20 bytes]");
+        assert_eq!(placeholder(b"GIF89a"), "[image: 6 bytes]");
+        assert_eq!(escape(GraphicsProtocol::None, &png(1, 1, 0)), None);
+    }
+
+    /// The Kitty payload is split in chunks of 4096 bytes, where only the first one has the
+    /// format, and only the last one isn't marked as having more.
+    #[test]
+    fn kitty_escape_is_chunked() {
+        let png = png(2, 3, 7000);
+        let encoded = base64(&png);
+        let escape = escape(GraphicsProtocol::Kitty, &png).unwrap_or_default();
+
+        let chunks = escape
+            .strip_prefix("\x1b_G")
+            .unwrap_or_default()
+            .split("\x1b\\\x1b_G")
+            .map(|chunk| chunk.trim_end_matches("\x1b\\"))
+            .filter_map(|chunk| chunk.split_once(';'))
+            .collect::<Vec<_>>();
+        let controls = chunks
+            .iter()
+            .map(|(control, _)| *control)
+            .collect::<Vec<_>>();
+        assert_eq!(controls, ["a=T,f=100,m=1", "m=1", "m=0"]);
+        let lengths = chunks
+            .iter()
+            .map(|(_, chunk)| chunk.len())
+            .collect::<Vec<_>>();
+        assert_eq!(lengths, [4096, 4096, encoded.len() - 8192]);
+        assert_eq!(
+            chunks.iter().map(|(_, chunk)| *chunk).collect::<String>(),
+            encoded
+        );
+    }
+}
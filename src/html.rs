@@ -0,0 +1,205 @@
+//! Export of the rendered history as HTML, with the styles inlined.
+//!
+//! Every span of the rendered lines becomes a `<span>` with an inline `style` (e.g. the stderr in
+//! red, or the highlighted search matches), inside a `<pre>` block, so that the document can be
+//! viewed without any stylesheet. (see [`crate::App::transcript_html`])
+
+use std::fmt::Write;
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+
+/// The colors of the terminal that are assumed for the reversed spans without colors.
+const DEFAULT_FOREGROUND: &str = "black";
+/// (see [`DEFAULT_FOREGROUND`])
+const DEFAULT_BACKGROUND: &str = "white";
+
+/// The HTML document of the lines.
+pub(crate) fn document(lines: &[Line<'_>]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n<body>\n<pre>\n",
+    );
+    for line in lines {
+        for span in &line.spans {
+            let style = css(line.style.patch(span.style));
+            match style.is_empty() {
+                true => escape(&mut html, &span.content),
+                false => {
+                    // infallible: writing to a `String` never fails.
+                    let _ = write!(html, "<span style=\"{}\">", style);
+                    escape(&mut html, &span.content);
+                    html.push_str("</span>");
+                }
+            }
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+/// The inline CSS of the style, e.g. `color: #cd0000; font-weight: bold`.
+fn css(style: Style) -> String {
+    let mut foreground = style.fg.and_then(css_color);
+    let mut background = style.bg.and_then(css_color);
+    if style.add_modifier.contains(Modifier::REVERSED) {
+        (foreground, background) = (
+            Some(background.unwrap_or_else(|| DEFAULT_BACKGROUND.to_string())),
+            Some(foreground.unwrap_or_else(|| DEFAULT_FOREGROUND.to_string())),
+        );
+    }
+
+    let mut properties = Vec::new();
+    if let Some(foreground) = foreground {
+        properties.push(format!("color: {}", foreground));
+    }
+    if let Some(background) = background {
+        properties.push(format!("background-color: {}", background));
+    }
+    for (modifier, property) in [
+        (Modifier::BOLD, "font-weight: bold"),
+        (Modifier::DIM, "opacity: 0.5"),
+        (Modifier::ITALIC, "font-style: italic"),
+    ] {
+        if style.add_modifier.contains(modifier) {
+            properties.push(property.to_string());
+        }
+    }
+    // the decorations are a single property, so that the underline and the strikethrough combine.
+    let decorations = [
+        (Modifier::UNDERLINED, "underline"),
+        (Modifier::CROSSED_OUT, "line-through"),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| style.add_modifier.contains(*modifier))
+    .map(|(_, decoration)| decoration)
+    .collect::<Vec<_>>();
+    if !decorations.is_empty() {
+        properties.push(format!("text-decoration: {}", decorations.join(" ")));
+    }
+    properties.join("; ")
+}
+
+/// The CSS color of the terminal color, if it isn't the default color.
+fn css_color(color: Color) -> Option<String> {
+    let (r, g, b) = rgb(color)?;
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// The RGB of the terminal color (with the xterm palette), if it isn't the default color.
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    let rgb = match color {
+        Color::Reset => return None,
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White => (255, 255, 255),
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) => return indexed(index),
+    };
+    Some(rgb)
+}
+
+/// The RGB of the color in the 256 color palette.
+fn indexed(index: u8) -> Option<(u8, u8, u8)> {
+    /// The named colors, in the order of their indices.
+    const NAMED: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ];
+    /// The levels of each component in the 6x6x6 color cube.
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match index {
+        0..=15 => NAMED.get(usize::from(index)).copied().and_then(rgb),
+        16..=231 => {
+            let index = usize::from(index - 16);
+            Some((LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6]))
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            Some((level, level, level))
+        }
+    }
+}
+
+/// Write the text, escaping the HTML special characters.
+fn escape(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::Stylize;
+    use ratatui::text::Span;
+
+    use super::*;
+
+    /// The underline and the strikethrough are combined in a single property, along with the
+    /// other modifiers, and either one alone is kept as is.
+    #[test]
+    fn decorations_combine() {
+        let both = Style::default().underlined().crossed_out().bold();
+        assert_eq!(
+            css(both),
+            "font-weight: bold; text-decoration: underline line-through"
+        );
+        assert_eq!(
+            css(Style::default().underlined()),
+            "text-decoration: underline"
+        );
+        assert_eq!(
+            css(Style::default().crossed_out().fg(Color::Indexed(196))),
+            "color: #ff0000; text-decoration: line-through"
+        );
+        assert_eq!(css(Style::default()), "");
+    }
+
+    /// The spans are escaped, and wrapped with their style (patched over the style of the line).
+    #[test]
+    fn document_inlines_styles() {
+        let lines = [
+            Line::from(vec![Span::raw("a <b>").italic(), Span::raw(" & c")]),
+            Line::from("plain").style(Style::default().reversed()),
+        ];
+        let html = document(&lines);
+        assert!(html.contains(
+            "<span style=\"font-style: italic\">a &lt;b&gt;</span> &amp; c\n\
+             <span style=\"color: white; background-color: black\">plain</span>\n"
+        ));
+    }
+}
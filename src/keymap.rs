@@ -0,0 +1,272 @@
+//! Key bindings of the shell.
+//!
+//! Every key press (other than typing text) is looked up in the [`KeyMap`], and the [`Action`]
+//! that it's bound to is performed. The key map is configured via
+//! [`crate::App::with_key_map`], starting from the default bindings (see [`KeyMap::default`]).
+//!
+//! # Example
+//!
+//! ```rust
+//! use crossterm::event::{KeyCode, KeyModifiers};
+//! use shelgon::keymap::{Action, KeyMap};
+//!
+//! // `Ctrl+K` clears the screen, instead of `Ctrl+L`.
+//! let key_map = KeyMap::default()
+//!     .unbind(KeyCode::Char('l'), KeyModifiers::CONTROL)
+//!     .bind(KeyCode::Char('k'), KeyModifiers::CONTROL, Action::Clear);
+//!
+//! assert_eq!(
+//!     key_map.action(KeyCode::Char('k'), KeyModifiers::CONTROL),
+//!     Some(Action::Clear)
+//! );
+//! assert_eq!(key_map.action(KeyCode::Char('l'), KeyModifiers::CONTROL), None);
+//! ```
+
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+///
+/// [`Action`] is what the shell does when a key that is bound to it is pressed.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Execute the command (or apply the selected completion), or add a stdin line.
+    Submit,
+    /// Complete the command, or select the next completion candidate.
+    Complete,
+    /// Select the previous completion candidate.
+    CompletePrevious,
+    /// Dismiss the completions.
+    Dismiss,
+    /// Delete the character before the cursor.
+    Backspace,
+    /// Move the cursor to the left.
+    CursorLeft,
+    /// Move the cursor to the right.
+    CursorRight,
+    /// Move the cursor to the start of the command.
+    CursorHome,
+    /// Move the cursor to the end of the command.
+    CursorEnd,
+    /// Recall the previous command from the history.
+    HistoryPrevious,
+    /// Navigate back down from a recalled command (see [`crate::App::with_history_recall_mode`]).
+    HistoryNext,
+    /// Scroll the history up by a page.
+    ScrollUp,
+    /// Scroll the history down by a page.
+    ScrollDown,
+    /// Highlight the typed text in the history (or clear the highlight, if nothing is typed).
+    Highlight,
+    /// Clear the screen.
+    Clear,
+    /// Expand the most recent collapsed output (see [`crate::App::with_auto_collapse_threshold`]).
+    Expand,
+    /// Switch to the next group of outputs (see [`crate::App::with_groups`]).
+    NextGroup,
+    /// Cancel the output that is being revealed or pending, end the stdin collection (see
+    /// [`crate::App::with_stdin_interrupt`]), or exit the shell.
+    Interrupt,
+    /// End the stdin collection (see [`crate::App::with_stdin_eof`]), or exit the shell.
+    Eof,
+}
+
+impl Action {
+    /// The label of the action, as shown in the key hints. (see [`crate::App::with_key_hints`])
+    fn label(self) -> &'static str {
+        match self {
+            Action::Submit => "execute",
+            Action::Complete => "complete",
+            Action::CompletePrevious => "previous",
+            Action::Dismiss => "dismiss",
+            Action::Backspace => "delete",
+            Action::CursorLeft => "left",
+            Action::CursorRight => "right",
+            Action::CursorHome => "home",
+            Action::CursorEnd => "end",
+            Action::HistoryPrevious => "history",
+            Action::HistoryNext => "next",
+            Action::ScrollUp => "scroll up",
+            Action::ScrollDown => "scroll down",
+            Action::Highlight => "highlight",
+            Action::Clear => "clear",
+            Action::Expand => "expand",
+            Action::NextGroup => "group",
+            Action::Interrupt => "cancel",
+            Action::Eof => "exit",
+        }
+    }
+}
+
+/// The actions that are summarized in the key hints, in order.
+const HINTED: [Action; 6] = [
+    Action::Clear,
+    Action::Eof,
+    Action::Interrupt,
+    Action::Complete,
+    Action::HistoryPrevious,
+    Action::Highlight,
+];
+
+///
+/// [`KeyMap`] maps the keys (along with their modifiers) to the [`Action`]s.
+///
+/// Any number of keys can be bound to the same action, but a key is bound to a single action.
+/// The keys that aren't bound to an action insert their character (if any) into the command.
+///
+/// ```rust
+/// use crossterm::event::{KeyCode, KeyModifiers};
+/// use shelgon::keymap::{Action, KeyMap};
+///
+/// // both `Ctrl+C` and `Ctrl+G` cancel.
+/// let key_map =
+///     KeyMap::default().bind(KeyCode::Char('g'), KeyModifiers::CONTROL, Action::Interrupt);
+///
+/// for key in ['c', 'g'] {
+///     assert_eq!(
+///         key_map.action(KeyCode::Char(key), KeyModifiers::CONTROL),
+///         Some(Action::Interrupt)
+///     );
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    /// The action that each key is bound to.
+    bindings: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl KeyMap {
+    /// Create an empty [`KeyMap`], without any bindings.
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Bind the key (with the `modifiers`) to the `action`, replacing its previous binding.
+    pub fn bind(mut self, code: KeyCode, modifiers: KeyModifiers, action: Action) -> Self {
+        self.bindings.insert((code, modifiers), action);
+        self
+    }
+
+    /// Remove the binding of the key (with the `modifiers`), if any.
+    pub fn unbind(mut self, code: KeyCode, modifiers: KeyModifiers) -> Self {
+        self.bindings.remove(&(code, modifiers));
+        self
+    }
+
+    /// The action that the key (with the `modifiers`) is bound to, if any.
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        self.bindings.get(&(code, modifiers)).copied()
+    }
+
+    /// The summary of the bindings, e.g. `^L clear  ^D exit  Tab complete`. The keys bound to the
+    /// same action are joined with `/`.
+    pub(crate) fn hints(&self) -> String {
+        HINTED
+            .iter()
+            .filter_map(|action| {
+                let keys = self.keys(*action)?;
+                Some(format!("{} {}", keys, action.label()))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// The keys bound to the action, joined with `/` (e.g. `^C/^G`), if any.
+    pub(crate) fn keys(&self, action: Action) -> Option<String> {
+        let mut keys = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .filter_map(|((code, modifiers), _)| key_label(*code, *modifiers))
+            .collect::<Vec<_>>();
+        keys.sort();
+        (!keys.is_empty()).then(|| keys.join("/"))
+    }
+}
+
+impl Default for KeyMap {
+    /// The default bindings of the shell.
+    fn default() -> Self {
+        let none = KeyModifiers::NONE;
+        let control = KeyModifiers::CONTROL;
+        KeyMap::new()
+            .bind(KeyCode::Enter, none, Action::Submit)
+            .bind(KeyCode::Tab, none, Action::Complete)
+            // terminals report `Shift+Tab` as `BackTab`, with or without the `Shift` modifier.
+            .bind(KeyCode::BackTab, none, Action::CompletePrevious)
+            .bind(
+                KeyCode::BackTab,
+                KeyModifiers::SHIFT,
+                Action::CompletePrevious,
+            )
+            .bind(KeyCode::Esc, none, Action::Dismiss)
+            .bind(KeyCode::Backspace, none, Action::Backspace)
+            .bind(KeyCode::Left, none, Action::CursorLeft)
+            .bind(KeyCode::Right, none, Action::CursorRight)
+            .bind(KeyCode::Home, none, Action::CursorHome)
+            .bind(KeyCode::End, none, Action::CursorEnd)
+            .bind(KeyCode::Up, none, Action::HistoryPrevious)
+            .bind(KeyCode::Down, none, Action::HistoryNext)
+            .bind(KeyCode::PageUp, none, Action::ScrollUp)
+            .bind(KeyCode::PageDown, none, Action::ScrollDown)
+            .bind(KeyCode::Char('f'), control, Action::Highlight)
+            .bind(KeyCode::Char('l'), control, Action::Clear)
+            .bind(KeyCode::Char('o'), control, Action::Expand)
+            .bind(KeyCode::Char('t'), control, Action::NextGroup)
+            .bind(KeyCode::Char('c'), control, Action::Interrupt)
+            .bind(KeyCode::Char('d'), control, Action::Eof)
+    }
+}
+
+/// The label of the key, as shown in the key hints (e.g. `^L` or `Tab`). The keys with
+/// modifiers other than `Ctrl` and `Shift` are spelled out (e.g. `Alt+x`).
+fn key_label(code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+    let key = match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => "S-Tab".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Backspace => "Bksp".to_string(),
+        KeyCode::Delete => "Del".to_string(),
+        KeyCode::Insert => "Ins".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDn".to_string(),
+        _ => return None,
+    };
+
+    let label = match modifiers {
+        KeyModifiers::NONE => key,
+        KeyModifiers::SHIFT if code == KeyCode::BackTab => key,
+        KeyModifiers::CONTROL => format!("^{}", key.to_uppercase()),
+        modifiers => {
+            let mut label = String::new();
+            for (modifier, name) in [
+                (KeyModifiers::CONTROL, "Ctrl"),
+                (KeyModifiers::ALT, "Alt"),
+                (KeyModifiers::SHIFT, "Shift"),
+                (KeyModifiers::SUPER, "Super"),
+            ] {
+                if modifiers.contains(modifier) {
+                    label.push_str(name);
+                    label.push('+');
+                }
+            }
+            label.push_str(&key);
+            label
+        }
+    };
+    Some(label)
+}
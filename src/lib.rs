@@ -35,6 +35,7 @@
 //!         command::Prepare {
 //!             command: cmd.to_string(),
 //!             stdin_required: false,
+//!             ..Default::default()
 //!         }
 //!     }
 //!
@@ -49,6 +50,7 @@
 //!             stdin: Vec::new(),
 //!             stdout: vec![input.command],
 //!             stderr: Vec::new(),
+//!             ..Default::default()
 //!         }))
 //!     }
 //! }
@@ -90,7 +92,7 @@
 //!     // ... other required methods
 //!     # fn prompt(&self, _: &Self::Context) -> String { "$ ".to_string() }
 //!     # fn prepare(&self, cmd: &str) -> command::Prepare {
-//!     #     command::Prepare { command: cmd.to_string(), stdin_required: false }
+//!     #     command::Prepare { command: cmd.to_string(), stdin_required: false, ..Default::default() }
 //!     # }
 //! }
 //! ```
@@ -117,7 +119,7 @@
 //! #     type Context = ();
 //! #     fn prompt(&self, _: &Self::Context) -> String { "$ ".to_string() }
 //! #     fn prepare(&self, cmd: &str) -> command::Prepare {
-//! #         command::Prepare { command: cmd.to_string(), stdin_required: false }
+//! #         command::Prepare { command: cmd.to_string(), stdin_required: false, ..Default::default() }
 //! #     }
 //! async fn fetch_data() -> anyhow::Result<String> {
 //!     // Async operations
@@ -139,10 +141,14 @@
 //!
 //! - [`command`]: Core traits and types for command execution
 //! - [`renderer`]: Terminal UI and application state management
+//! - [`routing`]: Composition of multiple executors, routed by command prefix
+//! - [`commands!`]: Executors defined from a table of commands
 //!
 //! ## Features
 //!
 //! - `tokio`: Enables async runtime support (enabled by default)
+//! - `serde`: Enables the (de)serialization of [`CommandOutput`], and persisting the session to a
+//!   file (see `App::with_session_file`)
 //!
 //! ## Shell Capabilities
 //!
@@ -162,16 +168,42 @@
 //! - `Ctrl+L`: Clear screen
 //! - `Ctrl+C/Ctrl+D`: Exit shell
 //! - `Left/Right`: Move cursor
-//! - `Tab`: Command completion
-//! - `Enter`: Execute command or add STDIN line
+//! - `Home/End`: Move cursor to the start/end of the command
+//! - `Tab`: Command completion (cycles through the candidates, once shown)
+//! - `Shift+Tab`: Cycle back through the completion candidates
+//! - `Esc`: Dismiss completions
+//! - `Up`: Recall the previous command, walking back through the history (or, with the prefix
+//!   search, through the commands matching the typed text)
+//! - `Down`: Walk forward from a recalled command, back to the command being typed
+//! - `PageUp/PageDown`: Scroll through the history
+//! - `Ctrl+F`: Highlight the typed text in the history
+//! - `Ctrl+O`: Expand the most recent collapsed output
+//! - `Ctrl+T`: Switch to the next group of outputs
+//! - `Enter`: Execute command (or apply the selected completion) or add STDIN line
 //! - `Backspace`: Delete character
 //!
+//! The bindings can be changed via [`App::with_key_map`] (see [`keymap`]).
+//!
 //! ## License
 //!
 //! This project is licensed under the MIT License - see the [LICENSE](./LICENSE) file for details.
 
 pub mod command;
+mod graphics;
+mod html;
+pub mod keymap;
+mod macros;
+mod record;
 pub mod renderer;
+pub mod routing;
+#[cfg(feature = "serde")]
+mod session;
 
 pub use command::*;
 pub use renderer::App;
+
+/// The re-exports used by the macros. (not part of the public API)
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+}
@@ -0,0 +1,123 @@
+//! Macros for reducing the boilerplate of executors.
+
+///
+/// [`commands!`](crate::commands) defines an executor (a unit struct implementing
+/// [`Execute`](crate::command::Execute)) from a table of commands, mapping each command name to its
+/// handler.
+///
+/// The command is dispatched on its name (the first word) to the handler, which is a closure
+/// (without captures) taking the context and the [`CommandInput`](crate::command::CommandInput),
+/// like [`Execute::execute`](crate::command::Execute::execute). The command names are completed
+/// (see [`Execute::commands`](crate::command::Execute::commands)), and the unknown commands fail
+/// with `unknown command: <name>`.
+///
+/// The names are matched as string patterns, so a duplicated name is reported by the compiler (as
+/// an unreachable pattern).
+///
+/// # Example
+///
+#[cfg_attr(feature = "tokio", doc = "```rust")]
+#[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+/// use std::sync::Arc;
+///
+/// use shelgon::command::{CommandInput, CommandOutput, Execute, OutputAction};
+///
+/// shelgon::commands! {
+///     /// A counter, with the commands to change it.
+///     struct Counter {
+///         type Context = i64;
+///         prompt = "#";
+///
+///         "add" => |count, input| {
+///             *count += 1;
+///             Ok(OutputAction::Command(CommandOutput {
+///                 prompt: input.prompt,
+///                 command: input.command,
+///                 stdout: vec![count.to_string()],
+///                 ..Default::default()
+///             }))
+///         },
+///         "reset" => |count, _| {
+///             *count = 0;
+///             Ok(OutputAction::Clear)
+///         },
+///     }
+/// }
+///
+/// let runtime = Arc::new(tokio::runtime::Builder::new_current_thread().build()?);
+/// let input = |command: &str| CommandInput {
+///     prompt: "#".to_string(),
+///     command: command.to_string(),
+///     stdin: None,
+///     cursor_position: None,
+///     keys: Default::default(),
+///     runtime: runtime.clone(),
+///     handle: runtime.handle().clone(),
+/// };
+///
+/// let mut count = 41;
+/// let Ok(OutputAction::Command(output)) = Counter.execute(&mut count, input("add")) else {
+///     panic!("expected the output of `add`");
+/// };
+/// assert_eq!(output.stdout, vec!["42"]);
+/// assert!(Counter.execute(&mut count, input("remove")).is_err());
+///
+/// assert_eq!(Counter.completion(&count, "re")?, ("set".to_string(), Vec::new()));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+#[macro_export]
+macro_rules! commands {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            type Context = $context:ty;
+            prompt = $prompt:expr;
+
+            $($command:literal => $handler:expr),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::command::Execute for $name {
+            type Context = $context;
+
+            fn prompt(&self, _: &Self::Context) -> ::std::string::String {
+                ::std::string::ToString::to_string(&$prompt)
+            }
+
+            fn commands(&self, _: &Self::Context) -> ::std::vec::Vec<::std::string::String> {
+                ::std::vec![$(::std::string::ToString::to_string($command)),+]
+            }
+
+            fn prepare(&self, cmd: &str) -> $crate::command::Prepare {
+                $crate::command::Prepare {
+                    command: ::std::string::ToString::to_string(cmd),
+                    stdin_required: false,
+                    ..::std::default::Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                ctx: &mut Self::Context,
+                input: $crate::command::CommandInput,
+            ) -> $crate::__private::anyhow::Result<$crate::command::OutputAction> {
+                let name = input.command.split_whitespace().next().unwrap_or_default();
+                match name {
+                    $($command => {
+                        // the coercion to a function pointer infers the types of the arguments.
+                        let handler: fn(
+                            &mut $context,
+                            $crate::command::CommandInput,
+                        ) -> $crate::__private::anyhow::Result<$crate::command::OutputAction> =
+                            $handler;
+                        handler(ctx, input)
+                    })+
+                    name => $crate::__private::anyhow::bail!("unknown command: {}", name),
+                }
+            }
+        }
+    };
+}
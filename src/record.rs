@@ -0,0 +1,218 @@
+//! Recording and replaying of terminal input sessions.
+//!
+//! A recorded session is a plain-text file, with one event per line:
+//!
+//! ```text
+//! <millis since start>\t<kind>\t<payload...>
+//! ```
+//!
+//! Where `kind` is one of `key`, `paste` or `resize`. This is intentionally simple, so that
+//! recordings attached to bug reports can be read (and edited) by hand.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+///
+/// [`Recorder`] appends every event that is received by the shell to a file, along with the time
+/// (in milliseconds) since the recording started.
+///
+pub(crate) struct Recorder {
+    /// The file that the events are written to.
+    file: LineWriter<File>,
+    /// The instant at which the recording started.
+    start: Instant,
+}
+
+impl Recorder {
+    /// Create a new recorder, truncating the file at `path`.
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: LineWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    /// Record the event. Events that cannot be represented in a recording are skipped.
+    pub(crate) fn record(&mut self, event: &Event) -> io::Result<()> {
+        match encode(event) {
+            Some(encoded) => writeln!(
+                self.file,
+                "{}\t{}",
+                self.start.elapsed().as_millis(),
+                encoded
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Load the events from a recording at `path`.
+pub(crate) fn load(path: &Path) -> anyhow::Result<Vec<Event>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+
+        // the timestamp is informational, events are replayed back to back.
+        let event = line
+            .split_once('\t')
+            .and_then(|(_, encoded)| decode(encoded))
+            .ok_or_else(|| anyhow::anyhow!("invalid event at line {}: {:?}", number + 1, line))?;
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Encode the event to its textual representation.
+fn encode(event: &Event) -> Option<String> {
+    match event {
+        Event::Key(ke) => {
+            let kind = match ke.kind {
+                KeyEventKind::Press => "press",
+                KeyEventKind::Repeat => "repeat",
+                KeyEventKind::Release => "release",
+            };
+            Some(format!(
+                "key\t{}\t{}\t{}",
+                encode_key_code(ke.code)?,
+                ke.modifiers.bits(),
+                kind
+            ))
+        }
+        Event::Paste(text) => Some(format!("paste\t{}", escape(text))),
+        Event::Resize(width, height) => Some(format!("resize\t{}\t{}", width, height)),
+        Event::FocusGained | Event::FocusLost | Event::Mouse(_) => None,
+    }
+}
+
+/// Decode the event from its textual representation.
+fn decode(encoded: &str) -> Option<Event> {
+    let mut parts = encoded.split('\t');
+    match parts.next()? {
+        "key" => {
+            let code = decode_key_code(parts.next()?)?;
+            let modifiers = KeyModifiers::from_bits(parts.next()?.parse().ok()?)?;
+            let kind = match parts.next()? {
+                "press" => KeyEventKind::Press,
+                "repeat" => KeyEventKind::Repeat,
+                "release" => KeyEventKind::Release,
+                _ => return None,
+            };
+            Some(Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                state: KeyEventState::NONE,
+            }))
+        }
+        "paste" => Some(Event::Paste(unescape(parts.next()?)?)),
+        "resize" => Some(Event::Resize(
+            parts.next()?.parse().ok()?,
+            parts.next()?.parse().ok()?,
+        )),
+        _ => None,
+    }
+}
+
+/// Encode the key code. Key codes that are not used by the shell are not supported.
+fn encode_key_code(code: KeyCode) -> Option<String> {
+    let encoded = match code {
+        KeyCode::Char(c) => format!("char:{}", escape(&c.to_string())),
+        KeyCode::F(n) => format!("f:{}", n),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        _ => return None,
+    };
+    Some(encoded)
+}
+
+/// Decode the key code.
+fn decode_key_code(encoded: &str) -> Option<KeyCode> {
+    if let Some(c) = encoded.strip_prefix("char:") {
+        let c = unescape(c)?;
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(KeyCode::Char(c)),
+            _ => None,
+        };
+    }
+    if let Some(n) = encoded.strip_prefix("f:") {
+        return n.parse().ok().map(KeyCode::F);
+    }
+
+    let code = match encoded {
+        "backspace" => KeyCode::Backspace,
+        "enter" => KeyCode::Enter,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "esc" => KeyCode::Esc,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Escape the characters that would otherwise break the line based format.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse of [`escape`].
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                't' => unescaped.push('\t'),
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                _ => return None,
+            },
+            c => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
@@ -22,14 +22,28 @@
 //!
 //! # Key Bindings
 //!
-//! The following key combinations are supported:
+//! The following key combinations are bound by default (see [`crate::keymap::KeyMap`], and
+//! [`App::with_key_map`] for rebinding them):
 //!
 //! - `Ctrl+L`: Clear screen
-//! - `Ctrl+C/Ctrl+D`: Exit shell (or terminate current command if running)
+//! - `Ctrl+C/Ctrl+D`: Exit shell (or end the stdin of the current command if running, see
+//!   [`StdinAction`]). `Ctrl+C` also reveals the output that is being typed out (see
+//!   [`App::with_typewriter`]), or cancels the pending outputs (see
+//!   [`command::PendingOutput`])
 //! - `Left/Right`: Move cursor
-//! - `Tab`: Trigger command completion
-//! - `Enter`: Execute command or add new STDIN line
+//! - `Home/End`: Move cursor to the start/end of the command
+//! - `Tab`: Trigger command completion (or select the next candidate, once the completions are
+//!   shown)
+//! - `Shift+Tab`: Select the previous completion candidate
+//! - `Ctrl+F`: Highlight the typed text in the history (or clear the highlights, when empty)
+//! - `Esc`: Dismiss the completions
+//! - `Enter`: Execute command (or apply the selected completion) or add new STDIN line
 //! - `Backspace`: Delete character
+//! - `Up`: Recall the previous command (walking back through the history, when pressed again)
+//! - `Down`: Walk forward from a recalled command, back to the command being typed
+//! - `PageUp/PageDown`: Scroll through the history
+//! - `Ctrl+O`: Expand the most recent collapsed output (see [`App::with_auto_collapse_threshold`])
+//! - `Ctrl+T`: Switch to the next group of outputs (see [`App::with_groups`])
 //!
 //! # Example
 //!
@@ -51,6 +65,7 @@
 //!         command::Prepare {
 //!             command: cmd.to_string(),
 //!             stdin_required: false,
+//!             ..Default::default()
 //!         }
 //!     }
 //!
@@ -65,6 +80,7 @@
 //!             stdin: Vec::new(),
 //!             stdout: vec!["Hello, world!".to_string()],
 //!             stderr: Vec::new(),
+//!             ..Default::default()
 //!         }))
 //!     }
 //! }
@@ -82,25 +98,49 @@
 //!
 //!
 
+use std::collections::{HashMap, VecDeque};
 use std::io;
-#[cfg(feature = "tokio")]
-use std::sync::Arc;
+use std::path::Path;
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use crossterm::{
-    event::{KeyCode, KeyEventKind, KeyModifiers},
+    event::{DisableBracketedPaste, EnableBracketedPaste, KeyCode, KeyEventKind, KeyModifiers},
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
+    backend::Backend,
+    layout::Rect,
     prelude::CrosstermBackend,
-    style::{Style, Stylize},
+    style::{Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Paragraph, Wrap},
-    Frame, Terminal,
+    widgets::{Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap},
+    Frame, Terminal, TerminalOptions, Viewport,
 };
 #[cfg(feature = "tokio")]
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::command::{self};
+use crate::graphics;
+use crate::html;
+use crate::keymap::{Action, KeyMap};
+use crate::record;
+#[cfg(feature = "serde")]
+use crate::session;
+
+/// The separator rendered between the prompt and the command.
+const PROMPT_SEPARATOR: &str = " ";
+
+/// The interval at which the shell wakes up (without input) to make progress on time-based work,
+/// e.g. pending outputs.
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+/// The width that the output in columns is laid out to, in the exported transcript. (see
+/// [`App::transcript_html`])
+const TRANSCRIPT_WIDTH: u16 = 80;
 
 ///
 /// [`App`] is the main application.
@@ -113,6 +153,8 @@ pub struct App<T: command::Execute> {
     executor: T,
     /// The context that is maintained by the [`App`] struct. This is specific to your
     context: T::Context,
+    /// The additional sources of completions. (see [`App::with_completion_provider`])
+    providers: Vec<Box<Provider<T>>>,
     /// The state of the shell. This is different from the context. This is used to maintain
     /// information about the renderer.
     state: State,
@@ -120,20 +162,270 @@ pub struct App<T: command::Execute> {
     /// The runtime that is passed to the `Execute` trait. This is used to facilitate executing
     /// on [`std::future::Future`]s, creating [`tokio::task::JoinHandle`]s, etc.
     runtime: Arc<Runtime>,
+    #[cfg(feature = "tokio")]
+    /// The handle of the runtime that is passed to the `Execute` trait. This is the handle of
+    /// `runtime`, unless a shared runtime is provided. (see [`App::with_runtime_handle`])
+    handle: Handle,
     /// The history of the commands that are executed.
     history: Vec<command::CommandOutput>,
+    /// The recorder that logs every event received by the shell. (see [`App::with_record`])
+    recorder: Option<record::Recorder>,
+    /// The events that are to be replayed before reading from the terminal. (see [`App::replay`])
+    replay: VecDeque<crossterm::event::Event>,
+    /// The settings of the renderer, configured via the `with_*` methods.
+    settings: Settings,
+    /// The position of the cursor on the screen, as of the last render.
+    cursor_position: Option<(u16, u16)>,
+    /// The number of times each command (by name) has been executed. This is used to rank the
+    /// completion candidates.
+    frequency: HashMap<String, usize>,
+    /// The term whose occurrences are highlighted in the history. (see [`App::highlight`])
+    search: Option<String>,
+    /// The outputs that are pending, along with the index of their entry in the history, and the
+    /// writer that streams their lines.
+    pending: Vec<(usize, command::PendingOutput, command::OutputWriter)>,
+    /// The commands that are queued to be executed, once the shell is idle.
+    queue: VecDeque<String>,
+    /// The notice that is rendered below the command, until the next key press.
+    notice: Option<String>,
+    /// Whether the terminal bell should be rung, after the event is handled.
+    bell: bool,
+    /// Whether the screen should be cleared (and redrawn in full) before the next frame. (see
+    /// [`App::clear_screen`])
+    redraw: bool,
+    /// The instant from which the cursor blinks, i.e. the last key press. (see
+    /// [`App::with_cursor_blink`])
+    blink_epoch: Instant,
+    /// The output that is being revealed progressively. (see [`App::with_typewriter`])
+    typewriter: Option<Typewriter>,
+    /// The entry of the history that was recalled last, while `Up` is pressed repeatedly.
+    recall: Option<Recall>,
+    /// The group that is switched to, or `None` to follow the group of the latest output. (see
+    /// [`App::with_groups`])
+    active_group: Option<String>,
+    /// The buffer and the cursor when the command was submitted, restored if it fails. (see
+    /// [`App::with_restore_on_error`])
+    submitted: Option<(String, usize)>,
+    /// The number of the segments of the submitted line that are still queued (at the front of
+    /// the queue), which are dropped when the line is restored. (see
+    /// [`App::with_command_separator`])
+    segments: usize,
+    /// Whether the terminal is restored, while the shell is paused. (see
+    /// [`App::with_pause_handle`])
+    suspended: bool,
+    /// The number of rows that the view is scrolled up by, from the bottom of the content.
+    scroll_offset: usize,
+    /// The height of the area that the shell is rendered in, as of the last render.
+    viewport_height: u16,
+    /// The maximum scroll (the rows of the content that don't fit the view), as of the last
+    /// render.
+    max_scroll: usize,
+    /// The raw mode of the terminal, verified by the watchdog. (see
+    /// [`App::with_raw_mode_watchdog`])
+    raw_mode: RawMode,
 }
 
 /// The state of the shell.
 enum State {
     /// The shell is idle. This is the default state of the shell.
     /// This is when the user is typing the command. This state holds the incomplete command, the
-    /// cursor location, and the completions.
-    Idle(String, usize, Option<Vec<String>>),
+    /// cursor location (a byte offset into the command, always on a character boundary), and the
+    /// completions.
+    Idle(String, usize, Option<Completions>),
     /// The shell is running. This is when the command is being executed. This state holds the
     /// stdin that is being supplied to the command. And the contextual information about the
-    /// command.
-    Running(command::Prepare, Vec<String>),
+    /// command, along with the command as it was typed.
+    Running(command::Prepare, Vec<String>, String),
+}
+
+/// The completions that are shown below the command.
+struct Completions {
+    /// The candidates, as the text that is to be appended to the command.
+    candidates: Vec<String>,
+    /// The index of the selected candidate, if any.
+    selected: Option<usize>,
+    /// The text that is typed since the candidates were completed. The candidates are refined by
+    /// it, and replace it when applied.
+    typed: String,
+}
+
+impl Completions {
+    /// Create the completions, with no candidate selected.
+    fn new(candidates: Vec<String>) -> Self {
+        Self {
+            candidates,
+            selected: None,
+            typed: String::new(),
+        }
+    }
+
+    /// Refine the candidates by the typed `text`, keeping the candidates that start with all the
+    /// text typed since the completion (regardless of the case, with `ignore_case`).
+    fn refine(&mut self, text: &str, ignore_case: bool) {
+        self.selected = None;
+        self.typed.push_str(text);
+        let typed = match ignore_case {
+            true => self.typed.to_lowercase(),
+            false => self.typed.clone(),
+        };
+        self.candidates.retain(|candidate| match ignore_case {
+            true => candidate.to_lowercase().starts_with(&typed),
+            false => candidate.starts_with(&typed),
+        });
+    }
+
+    /// The selected candidate, if any.
+    fn selected(&self) -> Option<&str> {
+        self.selected
+            .and_then(|index| self.candidates.get(index))
+            .map(String::as_str)
+    }
+
+    /// Select the next candidate (the first one, if none is selected). At the last candidate,
+    /// this wraps around to the first one if `wrap` is set, otherwise the selection stays put.
+    fn select_next(&mut self, wrap: bool) {
+        let Some(last) = self.candidates.len().checked_sub(1) else {
+            return;
+        };
+        self.selected = Some(match self.selected {
+            None => 0,
+            Some(index) if index < last => index + 1,
+            Some(_) if wrap => 0,
+            Some(_) => last,
+        });
+    }
+
+    /// Select the previous candidate (the last one, if none is selected). At the first candidate,
+    /// this wraps around to the last one if `wrap` is set, otherwise the selection stays put.
+    fn select_previous(&mut self, wrap: bool) {
+        let Some(last) = self.candidates.len().checked_sub(1) else {
+            return;
+        };
+        self.selected = Some(match self.selected {
+            None => last,
+            Some(0) if wrap => last,
+            Some(0) => 0,
+            Some(index) => (index - 1).min(last),
+        });
+    }
+}
+
+/// The progressive reveal of the stdout of an entry in the history. (see
+/// [`App::with_typewriter`])
+struct Typewriter {
+    /// The index of the entry in the history.
+    index: usize,
+    /// The instant at which the reveal started.
+    started: Instant,
+    /// The number of characters (of the stdout) that are revealed.
+    revealed: usize,
+}
+
+/// The recall of a command from the history, i.e. the position while browsing the history with
+/// `Up` and `Down`. (see [`App::with_prefix_history_search`])
+struct Recall {
+    /// The index of the recalled entry in the history.
+    index: usize,
+    /// The prefix that the recalled commands start with.
+    prefix: String,
+    /// The text that was typed before the recall, restored when navigating back down. (see
+    /// [`HistoryRecallMode::Stash`])
+    stash: Option<String>,
+}
+
+/// The raw mode of the terminal, as verified by the watchdog. (see
+/// [`App::with_raw_mode_watchdog`])
+#[derive(Clone, Copy)]
+struct RawMode {
+    /// Whether the raw mode is enabled.
+    is_enabled: fn() -> io::Result<bool>,
+    /// Enable the raw mode.
+    enable: fn() -> io::Result<()>,
+}
+
+impl Default for RawMode {
+    fn default() -> Self {
+        Self {
+            is_enabled: crossterm::terminal::is_raw_mode_enabled,
+            enable: crossterm::terminal::enable_raw_mode,
+        }
+    }
+}
+
+impl RawMode {
+    /// Re-enable the raw mode, if it was disabled.
+    fn restore(self) {
+        match (self.is_enabled)() {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::warn!("the raw mode was disabled, re-enabling it");
+                if let Err(e) = (self.enable)() {
+                    tracing::warn!("failed to re-enable the raw mode: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("failed to check the raw mode: {}", e),
+        }
+    }
+}
+
+/// The events of the shell, shared with the key reader of the command that is being executed, so
+/// that the keys it reads are taken from the replay (first) and recorded, like the events that
+/// the shell reads. (see [`command::KeyReader`])
+#[derive(Default)]
+struct SharedEvents {
+    /// The events that are to be replayed. (see [`App::replay`])
+    replay: VecDeque<crossterm::event::Event>,
+    /// The recorder of the events. (see [`App::with_record`])
+    recorder: Option<record::Recorder>,
+    /// The events other than keys (e.g. pastes, resizes) that are read while waiting for a key,
+    /// which are handled by the shell once the command is done.
+    deferred: Vec<crossterm::event::Event>,
+}
+
+impl SharedEvents {
+    /// The key reader that reads from the events.
+    fn key_reader(events: &Arc<Mutex<Self>>) -> command::KeyReader {
+        let events = events.clone();
+        command::KeyReader::new(move || loop {
+            let lock = || {
+                events
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("the events are poisoned"))
+            };
+            let replayed = lock()?.replay.pop_front();
+            // the terminal is read without holding the lock.
+            let event = match replayed {
+                Some(event) => event,
+                None => crossterm::event::read()?,
+            };
+            let mut events = lock()?;
+            match event {
+                crossterm::event::Event::Key(key) => {
+                    if let Some(recorder) = events.recorder.as_mut() {
+                        recorder.record(&event)?;
+                    }
+                    // the key releases (reported on windows) are skipped, like in the shell.
+                    if key.kind != KeyEventKind::Release {
+                        return Ok(key);
+                    }
+                }
+                event => events.deferred.push(event),
+            }
+        })
+    }
+}
+
+/// The backend of the terminal, along with the output that the terminal is set up (and restored)
+/// through, i.e. the writer that the shell is executed on. (see [`App::execute_on`])
+trait TerminalOutput: Backend {
+    /// The output of the terminal.
+    fn output(&mut self) -> &mut dyn io::Write;
+}
+
+impl<W: io::Write> TerminalOutput for CrosstermBackend<W> {
+    fn output(&mut self) -> &mut dyn io::Write {
+        self
+    }
 }
 
 ///
@@ -148,6 +440,462 @@ enum Next {
     Exit(String),
     /// Clear renderer buffer
     Clear,
+    /// Redraw the whole screen.
+    Redraw,
+}
+
+/// The settings of the renderer. These are configured via the `with_*` methods on [`App`].
+struct Settings {
+    /// How control characters in the typed or pasted input are handled.
+    control_char_policy: ControlCharPolicy,
+    /// The maximum length of the command, in characters. (see [`App::with_max_input_length`])
+    max_input_length: Option<usize>,
+    /// The feedback when the input is cut at the maximum length. (see
+    /// [`App::with_input_limit_feedback`])
+    input_limit_feedback: InputLimitFeedback,
+    #[cfg(feature = "serde")]
+    /// The file that the structured session is persisted to. (see [`App::with_session_file`])
+    session_file: Option<PathBuf>,
+    /// Whether the shell sets up (and tears down) the terminal. (see [`App::with_manage_terminal`])
+    manage_terminal: bool,
+    /// Whether the screen is cleared before the first frame. (see [`App::with_clear_on_start`])
+    clear_on_start: bool,
+    /// Whether the raw mode is re-enabled, if it's lost. (see [`App::with_raw_mode_watchdog`])
+    raw_mode_watchdog: bool,
+    /// The sink that the output is teed to. (see [`App::with_output_tee`])
+    output_tee: Option<Box<dyn io::Write>>,
+    /// The notifier that pushes the outputs into the history. (see [`App::with_notifier`])
+    notifier: Option<command::Notifier>,
+    /// The handle that pauses the shell. (see [`App::with_pause_handle`])
+    pause: Option<command::PauseHandle>,
+    /// Whether the preview of the command is rendered. (see [`App::with_preview`])
+    preview: bool,
+    /// Whether the help of the command is rendered. (see [`App::with_inline_help`])
+    inline_help: bool,
+    /// The action for `Ctrl+C` while collecting stdin. (see [`App::with_stdin_interrupt`])
+    stdin_interrupt: StdinAction,
+    /// The action for `Ctrl+D` while collecting stdin. (see [`App::with_stdin_eof`])
+    stdin_eof: StdinAction,
+    /// The separator between multiple commands on a line. (see [`App::with_command_separator`])
+    command_separator: Option<char>,
+    /// The name of the builtin history command. (see [`App::with_history_builtin`])
+    history_builtin: Option<String>,
+    /// Whether `!!` and `!N` are expanded to the commands in the history. (see
+    /// [`App::with_bang_expansion`])
+    bang_expansion: bool,
+    /// What `Enter` does on a blank line. (see [`App::with_blank_line_behavior`])
+    blank_line: BlankLine,
+    /// The escape that reads the stdin from a file. (see [`App::with_stdin_read_escape`])
+    stdin_read_escape: Option<String>,
+    /// Whether the text pasted while the stdin is collected is split into stdin lines. (see
+    /// [`App::with_stdin_paste_lines`])
+    stdin_paste_lines: bool,
+    /// Whether the newlines of the text pasted into the command are kept. (see
+    /// [`App::with_multiline_paste`])
+    multiline_paste: bool,
+    /// The bindings of the keys to the actions. (see [`App::with_key_map`])
+    key_map: KeyMap,
+    /// Whether the key hints are rendered in a footer. (see [`App::with_key_hints`])
+    key_hints: bool,
+    /// The maximum display width of the prompt. (see [`App::with_prompt_max_width`])
+    prompt_max_width: Option<u16>,
+    /// Whether the prompt is rendered on its own line, above the command. (see
+    /// [`App::with_prompt_on_own_line`])
+    prompt_on_own_line: bool,
+    /// Whether the prompt is refreshed periodically, without input. (see
+    /// [`App::with_prompt_refresh`])
+    prompt_refresh: bool,
+    /// The style of the completion candidates. (see [`App::with_completion_style`])
+    completion_style: Style,
+    /// The style of the selected completion candidate, if overridden. (see
+    /// [`App::with_completion_selected_style`])
+    completion_selected_style: Option<Style>,
+    /// The style of the command while the completions are shown, if overridden. (see
+    /// [`App::with_completing_command_style`])
+    completing_command_style: Option<Style>,
+    /// The feedback when there are no completions. (see [`App::with_empty_completion_feedback`])
+    empty_completion_feedback: EmptyCompletionFeedback,
+    /// The hook that is run after the terminal is set up. (see [`App::with_setup_hook`])
+    setup_hook: Option<TerminalHook>,
+    /// The hook that is run before the terminal is restored. (see [`App::with_teardown_hook`])
+    teardown_hook: Option<TerminalHook>,
+    /// The interval at which the cursor blinks. (see [`App::with_cursor_blink`])
+    cursor_blink: Option<Duration>,
+    /// Whether cycling the completions wraps around the ends. (see [`App::with_completion_wrap`])
+    completion_wrap: bool,
+    /// Whether the candidates are refined regardless of the case. (see
+    /// [`App::with_case_insensitive_completion`])
+    case_insensitive_completion: bool,
+    /// The interval at which the characters of the output are revealed. (see
+    /// [`App::with_typewriter`])
+    typewriter: Option<Duration>,
+    /// The symbol rendered at the start of the stdin lines. (see [`App::with_gutter`])
+    gutter: Option<char>,
+    /// Whether the duration of the commands is rendered. (see [`App::with_durations`])
+    durations: bool,
+    /// The marker rendered before the commands that modified the state. (see
+    /// [`App::with_modified_marker`])
+    modified_marker: String,
+    /// The behavior of `Tab` in the middle of the command. (see [`App::with_mid_line_tab`])
+    mid_line_tab: MidLineTab,
+    /// What `Tab` does, i.e. completing or indenting. (see [`App::with_tab_mode`])
+    tab_mode: TabMode,
+    /// The command that is recorded in the history. (see [`App::with_history_command_source`])
+    history_command_source: Option<HistoryCommandSource>,
+    /// Whether the command scrolls horizontally. (see [`App::with_horizontal_scroll`])
+    horizontal_scroll: bool,
+    /// The maximum number of lines in the history. (see [`App::with_max_total_lines`])
+    max_total_lines: Option<usize>,
+    /// The number of output lines above which the output is collapsed. (see
+    /// [`App::with_auto_collapse_threshold`])
+    auto_collapse_threshold: Option<usize>,
+    /// Whether a hint is rendered for the commands without output. (see
+    /// [`App::with_empty_output_hint`])
+    empty_output_hint: bool,
+    /// Whether only the outputs of the active group are rendered. (see [`App::with_groups`])
+    groups: bool,
+    /// The protocol that the images are displayed with. (see [`App::with_graphics_protocol`])
+    graphics_protocol: GraphicsProtocol,
+    /// Whether the shell recovers from the errors of the executor. (see
+    /// [`App::with_error_recovery`])
+    error_recovery: bool,
+    /// Whether the multi-line errors are collapsed into their first line. (see
+    /// [`App::with_collapsible_tracebacks`])
+    collapsible_tracebacks: bool,
+    /// Whether the command that failed is restored for editing. (see
+    /// [`App::with_restore_on_error`])
+    restore_on_error: bool,
+    /// Whether the output is aligned with the command. (see
+    /// [`App::with_output_align_to_command`])
+    align_output: bool,
+    /// The lines rendered while the history is empty. (see [`App::with_empty_placeholder`])
+    empty_placeholder: Vec<String>,
+    /// Whether `Up` only recalls the commands starting with the typed prefix. (see
+    /// [`App::with_prefix_history_search`])
+    prefix_history_search: bool,
+    /// What `Up` does when the buffer isn't empty. (see [`App::with_history_recall_mode`])
+    history_recall_mode: HistoryRecallMode,
+    /// The style of the typed prefix in the command recalled by the prefix search. (see
+    /// [`App::with_history_match_style`])
+    history_match_style: Style,
+    /// Whether the scrollbar is rendered along the right edge. (see [`App::with_scrollbar`])
+    scrollbar: bool,
+    /// Whether the view snaps back to the bottom when a command is executed. (see
+    /// [`App::with_follow_output`])
+    follow_output: bool,
+    /// Whether only the history entries in view are rendered. (see [`App::with_lazy_rendering`])
+    lazy_rendering: bool,
+    /// Whether the output is rendered with colors, as resolved from the [`ColorMode`]. (see
+    /// [`App::with_color`])
+    colors: bool,
+}
+
+/// A source of completions, sharing the context of the executor `T`.
+type Provider<T> = dyn command::CompletionProvider<Context = <T as command::Execute>::Context>;
+
+/// A hook that is run around the setup or the teardown of the terminal.
+type TerminalHook = Box<dyn FnMut(&mut dyn io::Write) -> anyhow::Result<()>>;
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            control_char_policy: ControlCharPolicy::default(),
+            max_input_length: None,
+            input_limit_feedback: InputLimitFeedback::default(),
+            #[cfg(feature = "serde")]
+            session_file: None,
+            manage_terminal: true,
+            clear_on_start: false,
+            raw_mode_watchdog: false,
+            output_tee: None,
+            notifier: None,
+            pause: None,
+            preview: false,
+            inline_help: false,
+            stdin_interrupt: StdinAction::Submit,
+            stdin_eof: StdinAction::Submit,
+            command_separator: None,
+            history_builtin: None,
+            bang_expansion: false,
+            blank_line: BlankLine::default(),
+            stdin_read_escape: None,
+            stdin_paste_lines: true,
+            multiline_paste: false,
+            key_map: KeyMap::default(),
+            key_hints: false,
+            prompt_max_width: None,
+            prompt_on_own_line: false,
+            prompt_refresh: false,
+            completion_style: Style::default().bg(ratatui::style::Color::Rgb(200, 200, 200)),
+            completion_selected_style: None,
+            completing_command_style: None,
+            empty_completion_feedback: EmptyCompletionFeedback::default(),
+            setup_hook: None,
+            teardown_hook: None,
+            cursor_blink: None,
+            completion_wrap: true,
+            case_insensitive_completion: false,
+            typewriter: None,
+            gutter: None,
+            durations: false,
+            modified_marker: "*".to_string(),
+            mid_line_tab: MidLineTab::default(),
+            tab_mode: TabMode::default(),
+            history_command_source: None,
+            horizontal_scroll: false,
+            max_total_lines: None,
+            auto_collapse_threshold: None,
+            empty_output_hint: false,
+            groups: false,
+            graphics_protocol: GraphicsProtocol::default(),
+            error_recovery: false,
+            restore_on_error: false,
+            collapsible_tracebacks: false,
+            align_output: false,
+            empty_placeholder: Vec::new(),
+            prefix_history_search: false,
+            history_recall_mode: HistoryRecallMode::default(),
+            history_match_style: Style::default().underlined(),
+            scrollbar: false,
+            follow_output: true,
+            lazy_rendering: false,
+            colors: ColorMode::default().enabled(),
+        }
+    }
+}
+
+///
+/// [`MidLineTab`] decides the behavior of `Tab`, when the cursor is in the middle of the command.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MidLineTab {
+    /// Ignore the key press.
+    #[default]
+    Ignore,
+    /// Complete the text up to the cursor, inserting the completion at the cursor.
+    Complete,
+    /// Insert the given number of spaces at the cursor.
+    InsertSpaces(usize),
+}
+
+///
+/// [`TabMode`] decides whether `Tab` completes, or inserts the indentation. This applies to the
+/// stdin lines too.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TabMode {
+    /// Complete the command (see [`MidLineTab`]), or the stdin line.
+    #[default]
+    Complete,
+    /// Insert the given number of spaces at the cursor, instead of completing.
+    InsertTab(usize),
+    /// Complete when there is a word before the cursor, otherwise insert the given number of
+    /// spaces (e.g. at the start of the line, or after a space).
+    Smart(usize),
+}
+
+///
+/// [`HistoryCommandSource`] decides the command that is recorded in the history.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCommandSource {
+    /// The command as it was typed.
+    Raw,
+    /// The command as it was transformed by [`command::Execute::prepare`] (e.g. with the aliases
+    /// expanded).
+    Transformed,
+}
+
+///
+/// [`GraphicsProtocol`] is the terminal graphics protocol that the images are displayed with.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    /// The images are only rendered as a placeholder.
+    #[default]
+    None,
+    /// The Kitty graphics protocol (supported by Kitty, WezTerm, Ghostty, etc.).
+    Kitty,
+    /// The iTerm2 inline images protocol (supported by iTerm2, WezTerm, etc.).
+    Iterm2,
+}
+
+///
+/// [`HistoryFormat`] is the format that the commands are exported in. (see
+/// [`App::export_commands`])
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// One command per line, e.g. for the bash history.
+    #[default]
+    Plain,
+    /// The extended history of zsh, i.e. `: <timestamp>:<duration>;<command>` per line, with the
+    /// timestamp and the duration in seconds. The commands without a timestamp are exported with
+    /// `0`.
+    ZshExtended,
+}
+
+///
+/// [`ColorMode`] decides whether the shell is rendered with colors (and text styles).
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Render with colors, unless `NO_COLOR` is set (to a non-empty value) or the terminal is
+    /// `dumb`.
+    #[default]
+    Auto,
+    /// Always render with colors.
+    Always,
+    /// Never render with colors. Everything is rendered with the default style, except the
+    /// cursor, the selected completion and the highlights, which are rendered reversed.
+    Never,
+}
+
+impl ColorMode {
+    /// Whether the colors are enabled, following the environment for [`ColorMode::Auto`].
+    fn enabled(self) -> bool {
+        match self {
+            ColorMode::Auto => {
+                let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+                let dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+                !no_color && !dumb
+            }
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+///
+/// [`EmptyCompletionFeedback`] decides the feedback that is given when `Tab` is pressed, and there
+/// is nothing to complete.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyCompletionFeedback {
+    /// Don't give any feedback.
+    #[default]
+    None,
+    /// Render a dimmed `(no completions)` line below the command, until the next key press.
+    Message,
+    /// Ring the terminal bell.
+    Bell,
+}
+
+impl EmptyCompletionFeedback {
+    /// Give the feedback, by setting the notice or the bell of the [`App`].
+    fn apply(self, notice: &mut Option<String>, bell: &mut bool) {
+        match self {
+            EmptyCompletionFeedback::None => {}
+            EmptyCompletionFeedback::Message => *notice = Some("(no completions)".to_string()),
+            EmptyCompletionFeedback::Bell => *bell = true,
+        }
+    }
+}
+
+///
+/// [`InputLimitFeedback`] decides the feedback that is given when the typed (or pasted) input is
+/// cut at the maximum length. (see [`App::with_max_input_length`])
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputLimitFeedback {
+    /// Don't give any feedback.
+    #[default]
+    None,
+    /// Render a dimmed `(input too long)` line below the command, until the next key press.
+    Message,
+    /// Ring the terminal bell.
+    Bell,
+}
+
+impl InputLimitFeedback {
+    /// Give the feedback, by setting the notice or the bell of the [`App`].
+    fn apply(self, notice: &mut Option<String>, bell: &mut bool) {
+        match self {
+            InputLimitFeedback::None => {}
+            InputLimitFeedback::Message => *notice = Some("(input too long)".to_string()),
+            InputLimitFeedback::Bell => *bell = true,
+        }
+    }
+}
+
+///
+/// [`BlankLine`] decides what `Enter` does on a blank line, i.e. when nothing (but whitespace) is
+/// typed.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlankLine {
+    /// Execute the blank command, like any other command.
+    #[default]
+    Execute,
+    /// Do nothing.
+    Ignore,
+    /// Render a fresh prompt (recording the blank command in the history), without executing
+    /// anything.
+    FreshPrompt,
+    /// A blank line ends the block: while the stdin of a command is collected, a blank line
+    /// submits the stdin (without the blank line). Nothing is done for a blank command.
+    SubmitBlock,
+}
+
+///
+/// [`HistoryRecallMode`] decides what `Up` does when something is already typed.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HistoryRecallMode {
+    /// Overwrite the typed text with the recalled command. Navigating back down past the most
+    /// recent command leaves the buffer empty.
+    Overwrite,
+    /// Only recall a command when nothing is typed, keeping the typed text otherwise.
+    WhenEmpty,
+    /// Stash the typed text, and restore it when navigating back down (with `Down`) past the most
+    /// recent command, so that the command being typed isn't lost while browsing the history.
+    #[default]
+    Stash,
+}
+
+///
+/// [`StdinAction`] decides what happens to a command that is waiting for stdin, when the stdin
+/// collection is ended (with `Ctrl+C` or `Ctrl+D`).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdinAction {
+    /// Execute the command, with the stdin collected so far.
+    Submit,
+    /// Discard the command, and the stdin collected so far, without executing it.
+    Abort,
+}
+
+///
+/// [`ControlCharPolicy`] decides how control characters that are typed or pasted into the shell
+/// are handled. Control characters that are bound to an action (e.g. `Ctrl+C`) are not affected.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ControlCharPolicy {
+    /// Remove the control characters from the input.
+    #[default]
+    Strip,
+    /// Replace the control characters with their caret notation (e.g. `^C`). Non-ASCII control
+    /// characters don't have a caret notation, and are removed.
+    Caret,
+}
+
+impl ControlCharPolicy {
+    /// Apply the policy to the input.
+    fn apply(self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        for c in text.chars() {
+            match (self, c) {
+                (_, c) if !c.is_control() => output.push(c),
+                (ControlCharPolicy::Caret, '\x7f') => output.push_str("^?"),
+                (ControlCharPolicy::Caret, c) if c.is_ascii() => {
+                    output.push('^');
+                    output.push((c as u8 + b'@') as char);
+                }
+                _ => {}
+            }
+        }
+        output
+    }
 }
 
 impl<T: command::Execute> App<T> {
@@ -156,6 +904,13 @@ impl<T: command::Execute> App<T> {
     where
         T: command::New,
     {
+        // the runtime is entered, so that `new` can spawn the background tasks.
+        #[cfg(feature = "tokio")]
+        let (executor, context) = {
+            let _runtime = rt.enter();
+            T::new()?
+        };
+        #[cfg(not(feature = "tokio"))]
         let (executor, context) = T::new()?;
         Ok(Self::new_with_executor(
             #[cfg(feature = "tokio")]
@@ -174,371 +929,7034 @@ impl<T: command::Execute> App<T> {
         Self {
             executor,
             context,
+            providers: Vec::new(),
             state: State::Idle(String::new(), 0, None),
             #[cfg(feature = "tokio")]
+            handle: rt.handle().clone(),
+            #[cfg(feature = "tokio")]
             runtime: Arc::new(rt),
             history: Vec::new(),
+            recorder: None,
+            replay: VecDeque::new(),
+            settings: Settings::default(),
+            cursor_position: None,
+            frequency: HashMap::new(),
+            search: None,
+            pending: Vec::new(),
+            queue: VecDeque::new(),
+            notice: None,
+            bell: false,
+            redraw: false,
+            blink_epoch: Instant::now(),
+            typewriter: None,
+            recall: None,
+            active_group: None,
+            submitted: None,
+            segments: 0,
+            suspended: false,
+            scroll_offset: 0,
+            max_scroll: 0,
+            viewport_height: 0,
+            raw_mode: RawMode::default(),
         }
     }
 
-    /// Render the shell.
-    fn render(&self, frame: &mut Frame) {
-        let prompt = self.executor.prompt(&self.context);
-        let area = frame.area();
-        let mut text_content = self
-            .history
-            .iter()
-            .flat_map(render_history)
-            .collect::<Vec<_>>();
+    /// Create a new instance of the [`App`] struct with the executor, and the default context.
+    /// This avoids implementing [`command::New`], for the executors with a context that is
+    /// [`Default`].
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// #[derive(Default)]
+    /// struct Counter {
+    ///     count: usize,
+    /// }
+    ///
+    /// struct Count;
+    ///
+    /// impl Execute for Count {
+    ///     type Context = Counter;
+    ///
+    ///     fn prompt(&self, ctx: &Self::Context) -> String {
+    ///         format!("[{}]", ctx.count)
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, ctx: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         ctx.count += 1;
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_default(runtime, Count);
+    /// assert_eq!(app.context().count, 0);
+    ///
+    /// app.run_command("count", None)?;
+    /// assert_eq!(app.context().count, 1);
+    /// assert_eq!(app.history()[0].prompt, "[0]");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn new_default(#[cfg(feature = "tokio")] rt: Runtime, executor: T) -> Self
+    where
+        T::Context: Default,
+    {
+        Self::new_with_executor(
+            #[cfg(feature = "tokio")]
+            rt,
+            executor,
+            T::Context::default(),
+        )
+    }
 
-        match &self.state {
-            State::Idle(ref cmd, cursor, comp) => {
-                let (left_cmd, right_cmd) = cmd.split_at(*cursor);
-                let left_cmd = Span::styled(left_cmd, Style::default().bold());
-                let (cursor, right_cmd) = match right_cmd {
-                    "" => {
-                        let cursor =
-                            Span::styled(" ", Style::default().bg(ratatui::style::Color::White));
-                        let right_cmd = Span::raw("");
-                        (cursor, right_cmd)
-                    }
-                    right_cmd => {
-                        let cursor = Span::styled(
-                            //
-                            // # Safety: `right_cmd` will never be empty.
-                            //
-                            #[allow(clippy::expect_used)]
-                            right_cmd
-                                .chars()
-                                .next()
-                                .expect("match statement failed")
-                                .to_string(),
-                            Style::default()
-                                .bg(ratatui::style::Color::White)
-                                .fg(ratatui::style::Color::Black),
-                        );
-
-                        let right_cmd =
-                            Span::styled(right_cmd[1..].to_string(), Style::default().bold());
-                        (cursor, right_cmd)
+    /// Persist the structured session (the full history, including the output of the commands)
+    /// as JSON to the file at `path`.
+    ///
+    /// If the file exists, the history is restored from it right away. The history is written
+    /// back to the file when the shell exits. Failures while loading or saving the session are
+    /// logged as warnings, and don't stop the shell.
+    ///
+    /// This requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn with_session_file(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            match session::load(&path) {
+                Ok(history) => {
+                    for output in history.iter() {
+                        *self
+                            .frequency
+                            .entry(command_name(&output.command))
+                            .or_default() += 1;
                     }
-                };
-
-                text_content.push(Line::from(vec![
-                    Span::styled(prompt.clone(), Style::default().blue()),
-                    Span::raw(" "),
-                    Span::styled(left_cmd.to_string(), Style::default().bold()),
-                    cursor,
-                    right_cmd,
-                ]));
-
-                if let Some(comp) = comp {
-                    let completions = comp
-                        .iter()
-                        .map(|cmp| cmd.to_string() + cmp)
-                        .map(|line| {
-                            Span::styled(
-                                line,
-                                Style::default().bg(ratatui::style::Color::Rgb(200, 200, 200)),
-                            )
-                        })
-                        .map(Line::from)
-                        .collect::<Vec<_>>();
-                    text_content.extend(completions);
+                    self.history = history;
                 }
-
-                let text_para = Paragraph::new(text_content).wrap(Wrap { trim: true });
-                frame.render_widget(text_para, area);
-            }
-            State::Running(ref prep, stdin) => {
-                text_content.push(Line::from(vec![
-                    Span::styled(prompt.clone(), Style::default().blue()),
-                    Span::raw(" "),
-                    Span::styled(prep.command.clone(), Style::default().bold()),
-                ]));
-                let stdin = stdin
-                    .iter()
-                    .map(Span::raw)
-                    .map(Line::from)
-                    .collect::<Vec<_>>();
-                text_content.extend(stdin);
-
-                let history_para = Paragraph::new(text_content).wrap(Wrap { trim: true });
-                frame.render_widget(history_para, area);
+                Err(e) => tracing::warn!("failed to load the session from {:?}: {}", path, e),
             }
         }
+        self.settings.session_file = Some(path);
+        self
     }
 
-    /// Handle the input from the user.
-    fn input(&mut self, event: crossterm::event::Event) -> anyhow::Result<Next> {
-        if let crossterm::event::Event::Key(ke) = event {
-            match (ke.code, ke.modifiers, ke.kind) {
-                (_, _, KeyEventKind::Release) => {
-                    // Ignore Release events, prevents getting double keypresses on windows
-                }
-                (KeyCode::Char('l'), KeyModifiers::CONTROL, _) => {
-                    self.history.clear();
-                    return Ok(Next::Continue);
-                }
+    /// Use an already running runtime, via its handle, for the commands.
+    ///
+    /// This is passed to the [`command::Execute`] trait as [`command::CommandInput::handle`], and
+    /// allows sharing a (multi-threaded) runtime that is configured and owned by the caller.
+    #[cfg(feature = "tokio")]
+    pub fn with_runtime_handle(mut self, handle: Handle) -> Self {
+        self.handle = handle;
+        self
+    }
 
-                (KeyCode::Char('d') | KeyCode::Char('c'), KeyModifiers::CONTROL, _) => {
-                    if let State::Running(..) = &self.state {
-                        self.continue_execution()?;
-                    } else {
-                        return Ok(Next::Exit("".to_string()));
-                    }
-                }
-                (KeyCode::Left, KeyModifiers::NONE, _) => self.move_cursor_left(),
-                (KeyCode::Right, KeyModifiers::NONE, _) => self.move_cursor_right(),
-                (KeyCode::Tab, KeyModifiers::NONE, _) => {
-                    if let State::Idle(ref mut cmd, ref mut cursor, ref mut comp @ None) =
-                        self.state
-                    {
-                        if *cursor == cmd.len() {
-                            let (fixed, variable) = self.executor.completion(&self.context, cmd)?;
-                            cmd.push_str(&fixed);
-                            *cursor = cmd.len();
-                            *comp = Some(variable);
-                        }
-                    }
-                }
-                (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT, _) => match self.state
-                {
-                    State::Idle(ref mut cmd, ref mut cursor, ref mut comp) => {
-                        cmd.insert(*cursor, c);
-                        *cursor += 1;
+    /// The handle of the runtime that is used for the commands (see
+    /// [`App::with_runtime_handle`]), e.g. for spawning the background tasks outside of the
+    /// commands.
+    #[cfg(feature = "tokio")]
+    pub fn runtime_handle(&self) -> &Handle {
+        &self.handle
+    }
 
-                        match comp.as_mut() {
-                            None => {}
-                            Some(cmp) => {
-                                *cmp = cmp
-                                    .iter()
-                                    .filter_map(|i| {
-                                        if i.starts_with(&cmd[..*cursor]) {
-                                            Some(i[*cursor..].to_string())
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                    .collect::<Vec<_>>();
-                            }
-                        }
-                    }
-                    State::Running(ref mut _pre, ref mut stdin) => {
-                        stdin.last_mut().map(|i| i.push(c)).unwrap_or_else(|| {
-                            stdin.push(c.to_string());
-                        });
-                    }
-                },
-                (KeyCode::Backspace, KeyModifiers::NONE, _) => {
-                    self.cursor_backspace();
-                }
-                (KeyCode::Enter, KeyModifiers::NONE, _) => match self.state {
-                    State::Idle(..) => {
-                        return self.execute_command();
-                    }
-                    State::Running(ref mut _pre, ref mut stdin) => {
-                        stdin.push(String::new());
-                    }
-                },
-                (KeyCode::Up, KeyModifiers::NONE, _) => {
-                    let last = self.history.last().map(|x| x.command.clone());
-                    if let Some(last) = last {
-                        match self.state {
-                            State::Idle(ref mut cmd, ref mut cursor, _) => {
-                                *cmd = last;
-                                *cursor = cmd.len();
-                            }
-                            State::Running(..) => {}
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
+    /// Set whether the shell manages the terminal. (default: `true`)
+    ///
+    /// By default, the shell enables raw mode, the alternate screen and bracketed paste when it
+    /// starts, and restores the terminal when it exits. When embedding the shell in an application
+    /// that already owns the terminal, this can be disabled, and the caller is responsible for
+    /// setting up (and tearing down) the terminal.
+    pub fn with_manage_terminal(mut self, manage: bool) -> Self {
+        self.settings.manage_terminal = manage;
+        self
+    }
 
-        Ok(Default::default())
+    /// Set whether the screen is cleared before the first frame is drawn. Otherwise, what is
+    /// already on the screen (e.g. a banner written by the setup hook, see
+    /// [`App::with_setup_hook`]) is kept where the first frame doesn't draw over it.
+    /// (default: `false`)
+    pub fn with_clear_on_start(mut self, clear: bool) -> Self {
+        self.settings.clear_on_start = clear;
+        self
     }
 
-    /// Execute the shell.
-    ///
-    /// This is the main method that is used to execute the shell. This is where the shell is
-    /// created and the input is handled. This also converts the shell into raw mode and enables
-    /// the alternate screen.
+    /// Set whether the raw mode is verified on every tick, and re-enabled (logging a warning) if
+    /// it was disabled while the shell is running, e.g. by a command or a library that restores
+    /// the terminal. Without the raw mode, the keys are echoed and only read line by line.
+    /// (default: `false`)
     ///
-    /// This method returns an `anyhow::Result<()>` which is used to handle the errors that are
-    /// encountered during the execution of the shell.
+    /// The raw mode is tracked by `crossterm`, so disabling it by other means (e.g. `stty` in
+    /// another process) isn't detected.
+    pub fn with_raw_mode_watchdog(mut self, watchdog: bool) -> Self {
+        self.settings.raw_mode_watchdog = watchdog;
+        self
+    }
+
+    /// Tee the output of every command to `sink` (a file, a pipe, etc.) as plain text, as the
+    /// commands are executed.
     ///
-    pub fn execute(mut self) -> anyhow::Result<String> {
-        crossterm::terminal::enable_raw_mode()?;
+    /// The command line (prompt and command) is written, followed by the stdin, stdout and stderr
+    /// lines. Failures while writing are logged as warnings, and don't stop the shell.
+    pub fn with_output_tee(mut self, sink: Box<dyn io::Write>) -> Self {
+        self.settings.output_tee = Some(sink);
+        self
+    }
 
-        let mut stdout = io::stdout();
-        crossterm::execute!(stdout, EnterAlternateScreen)?;
-        let backend = CrosstermBackend::new(stdout);
-        let mut terminal = Terminal::new(backend)?;
+    /// Push the outputs from the `notifier` into the history, as they arrive. This lets the
+    /// background tasks (e.g. spawned on [`App::runtime_handle`]) feed the shell, outside of the
+    /// commands.
+    pub fn with_notifier(mut self, notifier: command::Notifier) -> Self {
+        self.settings.notifier = Some(notifier);
+        self
+    }
 
-        let response: anyhow::Result<String> = loop {
-            let draw = terminal.draw(|f| self.render(f));
+    /// Pause (and resume) the shell via the `handle`, e.g. from another thread while another UI
+    /// takes over the terminal. While paused, the terminal is restored, and the shell neither
+    /// renders nor reads the events. (see [`command::PauseHandle`])
+    pub fn with_pause_handle(mut self, handle: command::PauseHandle) -> Self {
+        self.settings.pause = Some(handle);
+        self
+    }
 
-            if let Err(e) = draw {
-                break Err(e.into());
-            }
+    /// Set whether the preview (from [`command::Execute::preview`]) is rendered below the command
+    /// as it is being typed. (default: `false`)
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.settings.preview = preview;
+        self
+    }
 
-            let event = crossterm::event::read();
-            let next = match event {
-                Ok(event) => self.input(event),
-                Err(e) => break Err(e.into()),
-            };
+    /// Set whether the help (from [`command::Execute::help`]) is rendered below the command as it
+    /// is being typed. (default: `false`)
+    pub fn with_inline_help(mut self, help: bool) -> Self {
+        self.settings.inline_help = help;
+        self
+    }
 
-            match next {
-                Ok(Next::Continue) => continue,
-                Ok(Next::Exit(msg)) => break Ok(msg),
-                Ok(Next::Clear) => {
-                    terminal.clear()?;
-                    continue;
-                }
-                Err(e) => break Err(e),
-            }
-        };
+    /// Set what `Ctrl+C` does while collecting the stdin of a command.
+    /// (default: [`StdinAction::Submit`])
+    pub fn with_stdin_interrupt(mut self, action: StdinAction) -> Self {
+        self.settings.stdin_interrupt = action;
+        self
+    }
 
-        crossterm::terminal::disable_raw_mode()?;
-        crossterm::execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-        terminal.show_cursor()?;
+    /// Set what `Ctrl+D` does while collecting the stdin of a command.
+    /// (default: [`StdinAction::Submit`])
+    pub fn with_stdin_eof(mut self, action: StdinAction) -> Self {
+        self.settings.stdin_eof = action;
+        self
+    }
 
-        response
+    /// Set the bindings of the keys to the actions, e.g. to rebind `Ctrl+L`. (default:
+    /// [`KeyMap::default`])
+    pub fn with_key_map(mut self, key_map: KeyMap) -> Self {
+        self.settings.key_map = key_map;
+        self
     }
 
-    // helpers
+    /// Render a (dimmed) footer that summarizes the key bindings, e.g.
+    /// `^L clear  ^D exit  Tab complete`. The bottom row is reserved for the footer. The summary
+    /// follows the key map (see [`App::with_key_map`]). (default: `false`)
+    pub fn with_key_hints(mut self, hints: bool) -> Self {
+        self.settings.key_hints = hints;
+        self
+    }
 
-    /// Move the cursor to the left by one.
+    /// Truncate the prompts that are wider than `width` (in terminal cells), with an ellipsis, so
+    /// that the command stays on the same line as the prompt.
+    pub fn with_prompt_max_width(mut self, width: u16) -> Self {
+        self.settings.prompt_max_width = Some(width);
+        self
+    }
+
+    /// Render the prompt on its own line, with the command (and the cursor) on the line below,
+    /// both for the command being typed and in the history. (default: `false`)
+    pub fn with_prompt_on_own_line(mut self, own_line: bool) -> Self {
+        self.settings.prompt_on_own_line = own_line;
+        self
+    }
+
+    /// Refresh the prompt periodically (every tick), even without input, for the prompts showing
+    /// time-sensitive information (e.g. a clock). The prompt is recomputed (via
+    /// [`command::Execute::prompt`]) on every frame, and only the cells that changed are redrawn.
+    /// (default: `false`)
+    pub fn with_prompt_refresh(mut self, refresh: bool) -> Self {
+        self.settings.prompt_refresh = refresh;
+        self
+    }
+
+    /// Set the style of the completion candidates, e.g. to match the theme of the terminal.
+    /// (default: a light gray background)
+    pub fn with_completion_style(mut self, style: Style) -> Self {
+        self.settings.completion_style = style;
+        self
+    }
+
+    /// Set the style of the selected completion candidate. (default: the style of the cursor,
+    /// in bold)
+    pub fn with_completion_selected_style(mut self, style: Style) -> Self {
+        self.settings.completion_selected_style = Some(style);
+        self
+    }
+
+    /// Set the style of the command while the completions are shown, e.g. to signal the
+    /// completion mode. The command reverts to its style once the completions are dismissed.
+    /// (default: the style of the command, in bold)
+    pub fn with_completing_command_style(mut self, style: Style) -> Self {
+        self.settings.completing_command_style = Some(style);
+        self
+    }
+
+    /// Enable reading the stdin of a command from a file, with the given escape (e.g. `:read`).
+    ///
+    /// While the stdin is collected, submitting the line `<escape> <path>` appends the lines of
+    /// the file to the stdin (in place of the line), and executes the command. If the file cannot
+    /// be read, the error is shown below the stdin, and the collection continues.
+    pub fn with_stdin_read_escape(mut self, escape: impl Into<String>) -> Self {
+        self.settings.stdin_read_escape = Some(escape.into());
+        self
+    }
+
+    /// Split the text that is pasted while the stdin is collected into stdin lines, i.e. every
+    /// pasted line becomes a stdin line, and the text after the last newline is left as the line
+    /// being typed. Otherwise, the text is pasted into the line being typed (with the newlines
+    /// handled by the [`ControlCharPolicy`]). (default: `true`)
+    pub fn with_stdin_paste_lines(mut self, split: bool) -> Self {
+        self.settings.stdin_paste_lines = split;
+        self
+    }
+
+    /// Keep the newlines of the text that is pasted into the command (e.g. a script), so that the
+    /// command is rendered as multiple rows (aligned with the first one), and can be reviewed and
+    /// edited before it's executed. The command is executed as a whole, newlines included.
+    /// Otherwise, the newlines are handled by the [`ControlCharPolicy`]. (default: `false`)
+    pub fn with_multiline_paste(mut self, multiline: bool) -> Self {
+        self.settings.multiline_paste = multiline;
+        self
+    }
+
+    /// Set what `Enter` does on a blank line. (default: [`BlankLine::Execute`])
+    pub fn with_blank_line_behavior(mut self, behavior: BlankLine) -> Self {
+        self.settings.blank_line = behavior;
+        self
+    }
+
+    /// Allow multiple commands on a line, separated by `separator` (e.g. `;`).
+    ///
+    /// The commands are prepared and executed one after the other, each with its own entry in
+    /// the history. The separator doesn't split the line when it's quoted (with `'` or `"`), or
+    /// escaped (with `\`).
+    pub fn with_command_separator(mut self, separator: char) -> Self {
+        self.settings.command_separator = Some(separator);
+        self
+    }
+
+    /// Enable the builtin history command, with the given name (e.g. `history`).
+    ///
+    /// The builtin is handled by the shell, before the command reaches the executor, and lists
+    /// the commands in the history along with their (1-based) index.
+    pub fn with_history_builtin(mut self, name: impl Into<String>) -> Self {
+        self.settings.history_builtin = Some(name.into());
+        self
+    }
+
+    /// Expand `!!` to the last command in the history, and `!N` to the command at the (1-based)
+    /// index `N`, as listed by the builtin history command (see [`App::with_history_builtin`]),
+    /// before the command is executed. The expanded command is recorded in the history.
+    /// (default: `false`)
+    pub fn with_bang_expansion(mut self, expand: bool) -> Self {
+        self.settings.bang_expansion = expand;
+        self
+    }
+
+    /// Set the feedback that is given when `Tab` is pressed, and there is nothing to complete.
+    /// (default: [`EmptyCompletionFeedback::None`])
+    pub fn with_empty_completion_feedback(mut self, feedback: EmptyCompletionFeedback) -> Self {
+        self.settings.empty_completion_feedback = feedback;
+        self
+    }
+
+    /// Blink the cursor, toggling it between the styled cursor and the plain character every
+    /// `interval`. The cursor is shown as soon as a key is pressed, so that it can be followed
+    /// while typing. A zero `interval` disables blinking.
+    pub fn with_cursor_blink(mut self, interval: Duration) -> Self {
+        self.settings.cursor_blink = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Set whether cycling through the completions (with `Tab` and `Shift+Tab`) wraps around the
+    /// ends, i.e. from the last candidate to the first one (and vice versa). Otherwise, the
+    /// selection stops at the ends. (default: `true`)
+    pub fn with_completion_wrap(mut self, wrap: bool) -> Self {
+        self.settings.completion_wrap = wrap;
+        self
+    }
+
+    /// Refine the shown completions by the typed text regardless of the case, e.g. typing `GI`
+    /// keeps the candidate `git`. The applied candidate keeps its own case. (default: `false`)
+    pub fn with_case_insensitive_completion(mut self, ignore_case: bool) -> Self {
+        self.settings.case_insensitive_completion = ignore_case;
+        self
+    }
+
+    /// Reveal the stdout of the commands progressively, one character every `interval`, like a
+    /// typewriter. The stderr is shown once the stdout is fully revealed. Pressing `Ctrl+C`
+    /// reveals the rest of the output at once. A zero `interval` disables the effect.
+    pub fn with_typewriter(mut self, interval: Duration) -> Self {
+        self.settings.typewriter = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Render `gutter` (e.g. `│`) at the start of the stdin lines and of the continuation rows of
+    /// a multi-line command (see [`App::with_multiline_paste`]), both while they are being typed
+    /// and in the history, to visually group them with the command. (default: `None`)
+    pub fn with_gutter(mut self, gutter: Option<char>) -> Self {
+        self.settings.gutter = gutter;
+        self
+    }
+
+    /// Render the time that each command took (e.g. `(1.2s)`), dimmed after the command in the
+    /// history. The duration is measured around [`command::Execute::execute`], so it doesn't
+    /// include the time that a [`command::PendingOutput`] takes to resolve. (default: `false`)
+    pub fn with_durations(mut self, durations: bool) -> Self {
+        self.settings.durations = durations;
+        self
+    }
+
+    /// Set the marker that is rendered (in yellow) before the commands that modified the state
+    /// (see [`command::CommandOutput::modified`]), so that the commands with side effects stand
+    /// out in the history. (default: `*`)
+    pub fn with_modified_marker(mut self, marker: impl Into<String>) -> Self {
+        self.settings.modified_marker = marker.into();
+        self
+    }
+
+    /// Add a source of completions, merged with the completions of the executor (see
+    /// [`command::CompletionProvider`]). The providers are queried in the order they're added.
+    pub fn with_completion_provider(
+        mut self,
+        provider: impl command::CompletionProvider<Context = T::Context> + 'static,
+    ) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// Set the behavior of `Tab`, when the cursor is in the middle of the command.
+    /// (default: [`MidLineTab::Ignore`])
+    pub fn with_mid_line_tab(mut self, mid_line_tab: MidLineTab) -> Self {
+        self.settings.mid_line_tab = mid_line_tab;
+        self
+    }
+
+    /// Set whether `Tab` completes, or inserts the indentation (e.g. for the shells where the
+    /// commands are indented blocks). (default: [`TabMode::Complete`])
+    pub fn with_tab_mode(mut self, tab_mode: TabMode) -> Self {
+        self.settings.tab_mode = tab_mode;
+        self
+    }
+
+    /// Set the command that is recorded in the history (and recalled with `Up`), overriding the
+    /// command in the output of the executor. By default, the command in the output is recorded
+    /// as is.
+    pub fn with_history_command_source(mut self, source: HistoryCommandSource) -> Self {
+        self.settings.history_command_source = Some(source);
+        self
+    }
+
+    /// Scroll the command horizontally, instead of wrapping it, when it doesn't fit the width of
+    /// the terminal. The visible part of the line (including the prompt) is shifted as the cursor
+    /// moves past the right edge, so that the cursor stays visible. (default: `false`)
+    pub fn with_horizontal_scroll(mut self, horizontal_scroll: bool) -> Self {
+        self.settings.horizontal_scroll = horizontal_scroll;
+        self
+    }
+
+    /// Cap the number of lines (stdin, stdout and stderr) that are kept in the history, across
+    /// all the commands. This bounds the memory of long running shells.
+    ///
+    /// When the cap is exceeded, the oldest commands are evicted. If the latest command exceeds
+    /// the cap on its own, only its latest lines are kept (dropping its stdin first).
+    pub fn with_max_total_lines(mut self, max: usize) -> Self {
+        self.settings.max_total_lines = Some(max);
+        self
+    }
+
+    /// Collapse the outputs with more than `threshold` lines (stdout and stderr) into a summary
+    /// line, e.g. `… 120 lines (^O expand)`, so that the occasional large dump doesn't clutter the
+    /// history. The smaller outputs are rendered in full. The most recent collapsed output is
+    /// expanded with `Ctrl+O`. (see [`command::CommandOutput::collapsed`])
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Seq;
+    ///
+    /// impl Execute for Seq {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     // `seq N` prints the numbers up to `N`.
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         let count = input.command.trim_start_matches("seq ").parse::<usize>()?;
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             stdout: (1..=count).map(|n| n.to_string()).collect(),
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Seq, ()).with_auto_collapse_threshold(100);
+    /// app.run_command("seq 1000", None)?;
+    /// app.run_command("seq 3", None)?;
+    ///
+    /// assert!(app.history()[0].collapsed);
+    /// assert!(!app.history()[1].collapsed);
+    /// // the collapsed output is rendered as the summary.
+    /// let html = app.transcript_html();
+    /// assert!(html.contains("1000 lines"));
+    /// assert!(!html.contains("\n999\n"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_auto_collapse_threshold(mut self, threshold: usize) -> Self {
+        self.settings.auto_collapse_threshold = Some(threshold);
+        self
+    }
+
+    /// Render a dim `(no output)` line after the commands that produced neither stdout nor
+    /// stderr, so that it's clear that the command did run. The blank commands, and the outputs
+    /// that are still pending (or being revealed), don't get the hint. (default: `false`)
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Touch;
+    ///
+    /// impl Execute for Touch {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     // `touch` is silent, like the real one.
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// for hint in [false, true] {
+    ///     let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    ///     let mut app = App::new_with_executor(runtime, Touch, ()).with_empty_output_hint(hint);
+    ///     app.run_command("touch notes.txt", None)?;
+    ///
+    ///     assert_eq!(app.transcript_html().contains("(no output)"), hint);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_empty_output_hint(mut self, hint: bool) -> Self {
+        self.settings.empty_output_hint = hint;
+        self
+    }
+
+    /// Organize the history into groups (e.g. tabs or sections), by the group of each output (see
+    /// [`command::CommandOutput::group`]). Only the outputs of the active group are rendered,
+    /// along with the outputs without a group, and the groups are listed above the prompt.
+    ///
+    /// The active group follows the group of the latest output, and `Ctrl+T` switches to the next
+    /// group. (default: `false`)
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Make;
+    ///
+    /// impl Execute for Make {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     // the outputs are grouped by the target, e.g. `make test` in the `test` group.
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         let target = input.command.trim_start_matches("make ").to_string();
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             stdout: vec![format!("{} done", target)],
+    ///             group: Some(target),
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Make, ()).with_groups(true);
+    /// app.run_command("make build", None)?;
+    /// app.run_command("make test", None)?;
+    ///
+    /// // the `test` group (of the latest output) is active.
+    /// let html = app.transcript_html();
+    /// assert!(html.contains("test done"));
+    /// assert!(!html.contains("build done"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_groups(mut self, groups: bool) -> Self {
+        self.settings.groups = groups;
+        self
+    }
+
+    /// Set the terminal graphics protocol that the images (see [`command::OutputAction::Image`])
+    /// are displayed with.
+    ///
+    /// The shell renders the images as a placeholder line, as the images can't be drawn in the
+    /// cells of the shell. With a protocol selected, the escape sequence that displays the image
+    /// is written to the output tee (see [`App::with_output_tee`]) in place of the placeholder,
+    /// so that the transcript shows the image in a supporting terminal.
+    /// (default: [`GraphicsProtocol::None`])
+    pub fn with_graphics_protocol(mut self, protocol: GraphicsProtocol) -> Self {
+        self.settings.graphics_protocol = protocol;
+        self
+    }
+
+    /// Recover from the errors returned by [`command::Execute::execute`], rendering them as the
+    /// stderr of the command, instead of exiting the shell with the error. Either way, the shell
+    /// is reset to a clean idle state (e.g. the stdin that was being collected is discarded).
+    /// (default: `false`)
+    pub fn with_error_recovery(mut self, recover: bool) -> Self {
+        self.settings.error_recovery = recover;
+        self
+    }
+
+    /// Render the errors that are recovered from (see [`App::with_error_recovery`]) along with
+    /// their traceback, i.e. the chain of their causes (as `caused by: ...` lines). The
+    /// multi-line errors are collapsed into their first line, until they are expanded with
+    /// `Ctrl+O` (see [`App::with_auto_collapse_threshold`]). (default: `false`)
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use anyhow::Context;
+    /// use shelgon::command::{self, CommandInput, Execute, OutputAction};
+    /// use shelgon::App;
+    ///
+    /// struct Py;
+    ///
+    /// impl Execute for Py {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         ">>>".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         let path = input.command.trim_start_matches("import ");
+    ///         std::fs::read_to_string(path)
+    ///             .with_context(|| format!("cannot import {}", path))
+    ///             .context("ModuleNotFoundError")?;
+    ///         Ok(OutputAction::Exit)
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Py, ())
+    ///     .with_error_recovery(true)
+    ///     .with_collapsible_tracebacks(true);
+    /// app.run_command("import /missing.py", None)?;
+    ///
+    /// // the traceback is kept, but only its first line is rendered.
+    /// assert!(app.history()[0].collapsed);
+    /// assert!(app.history()[0].stderr.len() > 1);
+    /// let html = app.transcript_html();
+    /// assert!(html.contains("ModuleNotFoundError"));
+    /// assert!(!html.contains("cannot import /missing.py"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_collapsible_tracebacks(mut self, collapsible: bool) -> Self {
+        self.settings.collapsible_tracebacks = collapsible;
+        self
+    }
+
+    /// Restore the buffer (and the cursor) as it was submitted, when the command fails (see
+    /// [`App::with_error_recovery`]), instead of resetting to an empty buffer, so that the
+    /// command can be fixed and retried. The commands run via [`App::run_command`] aren't typed,
+    /// so there's nothing to restore for them. With multiple commands on a line (see
+    /// [`App::with_command_separator`]), the commands after the failed one are restored along
+    /// with it, instead of being executed. (default: `false`)
+    pub fn with_restore_on_error(mut self, restore: bool) -> Self {
+        self.settings.restore_on_error = restore;
+        self
+    }
+
+    /// Align the output (stdout and stderr) with the command, i.e. indent the output lines by the
+    /// display width of the prompt (and the separator). (default: `false`)
+    pub fn with_output_align_to_command(mut self, align: bool) -> Self {
+        self.settings.align_output = align;
+        self
+    }
+
+    /// Render the `lines` (dimmed) above the prompt while the history is empty, e.g. a hint like
+    /// `Type a command, Tab to complete, Ctrl+D to exit`. The lines disappear once a command is
+    /// executed.
+    pub fn with_empty_placeholder(mut self, lines: Vec<String>) -> Self {
+        self.settings.empty_placeholder = lines;
+        self
+    }
+
+    /// Make `Up` recall the most recent command that starts with the typed text (like the
+    /// `history-search-backward` of bash), instead of the most recent command. Pressing `Up` again
+    /// recalls the older matching commands. (default: `false`)
+    pub fn with_prefix_history_search(mut self, prefix_search: bool) -> Self {
+        self.settings.prefix_history_search = prefix_search;
+        self
+    }
+
+    /// Set the style of the prefix that the command recalled by the prefix search (see
+    /// [`App::with_prefix_history_search`]) matched, so that it stands out from the rest of the
+    /// recalled command. The style is applied over the style of the command.
+    /// (default: underlined)
+    pub fn with_history_match_style(mut self, style: Style) -> Self {
+        self.settings.history_match_style = style;
+        self
+    }
+
+    /// Set what `Up` does when something is already typed: overwrite it, keep it (only recalling
+    /// into an empty buffer), or stash it to be restored with `Down`. (default:
+    /// [`HistoryRecallMode::Stash`])
+    pub fn with_history_recall_mode(mut self, mode: HistoryRecallMode) -> Self {
+        self.settings.history_recall_mode = mode;
+        self
+    }
+
+    /// Render a scrollbar along the right edge, reflecting the position of the view (scrolled
+    /// with `PageUp` and `PageDown`) in the content. A column is reserved for the scrollbar.
+    /// (default: `false`)
+    pub fn with_scrollbar(mut self, scrollbar: bool) -> Self {
+        self.settings.scrollbar = scrollbar;
+        self
+    }
+
+    /// Set whether executing a command scrolls the view back to the bottom, to follow the new
+    /// output. Otherwise, a view that is scrolled up stays put (for reading), as the output is
+    /// added below it. (default: `true`)
+    pub fn with_follow_output(mut self, follow: bool) -> Self {
+        self.settings.follow_output = follow;
+        self
+    }
+
+    /// Set whether only the history entries that are in view are rendered, skipping the rest
+    /// entirely. This keeps the frames cheap with huge histories, at the cost of an estimated
+    /// position of the scrollbar (the entries that are skipped are counted by their lines,
+    /// without the wrapping). (default: `false`)
+    pub fn with_lazy_rendering(mut self, lazy: bool) -> Self {
+        self.settings.lazy_rendering = lazy;
+        self
+    }
+
+    /// Set whether the shell is rendered with colors. Without colors, everything is rendered
+    /// with the default style, keeping the layout as is. (default: [`ColorMode::Auto`])
+    pub fn with_color(mut self, mode: ColorMode) -> Self {
+        self.settings.colors = mode.enabled();
+        self
+    }
+
+    /// Run `hook` when the shell starts, right after the terminal is set up (raw mode, alternate
+    /// screen, etc.). The hook receives the terminal output, e.g. for enabling keyboard
+    /// enhancement flags.
+    ///
+    /// If the hook fails, the terminal is restored, and [`App::execute`] returns the error.
+    pub fn with_setup_hook(
+        mut self,
+        hook: impl FnMut(&mut dyn io::Write) -> anyhow::Result<()> + 'static,
+    ) -> Self {
+        self.settings.setup_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` when the shell exits, right before the terminal is restored. The hook receives
+    /// the terminal output, e.g. for disabling what was enabled by the setup hook.
+    ///
+    /// If the hook fails, the terminal is still restored, and [`App::execute`] returns the error.
+    pub fn with_teardown_hook(
+        mut self,
+        hook: impl FnMut(&mut dyn io::Write) -> anyhow::Result<()> + 'static,
+    ) -> Self {
+        self.settings.teardown_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Set how control characters in the typed or pasted input are handled.
+    /// (default: [`ControlCharPolicy::Strip`])
+    pub fn with_control_char_policy(mut self, policy: ControlCharPolicy) -> Self {
+        self.settings.control_char_policy = policy;
+        self
+    }
+
+    /// Limit the length of the command to `max` characters. The input past the limit is ignored
+    /// (with the feedback, see [`App::with_input_limit_feedback`]), which keeps pathologically long
+    /// pastes from slowing down the editing. (default: unlimited)
+    pub fn with_max_input_length(mut self, max: usize) -> Self {
+        self.settings.max_input_length = Some(max);
+        self
+    }
+
+    /// Set the feedback that is given when the input is cut at the maximum length (see
+    /// [`App::with_max_input_length`]). (default: [`InputLimitFeedback::None`])
+    pub fn with_input_limit_feedback(mut self, feedback: InputLimitFeedback) -> Self {
+        self.settings.input_limit_feedback = feedback;
+        self
+    }
+
+    /// Record every event received by the shell to the file at `path`, along with timestamps.
+    ///
+    /// The file is truncated if it already exists. The recording can later be fed back to the
+    /// shell with [`App::replay`], which turns user sessions into reproducible bug reports.
+    pub fn with_record(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        self.recorder = Some(record::Recorder::create(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// Replay the events recorded (via [`App::with_record`]) in the file at `path`.
+    ///
+    /// The recorded events are processed back to back when the shell starts, before any input is
+    /// read from the terminal.
+    pub fn replay(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        self.replay.extend(record::load(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// Render the shell.
+    fn render(&mut self, frame: &mut Frame) {
+        let prompt = truncate_prompt(&self.executor.prompt(&self.context), &self.settings);
+        let area = frame.area();
+        // the key hints take the bottom row, when enabled.
+        let area = match self.settings.key_hints {
+            true => {
+                let height = area.height.saturating_sub(1);
+                let footer = Rect {
+                    y: area.y + height,
+                    height: area.height - height,
+                    ..area
+                };
+                let style = match self.settings.colors {
+                    true => Style::default().dim(),
+                    false => Style::default(),
+                };
+                let hints = Line::styled(self.settings.key_map.hints(), style);
+                frame.render_widget(Paragraph::new(hints), footer);
+                Rect { height, ..area }
+            }
+            false => area,
+        };
+        // the scrollbar takes the rightmost column, when enabled.
+        let (area, scrollbar_area) = match self.settings.scrollbar {
+            true => {
+                let width = area.width.saturating_sub(1);
+                let scrollbar_area = Rect {
+                    x: area.x + width,
+                    width: area.width - width,
+                    ..area
+                };
+                (Rect { width, ..area }, Some(scrollbar_area))
+            }
+            false => (area, None),
+        };
+        self.viewport_height = area.height;
+        // the content below the history, i.e. the placeholder and the prompt. The history is
+        // rendered above it, last.
+        let mut text_content = Vec::new();
+        if self.history.is_empty() {
+            text_content.extend(
+                self.settings
+                    .empty_placeholder
+                    .iter()
+                    .map(|line| Line::styled(line.clone(), Style::default().dim())),
+            );
+        }
+
+        if let Some(tabs) = self.group_tabs() {
+            text_content.push(tabs);
+        }
+
+        // the line (in `text_content`) and the column (in cells) of the cursor.
+        let cursor_at = match &self.state {
+            State::Idle(ref cmd, cursor, comp) => {
+                let (prompt_line, prefix) = self.live_prefix(&prompt);
+                text_content.extend(prompt_line);
+                let column = command_column(&prefix);
+                // the command is rendered as multiple rows, if it has newlines.
+                let row = cmd[..*cursor].rsplit('\n').next().unwrap_or_default();
+                let cells_before = column + row.width();
+                // the completions are inserted at the cursor, in place of the text typed since.
+                let typed = comp.as_ref().map_or(0, |comp| comp.typed.len());
+                let before_cursor = &cmd[..*cursor - typed];
+                let after_cursor = &cmd[*cursor..];
+                let (left_cmd, right_cmd) = cmd.split_at(*cursor);
+                let command_style = match (comp, self.settings.completing_command_style) {
+                    (Some(_), Some(style)) => style,
+                    _ => Style::default().bold(),
+                };
+                // the prefix that the recalled command matched is styled apart from the rest.
+                let matched = match self.recall {
+                    Some(ref recall) if cmd.starts_with(&recall.prefix) => recall.prefix.len(),
+                    _ => 0,
+                };
+                let match_style = command_style.patch(self.settings.history_match_style);
+                let styled = |start: usize, text: &str| {
+                    let (head, tail) = text.split_at(matched.saturating_sub(start).min(text.len()));
+                    [
+                        Span::styled(head.to_string(), match_style),
+                        Span::styled(tail.to_string(), command_style),
+                    ]
+                };
+                let cursor_style =
+                    match cursor_visible(self.blink_epoch.elapsed(), self.settings.cursor_blink) {
+                        true => selected_style(self.settings.colors),
+                        false => command_style,
+                    };
+                let (cursor, right_cmd) = match right_cmd {
+                    "" => {
+                        let cursor = Span::styled(" ", cursor_style);
+                        (cursor, styled(cmd.len(), ""))
+                    }
+                    right_cmd => {
+                        //
+                        // # Safety: `right_cmd` will never be empty.
+                        //
+                        #[allow(clippy::expect_used)]
+                        let under = right_cmd.chars().next().expect("match statement failed");
+                        let cursor = match under {
+                            // the cursor on a newline is rendered at the end of its row.
+                            '\n' => " \n".to_string(),
+                            c => c.to_string(),
+                        };
+                        let cursor = Span::styled(cursor, cursor_style);
+                        // the character under the cursor spans multiple bytes, if it isn't ASCII.
+                        let after = under.len_utf8();
+
+                        (cursor, styled(left_cmd.len() + after, &right_cmd[after..]))
+                    }
+                };
+
+                let mut line = prefix;
+                line.extend(styled(0, left_cmd));
+                // the cursor is at the end of the line up to it, split and wrapped the same way.
+                let mut through_cursor = line.clone();
+                through_cursor.push(Span::raw(cursor.content.trim_end_matches('\n').to_string()));
+                let cursor_width = through_cursor.last().map_or(0, Span::width);
+                line.push(cursor);
+                line.extend(right_cmd);
+                let mut line = Line::from(line);
+                let mut through_cursor = Line::from(through_cursor);
+                let width = usize::from(area.width);
+                if self.settings.horizontal_scroll && !cmd.contains('\n') {
+                    // the line is cropped to the viewport, shifted just enough to keep the cursor
+                    // visible.
+                    let offset = cells_before.saturating_sub(width.saturating_sub(1));
+                    line = crop_line(line, offset, width);
+                    through_cursor = crop_line(through_cursor, offset, width);
+                }
+                let cursor_rows = split_rows(through_cursor, column, self.settings.gutter)
+                    .into_iter()
+                    .flat_map(|row| wrap_cells(row, width))
+                    .collect::<Vec<_>>();
+                let cursor_at = (
+                    text_content.len() + cursor_rows.len().saturating_sub(1),
+                    cursor_rows
+                        .last()
+                        .map_or(0, Line::width)
+                        .saturating_sub(cursor_width),
+                );
+                text_content.extend(
+                    split_rows(line, column, self.settings.gutter)
+                        .into_iter()
+                        .flat_map(|row| wrap_cells(row, width)),
+                );
+
+                if let Some(comp) = comp {
+                    // Candidates are indented by the display width of the prompt, so that they
+                    // line up with the command even when the prompt contains wide glyphs.
+                    let indent = " ".repeat(column);
+                    let completions = comp
+                        .candidates
+                        .iter()
+                        .enumerate()
+                        .map(|(index, cmp)| {
+                            let style = match comp.selected == Some(index) {
+                                true => self
+                                    .settings
+                                    .completion_selected_style
+                                    .unwrap_or(selected_style(self.settings.colors).bold()),
+                                false => self.settings.completion_style,
+                            };
+                            Line::from(vec![
+                                Span::raw(indent.clone()),
+                                Span::styled(
+                                    format!("{}{}{}", before_cursor, cmp, after_cursor),
+                                    style,
+                                ),
+                            ])
+                        })
+                        .collect::<Vec<_>>();
+                    text_content.extend(completions);
+                }
+
+                if let Some(ref notice) = self.notice {
+                    text_content.push(Line::styled(notice.clone(), Style::default().dim()));
+                }
+
+                if self.settings.preview {
+                    let preview = self.executor.preview(&self.context, cmd);
+                    text_content.extend(
+                        preview
+                            .into_iter()
+                            .flatten()
+                            .map(|line| line.patch_style(Style::default().dim())),
+                    );
+                }
+
+                if self.settings.inline_help {
+                    text_content
+                        .extend(self.executor.help(&self.context, cmd).into_iter().flatten());
+                }
+
+                cursor_at
+            }
+            State::Running(ref prep, stdin, _) => {
+                let (prompt_line, mut line) = self.live_prefix(&prompt);
+                text_content.extend(prompt_line);
+                let command_line = text_content.len();
+                line.push(Span::styled(prep.command.clone(), Style::default().bold()));
+                text_content.push(Line::from(line));
+
+                // the stdin lines are prefixed with the prompt for the stdin, if any.
+                let gutter = match prep.stdin_prompt {
+                    Some(ref stdin_prompt) => prompt_spans(stdin_prompt),
+                    None => gutter_spans(self.settings.gutter),
+                };
+                let gutter_width = gutter.iter().map(Span::width).sum::<usize>();
+                let shown = match prep.mask_stdin {
+                    Some(mask) => stdin.iter().map(|line| masked(line, mask)).collect(),
+                    None => stdin.clone(),
+                };
+                text_content.extend(shown.iter().flat_map(|line| {
+                    let mut spans = gutter.clone();
+                    spans.push(Span::raw(line.clone()));
+                    wrap_cells(Line::from(spans), usize::from(area.width))
+                }));
+                // the line that is being typed is marked, even before anything is typed.
+                if stdin.is_empty() && !gutter.is_empty() {
+                    text_content.push(Line::from(gutter));
+                }
+
+                // the typed input is appended to the last stdin line, or starts a new one.
+                let cursor_at = match text_content.last() {
+                    Some(last) if !shown.is_empty() => (text_content.len() - 1, last.width()),
+                    _ => (command_line + 1, gutter_width),
+                };
+
+                if let Some(status) = self.executor.stdin_status(&self.context, prep, stdin) {
+                    // the status is rendered below the line that is being typed.
+                    if text_content.len() == command_line + 1 {
+                        text_content.push(Line::default());
+                    }
+                    text_content.push(status);
+                }
+
+                if let Some(ref notice) = self.notice {
+                    if text_content.len() == command_line + 1 {
+                        text_content.push(Line::default());
+                    }
+                    text_content.push(Line::styled(notice.clone(), Style::default().dim()));
+                }
+
+                cursor_at
+            }
+        };
+
+        let wrap = Wrap { trim: false };
+        let group = self.active_group();
+        let render_entry = |index: usize| {
+            let history = &self.history[index];
+            if !in_group(history, group) {
+                return Vec::new();
+            }
+            let pending = self.pending.iter().any(|(pending, _, _)| *pending == index);
+            let mut lines = match self.typewriter {
+                Some(ref typewriter) if typewriter.index == index => render_history(
+                    &self.executor,
+                    &reveal(history, typewriter.revealed),
+                    &self.settings,
+                    area.width,
+                    false,
+                ),
+                _ => render_history(
+                    &self.executor,
+                    history,
+                    &self.settings,
+                    area.width,
+                    !pending,
+                ),
+            };
+            if pending {
+                lines.push(Line::styled("…", Style::default().dim()));
+            }
+            lines
+                .into_iter()
+                .map(|line| match self.search {
+                    Some(ref term) => highlight(line, term, self.settings.colors),
+                    None => line,
+                })
+                .collect::<Vec<_>>()
+        };
+        // lazily, the entries are rendered from the bottom, until the view is filled. The rows of
+        // the entries above are estimated.
+        let (history_content, skipped_rows) = match self.settings.lazy_rendering {
+            true => {
+                let rows_in_view = self.scroll_offset + usize::from(area.height);
+                let mut rows = Paragraph::new(text_content.clone())
+                    .wrap(wrap)
+                    .line_count(area.width);
+                let mut entries = Vec::new();
+                let mut skipped = self.history.len();
+                while skipped > 0 && rows < rows_in_view {
+                    skipped -= 1;
+                    let lines = render_entry(skipped);
+                    rows += Paragraph::new(lines.clone())
+                        .wrap(wrap)
+                        .line_count(area.width);
+                    entries.push(lines);
+                }
+                let skipped_rows = self.history[..skipped]
+                    .iter()
+                    .filter(|history| in_group(history, group))
+                    .map(|history| estimated_rows(history, &self.settings))
+                    .sum::<usize>();
+                (
+                    entries.into_iter().rev().flatten().collect::<Vec<_>>(),
+                    skipped_rows,
+                )
+            }
+            false => ((0..self.history.len()).flat_map(render_entry).collect(), 0),
+        };
+        let cursor_at = (cursor_at.0 + history_content.len(), cursor_at.1);
+        text_content.splice(0..0, history_content);
+
+        // the paragraph follows the bottom of the content, and the cursor is tracked through the
+        // wrapping and the scroll.
+        let (cursor_line, cursor_column) = cursor_at;
+        let cursor_row =
+            Paragraph::new(text_content[..cursor_line.min(text_content.len())].to_vec())
+                .wrap(wrap)
+                .line_count(area.width);
+        // the paragraph wraps a row of only whitespace past an empty row, e.g. the cursor alone.
+        let blank_row = text_content.get(cursor_line).is_some_and(|line| {
+            let mut chars = line.spans.iter().flat_map(|span| span.content.chars());
+            chars.clone().next().is_some() && chars.all(char::is_whitespace)
+        });
+        let cursor_row = cursor_row + usize::from(blank_row);
+
+        if !self.settings.colors {
+            text_content = text_content.into_iter().map(strip_colors).collect();
+        }
+
+        let text_para = Paragraph::new(text_content).wrap(wrap);
+        let rendered_length = text_para.line_count(area.width);
+        let max_scroll = (skipped_rows + rendered_length).saturating_sub(area.height.into());
+        // the view follows the bottom of the content, unless it's scrolled up. Without following
+        // the output, the view that is scrolled up stays put as the content grows below it.
+        if !self.settings.follow_output && self.scroll_offset > 0 {
+            self.scroll_offset += max_scroll.saturating_sub(self.max_scroll);
+        }
+        self.max_scroll = max_scroll;
+        self.scroll_offset = self.scroll_offset.min(max_scroll);
+        // the scroll of the rendered content, i.e. below the skipped rows (if any).
+        let scroll = rendered_length
+            .saturating_sub(area.height.into())
+            .saturating_sub(self.scroll_offset);
+
+        self.cursor_position = match area.width {
+            0 => None,
+            width => {
+                let row = (cursor_row + cursor_column / usize::from(width)).checked_sub(scroll);
+                let column = cursor_column % usize::from(width);
+                // the casts are lossless, as the row and the column are bounded by the area.
+                row.filter(|row| *row < area.height.into())
+                    .map(|row| (area.y + row as u16, area.x + column as u16))
+            }
+        };
+
+        let text_para = text_para.scroll((scroll.try_into().unwrap_or(u16::MAX), 0));
+        frame.render_widget(text_para, area);
+
+        if let Some(scrollbar_area) = scrollbar_area {
+            // the positions are the rows that the view can be scrolled to.
+            let mut state = ScrollbarState::new(max_scroll + 1)
+                .position(skipped_rows + scroll)
+                .viewport_content_length(area.height.into());
+            let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None);
+            frame.render_stateful_widget(scrollbar, scrollbar_area, &mut state);
+        }
+    }
+
+    /// The spans before the command that is being typed (see [`command_prefix`]), with the
+    /// segments of the prompt context (in their style) and the prompt symbol (in bold), if any,
+    /// after the (truncated) `prompt`.
+    fn live_prefix(&self, prompt: &str) -> (Option<Line<'static>>, Vec<Span<'static>>) {
+        let (mut prompt_line, mut prefix) = command_prefix(prompt, &self.settings);
+        let segments = self
+            .executor
+            .prompt_context(&self.context)
+            .into_iter()
+            .chain(
+                self.executor
+                    .prompt_symbol(&self.context)
+                    .map(|symbol| (symbol.to_string(), Style::default().blue().bold())),
+            )
+            .filter(|(text, _)| !text.is_empty())
+            .flat_map(|(text, style)| {
+                [
+                    Span::styled(" ", Style::default().blue()),
+                    Span::styled(text, style),
+                ]
+            })
+            .collect::<Vec<_>>();
+        match prompt_line {
+            Some(ref mut line) => line.spans.extend(segments),
+            None => {
+                prefix.splice(1..1, segments);
+            }
+        }
+        (prompt_line, prefix)
+    }
+
+    /// Run the `command` as if it was typed (with the `stdin`, if any), and return the output of
+    /// the executor.
+    ///
+    /// The command is prepared and executed, and the output is added to the history, the same way
+    /// as the commands that are typed. Any input that is being typed (or stdin that is being
+    /// collected) is discarded.
+    pub fn run_command(
+        &mut self,
+        command: &str,
+        stdin: Option<Vec<String>>,
+    ) -> anyhow::Result<command::OutputAction> {
+        let prepare = self.executor.prepare(command);
+        let recorded = self.recorded_command(command, &prepare);
+        // the next action (e.g. exiting) is left to the caller, through the returned output.
+        let (_, action) = match stdin {
+            Some(stdin) => {
+                let stdin = Some((stdin, prepare.mask_stdin));
+                self._final_execution(&prepare.command, stdin, recorded)?
+            }
+            None => self._final_execution(command, None, recorded)?,
+        };
+        Ok(action)
+    }
+
+    /// Queue the `command` to be executed as if it was typed, once the shell is idle (and nothing
+    /// is being typed). The queued commands are executed in order, interleaved with the input of
+    /// the user.
+    pub fn enqueue_command(&mut self, command: impl Into<String>) {
+        self.queue.push_back(command.into());
+    }
+
+    /// The position of the cursor on the screen, as `(row, column)`, as of the last render.
+    ///
+    /// This accounts for the wrapping of the lines, and the scrolling of the content. This is
+    /// `None` before the first render, or when the cursor is outside of the viewport.
+    pub fn cursor_position(&self) -> Option<(u16, u16)> {
+        self.cursor_position
+    }
+
+    /// Handle the input from the user.
+    fn input(&mut self, event: crossterm::event::Event) -> anyhow::Result<Next> {
+        if let crossterm::event::Event::Key(ke) = event {
+            let action = self.settings.key_map.action(ke.code, ke.modifiers);
+            if ke.kind != KeyEventKind::Release {
+                self.notice = None;
+                self.blink_epoch = Instant::now();
+                if !matches!(action, Some(Action::HistoryPrevious | Action::HistoryNext)) {
+                    self.recall = None;
+                }
+            }
+
+            match (ke.kind, action) {
+                (KeyEventKind::Release, _) => {
+                    // Ignore Release events, prevents getting double keypresses on windows
+                }
+                (_, Some(Action::Clear)) => {
+                    if self.executor.should_clear(&self.context) {
+                        self.clear_history();
+                    }
+                    return Ok(Next::Continue);
+                }
+
+                (_, Some(action @ (Action::Interrupt | Action::Eof))) => {
+                    if action == Action::Interrupt
+                        && matches!(self.state, State::Idle(..))
+                        && (self.typewriter.take().is_some() || self.cancel_pending())
+                    {
+                        return Ok(Next::Continue);
+                    }
+
+                    if let State::Running(..) = &self.state {
+                        let stdin_action = match action {
+                            Action::Interrupt => self.settings.stdin_interrupt,
+                            _ => self.settings.stdin_eof,
+                        };
+                        return match stdin_action {
+                            StdinAction::Submit => self.continue_execution(),
+                            StdinAction::Abort => {
+                                self.state = State::Idle(String::new(), 0, None);
+                                Ok(Next::Continue)
+                            }
+                        };
+                    } else if self.executor.should_exit(&self.context) {
+                        return Ok(Next::Exit("".to_string()));
+                    } else {
+                        return Ok(Next::Continue);
+                    }
+                }
+                (_, Some(Action::NextGroup)) => self.next_group(),
+                (_, Some(Action::Expand)) => {
+                    if let Some(output) = self
+                        .history
+                        .iter_mut()
+                        .rev()
+                        .find(|output| output.collapsed)
+                    {
+                        output.collapsed = false;
+                    }
+                }
+                (_, Some(Action::Highlight)) => {
+                    if let State::Idle(ref cmd, _, _) = self.state {
+                        match cmd.is_empty() {
+                            true => self.clear_highlight(),
+                            false => self.highlight(cmd.clone()),
+                        }
+                    }
+                }
+                (_, Some(Action::CursorLeft)) => self.move_cursor_left(),
+                (_, Some(Action::CursorRight)) => self.move_cursor_right(),
+                (_, Some(Action::CursorHome)) => self.move_cursor_to(|_| 0),
+                (_, Some(Action::CursorEnd)) => self.move_cursor_to(str::len),
+                (_, Some(Action::Complete)) if self.tab_indent().is_some() => {
+                    let indent = self.tab_indent().unwrap_or_default();
+                    self.insert_text(&" ".repeat(indent));
+                }
+                (_, Some(Action::Complete)) => match self.state {
+                    State::Idle(_, _, Some(ref mut comp)) => {
+                        comp.select_next(self.settings.completion_wrap);
+                    }
+                    State::Idle(ref mut cmd, ref mut cursor, ref mut comp @ None) => {
+                        let mid_line = (*cursor != cmd.len()).then_some(self.settings.mid_line_tab);
+                        match mid_line {
+                            // the spaces are inserted as the indent, above. (see `tab_indent`)
+                            Some(MidLineTab::Ignore | MidLineTab::InsertSpaces(_)) => {}
+                            None | Some(MidLineTab::Complete) => {
+                                let mut result = complete(
+                                    &self.executor,
+                                    &self.providers,
+                                    &self.context,
+                                    cmd,
+                                    *cursor,
+                                )?;
+                                // an invalid range (e.g. outside the buffer) is ignored, as
+                                // an empty completion.
+                                let (completed, at) = match result.apply(cmd) {
+                                    Some(applied) => applied,
+                                    None => {
+                                        result.candidates.clear();
+                                        (cmd.clone(), *cursor)
+                                    }
+                                };
+                                if completed == *cmd && result.candidates.is_empty() {
+                                    self.settings
+                                        .empty_completion_feedback
+                                        .apply(&mut self.notice, &mut self.bell);
+                                }
+                                (*cmd, *cursor) = (completed, at);
+                                let typed = &cmd[..*cursor];
+                                rank_by_frequency(&self.frequency, typed, &mut result.candidates);
+                                *comp = Some(Completions::new(result.candidates));
+                            }
+                        }
+                    }
+                    State::Running(ref prep, ref mut stdin, _) => {
+                        let partial = stdin.last().map(String::as_str).unwrap_or_default();
+                        let (fixed, variable) =
+                            self.executor
+                                .stdin_completion(&self.context, prep, partial)?;
+                        if fixed.is_empty() && variable.is_empty() {
+                            self.settings
+                                .empty_completion_feedback
+                                .apply(&mut self.notice, &mut self.bell);
+                        }
+                        // the candidates are listed on a single line, until the next key press.
+                        if !variable.is_empty() {
+                            let prefix = format!("{}{}", partial, fixed);
+                            let candidates = variable
+                                .iter()
+                                .map(|candidate| format!("{}{}", prefix, candidate))
+                                .collect::<Vec<_>>();
+                            self.notice = Some(candidates.join("  "));
+                        }
+                        match stdin.last_mut() {
+                            Some(line) => line.push_str(&fixed),
+                            None if !fixed.is_empty() => stdin.push(fixed),
+                            None => {}
+                        }
+                    }
+                },
+                (_, Some(Action::CompletePrevious)) => {
+                    if let State::Idle(_, _, Some(ref mut comp)) = self.state {
+                        comp.select_previous(self.settings.completion_wrap);
+                    }
+                }
+                (_, Some(Action::Dismiss)) => {
+                    if let State::Idle(_, _, ref mut comp) = self.state {
+                        *comp = None;
+                    }
+                }
+                (_, Some(Action::Backspace)) => {
+                    self.cursor_backspace();
+                }
+                (_, Some(Action::Submit)) => match self.state {
+                    State::Idle(ref mut cmd, ref mut cursor, ref mut comp) => {
+                        let completions = comp.take();
+                        let selected = completions
+                            .as_ref()
+                            .and_then(|comp| Some((comp.selected()?, comp.typed.len())));
+                        match selected {
+                            Some((selected, typed)) => {
+                                // the candidate replaces the text typed since the completion.
+                                let start = *cursor - typed;
+                                cmd.replace_range(start..*cursor, selected);
+                                *cursor = start + selected.len();
+                            }
+                            None if cmd.trim().is_empty() => match self.settings.blank_line {
+                                BlankLine::Execute => return self.execute_command(),
+                                BlankLine::Ignore | BlankLine::SubmitBlock => {}
+                                BlankLine::FreshPrompt => {
+                                    let output = command::CommandOutput {
+                                        prompt: full_prompt(&self.executor, &self.context),
+                                        command: std::mem::take(cmd),
+                                        ..Default::default()
+                                    };
+                                    *cursor = 0;
+                                    self.push_history(output);
+                                }
+                            },
+                            None => return self.execute_command(),
+                        }
+                    }
+                    State::Running(ref mut _pre, ref mut stdin, _) => {
+                        // the stdin is read from the file, with the escape (e.g. `:read path`).
+                        let path = self.settings.stdin_read_escape.as_ref().and_then(|escape| {
+                            let path = stdin.last()?.strip_prefix(escape.as_str())?;
+                            path.starts_with(' ').then(|| path.trim().to_string())
+                        });
+                        if let Some(path) = path {
+                            match std::fs::read_to_string(&path) {
+                                Ok(contents) => {
+                                    stdin.pop();
+                                    stdin.extend(contents.lines().map(String::from));
+                                    return self.continue_execution();
+                                }
+                                Err(e) => {
+                                    self.notice = Some(format!("cannot read {}: {}", path, e));
+                                    return Ok(Next::Continue);
+                                }
+                            }
+                        }
+
+                        let blank = stdin.last().map_or(true, String::is_empty);
+                        match (blank, self.settings.blank_line) {
+                            (true, BlankLine::SubmitBlock) => {
+                                stdin.pop();
+                                return self.continue_execution();
+                            }
+                            _ => stdin.push(String::new()),
+                        }
+                    }
+                },
+                (_, Some(Action::HistoryPrevious)) => self.recall_previous(),
+                (_, Some(Action::HistoryNext)) => self.recall_next(),
+                (_, Some(Action::ScrollUp)) => {
+                    self.scroll_offset += self.page_size();
+                }
+                (_, Some(Action::ScrollDown)) => {
+                    self.scroll_offset = self.scroll_offset.saturating_sub(self.page_size());
+                }
+                // the keys that aren't bound to an action type their character, if any.
+                (_, None) => {
+                    if let (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) =
+                        (ke.code, ke.modifiers)
+                    {
+                        self.insert_text(&c.to_string());
+                    }
+                }
+            }
+        } else if let crossterm::event::Event::Paste(text) = event {
+            self.recall = None;
+            match self.state {
+                State::Running(..) if self.settings.stdin_paste_lines => self.paste_lines(&text),
+                State::Idle(..) if self.settings.multiline_paste => self.paste_lines(&text),
+                _ => self.insert_text(&text),
+            }
+        }
+
+        Ok(Default::default())
+    }
+
+    /// Execute the shell.
+    ///
+    /// This is the main method that is used to execute the shell. This is where the shell is
+    /// created and the input is handled. This also converts the shell into raw mode and enables
+    /// the alternate screen.
+    ///
+    /// This method returns an `anyhow::Result<()>` which is used to handle the errors that are
+    /// encountered during the execution of the shell.
+    ///
+    pub fn execute(self) -> anyhow::Result<String> {
+        self.execute_and_return().map(|(msg, _)| msg)
+    }
+
+    /// Execute the shell, and return the [`App`] back once the shell exits.
+    ///
+    /// This is the same as [`App::execute`], but the [`App`] isn't consumed. This allows the
+    /// caller to inspect (or persist) the history and the context after the shell exits.
+    ///
+    pub fn execute_and_return(self) -> anyhow::Result<(String, Self)> {
+        self.execute_on(io::stdout(), Viewport::Fullscreen)
+    }
+
+    /// Execute the shell, writing the terminal output to `out` (e.g. the stdout) and rendering to
+    /// the `viewport`, and return the [`App`] back once the shell exits.
+    fn execute_on<W: io::Write>(
+        mut self,
+        mut out: W,
+        viewport: Viewport,
+    ) -> anyhow::Result<(String, Self)> {
+        self.setup_terminal(&mut out)?;
+        let backend = CrosstermBackend::new(out);
+        let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
+
+        let response = self.run(&mut terminal);
+
+        self.teardown_terminal(terminal.backend_mut())?;
+        terminal.show_cursor()?;
+
+        #[cfg(feature = "serde")]
+        if let Some(path) = self.settings.session_file.as_ref() {
+            if let Err(e) = session::save(path, &self.history) {
+                tracing::warn!("failed to save the session to {:?}: {}", path, e);
+            }
+        }
+
+        response.map(|msg| (msg, self))
+    }
+
+    /// Run the shell on the terminal (that is set up), until it exits.
+    fn run<B: TerminalOutput>(&mut self, terminal: &mut Terminal<B>) -> anyhow::Result<String> {
+        // the screen is cleared (if enabled) before the first frame, the same way as on request.
+        self.redraw = self.settings.clear_on_start;
+        loop {
+            if let Some(msg) = self.step(terminal)? {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// The history of the commands that are executed.
+    pub fn history(&self) -> &[command::CommandOutput] {
+        &self.history
+    }
+
+    /// The history as an HTML document, rendered the same way as on the screen, with the styles
+    /// inlined (e.g. the stderr in red, and the highlighted occurrences of the search term), so
+    /// that the session can be saved along with its styles.
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::renderer::ColorMode;
+    /// use shelgon::App;
+    ///
+    /// struct Check;
+    ///
+    /// impl Execute for Check {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             stderr: vec!["1 < 2 failed".to_string()],
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// // the colors are forced, regardless of `NO_COLOR`.
+    /// let mut app = App::new_with_executor(runtime, Check, ()).with_color(ColorMode::Always);
+    /// app.run_command("check", None)?;
+    ///
+    /// let html = app.transcript_html();
+    /// // the prompt is blue, and the stderr is red.
+    /// assert!(html.contains(r#"<span style="color: #0000ee">$</span>"#));
+    /// assert!(html.contains(r#"<span style="color: #cd0000">1 &lt; 2 failed</span>"#));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn transcript_html(&self) -> String {
+        let group = self.active_group();
+        let lines = self
+            .history
+            .iter()
+            .filter(|history| in_group(history, group))
+            .flat_map(|history| {
+                render_history(
+                    &self.executor,
+                    history,
+                    &self.settings,
+                    TRANSCRIPT_WIDTH,
+                    true,
+                )
+            })
+            .map(|line| match self.search {
+                Some(ref term) => highlight(line, term, self.settings.colors),
+                None => line,
+            })
+            .map(|line| match self.settings.colors {
+                true => line,
+                false => strip_colors(line),
+            })
+            .collect::<Vec<_>>();
+        html::document(&lines)
+    }
+
+    /// The commands in the history, in the `format` of a shell history file (e.g. to import the
+    /// session into the history of bash or zsh). The blank commands are left out.
+    ///
+    #[cfg_attr(feature = "tokio", doc = "```rust")]
+    #[cfg_attr(not(feature = "tokio"), doc = "```rust,ignore")]
+    /// use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+    /// use shelgon::renderer::HistoryFormat;
+    /// use shelgon::App;
+    ///
+    /// struct Git;
+    ///
+    /// impl Execute for Git {
+    ///     type Context = ();
+    ///
+    ///     fn prompt(&self, _: &Self::Context) -> String {
+    ///         "$".to_string()
+    ///     }
+    ///
+    ///     fn prepare(&self, cmd: &str) -> command::Prepare {
+    ///         command::Prepare {
+    ///             command: cmd.to_string(),
+    ///             stdin_required: false,
+    ///             ..Default::default()
+    ///         }
+    ///     }
+    ///
+    ///     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+    ///         Ok(OutputAction::Command(CommandOutput {
+    ///             prompt: input.prompt,
+    ///             command: input.command,
+    ///             ..Default::default()
+    ///         }))
+    ///     }
+    /// }
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    /// let mut app = App::new_with_executor(runtime, Git, ());
+    /// app.run_command("git status", None)?;
+    /// app.run_command("git log", None)?;
+    ///
+    /// assert_eq!(app.export_commands(HistoryFormat::Plain), "git status\ngit log\n");
+    ///
+    /// // e.g. `: 1700000000:0;git status`
+    /// let zsh = app.export_commands(HistoryFormat::ZshExtended);
+    /// for (line, command) in zsh.lines().zip(["git status", "git log"]) {
+    ///     let Some((timestamp, rest)) = line.strip_prefix(": ").and_then(|line| line.split_once(':'))
+    ///     else {
+    ///         panic!("expected the extended history format");
+    ///     };
+    ///     assert!(timestamp.parse::<u64>()? > 0);
+    ///     assert_eq!(rest, format!("0;{}", command));
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn export_commands(&self, format: HistoryFormat) -> String {
+        let mut exported = String::new();
+        for output in self.history.iter() {
+            if output.command.trim().is_empty() {
+                continue;
+            }
+            if format == HistoryFormat::ZshExtended {
+                let timestamp = output
+                    .timestamp
+                    .and_then(|timestamp| timestamp.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map_or(0, |timestamp| timestamp.as_secs());
+                let duration = output.duration.map_or(0, |duration| duration.as_secs());
+                exported.push_str(&format!(": {}:{};", timestamp, duration));
+            }
+            exported.push_str(&output.command);
+            exported.push('\n');
+        }
+        exported
+    }
+
+    /// Highlight all the occurrences of `term` in the history, until cleared.
+    pub fn highlight(&mut self, term: impl Into<String>) {
+        let term = term.into();
+        self.search = (!term.is_empty()).then_some(term);
+    }
+
+    /// Clear the highlights (see [`App::highlight`]).
+    pub fn clear_highlight(&mut self) {
+        self.search = None;
+    }
+
+    /// Clear the screen, redrawing it in full on the next frame, while keeping the history.
+    /// (`Ctrl+L` clears both the screen and the history, see [`App::clear_history`])
+    pub fn clear_screen(&mut self) {
+        self.redraw = true;
+    }
+
+    /// Clear the history, along with the pending outputs that refer to it, and scroll back to the
+    /// bottom.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.pending.clear();
+        self.typewriter = None;
+        self.scroll_offset = 0;
+    }
+
+    /// The context that is maintained by the [`App`].
+    pub fn context(&self) -> &T::Context {
+        &self.context
+    }
+
+    /// The context that is maintained by the [`App`], mutably. This can be used to change the
+    /// context after the [`App`] is created.
+    pub fn context_mut(&mut self) -> &mut T::Context {
+        &mut self.context
+    }
+
+    /// The executor that is used to execute the commands.
+    pub fn executor(&self) -> &T {
+        &self.executor
+    }
+
+    /// The executor that is used to execute the commands, mutably. This can be used to change
+    /// the executor (e.g. its configuration) after the [`App`] is created.
+    pub fn executor_mut(&mut self) -> &mut T {
+        &mut self.executor
+    }
+
+    // helpers
+
+    /// Run a single iteration of the shell: render, then wait for (and handle) the next event.
+    ///
+    /// When there is time-based work (e.g. pending outputs), waiting for the event times out
+    /// after a tick, so that the work can make progress. This returns the exit message once the
+    /// shell exits.
+    fn step<B: TerminalOutput>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+    ) -> anyhow::Result<Option<String>> {
+        if self.suspend(terminal.backend_mut())? {
+            return Ok(None);
+        }
+        if std::mem::take(&mut self.redraw) {
+            terminal.clear()?;
+        }
+        terminal.draw(|f| self.render(f))?;
+
+        let next = match self.next_queued() {
+            Some(cmd) => {
+                let cursor = cmd.len();
+                self.state = State::Idle(cmd, cursor, None);
+                self.execute_command()?
+            }
+            None => match self.next_event()? {
+                Some(event) => {
+                    self.record(&event)?;
+                    self.input(event)?
+                }
+                None => Next::Continue,
+            },
+        };
+        self.tick();
+
+        if std::mem::take(&mut self.bell) {
+            ring_bell();
+        }
+
+        match next {
+            Next::Continue => Ok(None),
+            Next::Exit(msg) => Ok(Some(msg)),
+            Next::Clear => {
+                terminal.clear()?;
+                Ok(None)
+            }
+            Next::Redraw => {
+                // clearing discards the previous frame, so that the next one is drawn in full.
+                terminal.clear()?;
+                terminal.draw(|f| self.render(f))?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Wait for the next event, either from the replay or from the terminal. This returns `None`
+    /// if there is time-based work, and no event arrived within a tick.
+    fn next_event(&mut self) -> anyhow::Result<Option<crossterm::event::Event>> {
+        if let Some(event) = self.replay.pop_front() {
+            return Ok(Some(event));
+        }
+
+        let ready = match self.tick_rate() {
+            Some(rate) => crossterm::event::poll(rate)?,
+            None => true,
+        };
+        match ready {
+            true => Ok(Some(crossterm::event::read()?)),
+            false => Ok(None),
+        }
+    }
+
+    /// The interval at which the shell has to wake up, if there is time-based work, i.e. pending
+    /// outputs, a blinking cursor, an output that is being revealed, the notifications, the
+    /// refresh of the prompt or the raw mode watchdog.
+    fn tick_rate(&self) -> Option<Duration> {
+        let pending = (!self.pending.is_empty()).then_some(TICK_RATE);
+        let blink = match self.state {
+            State::Idle(..) => self.settings.cursor_blink,
+            State::Running(..) => None,
+        };
+        let typewriter = self.typewriter.as_ref().and(self.settings.typewriter);
+        let notifier = self.settings.notifier.as_ref().map(|_| TICK_RATE);
+        let prompt = self.settings.prompt_refresh.then_some(TICK_RATE);
+        let watchdog = self.settings.raw_mode_watchdog.then_some(TICK_RATE);
+        pending
+            .into_iter()
+            .chain(blink)
+            .chain(typewriter)
+            .chain(notifier)
+            .chain(prompt)
+            .chain(watchdog)
+            .min()
+    }
+
+    /// The next queued command, if the shell is ready to execute it, i.e. it's idle and the user
+    /// hasn't started typing.
+    fn next_queued(&mut self) -> Option<String> {
+        match self.state {
+            State::Idle(ref cmd, _, _) if cmd.is_empty() => {
+                self.segments = self.segments.saturating_sub(1);
+                self.queue.pop_front()
+            }
+            _ => None,
+        }
+    }
+
+    /// Make progress on the time-based work: update the pending outputs that have resolved, push
+    /// the notifications, reveal more of the output that is being typed out, and restore the raw
+    /// mode (if enabled).
+    ///
+    /// The lines streamed since the last tick are taken all at once, so that they are rendered in
+    /// the next frame together. (see [`command::OutputWriter`])
+    fn tick(&mut self) {
+        if self.settings.raw_mode_watchdog {
+            self.raw_mode.restore();
+        }
+
+        let notifications = self
+            .settings
+            .notifier
+            .as_ref()
+            .map(command::Notifier::take)
+            .unwrap_or_default();
+        for output in notifications {
+            self.push_history(output);
+        }
+
+        if let (Some(typewriter), Some(interval)) =
+            (self.typewriter.as_mut(), self.settings.typewriter)
+        {
+            typewriter.revealed = revealed_chars(typewriter.started.elapsed(), interval);
+            let total = self
+                .history
+                .get(typewriter.index)
+                .map_or(0, |entry| stdout_chars(&entry.stdout));
+            if typewriter.revealed >= total {
+                self.typewriter = None;
+            }
+        }
+
+        let history = &mut self.history;
+        let recorded = self.settings.history_command_source.is_some();
+        let settings = &self.settings;
+        self.pending.retain(|(index, pending, writer)| {
+            // the output is resolved after the streamed lines are taken, so none are missed.
+            let streamed = writer.take();
+            let output = pending.take();
+            let Some(entry) = history.get_mut(*index) else {
+                return output.is_none();
+            };
+            write_lines(entry, streamed);
+            let Some(output) = output else {
+                return true;
+            };
+            match output {
+                Ok(mut output) => {
+                    // the streamed lines come before the output.
+                    prepend_lines(&mut output, entry.lines());
+                    // the recorded command (see `App::with_history_command_source`) is kept.
+                    if recorded {
+                        output.command = std::mem::take(&mut entry.command);
+                    }
+                    output.timestamp = output.timestamp.or(entry.timestamp);
+                    auto_collapse(&mut output, settings);
+                    *entry = output;
+                }
+                Err(e) => append_lines(
+                    entry,
+                    e.to_string()
+                        .lines()
+                        .map(|line| command::OutputLine::Stderr(line.to_string()))
+                        .collect(),
+                ),
+            }
+            false
+        });
+        self.cap_history();
+    }
+
+    /// Evict the oldest output, so that the history has at most the maximum number of lines. (see
+    /// [`App::with_max_total_lines`])
+    fn cap_history(&mut self) {
+        let Some(max) = self.settings.max_total_lines else {
+            return;
+        };
+        let lines = |entry: &command::CommandOutput| {
+            entry.stdin.len() + entry.stdout.len() + entry.stderr.len()
+        };
+
+        // the whole entries are evicted first, except for the last one.
+        let mut total = self.history.iter().map(lines).sum::<usize>();
+        let mut evicted = 0;
+        while total > max && evicted + 1 < self.history.len() {
+            total -= lines(&self.history[evicted]);
+            evicted += 1;
+        }
+
+        if evicted > 0 {
+            self.history.drain(..evicted);
+            self.pending
+                .retain_mut(|(index, pending, _)| match index.checked_sub(evicted) {
+                    Some(shifted) => {
+                        *index = shifted;
+                        true
+                    }
+                    None => {
+                        pending.abort();
+                        false
+                    }
+                });
+            self.typewriter = self.typewriter.take().and_then(|mut typewriter| {
+                typewriter.index = typewriter.index.checked_sub(evicted)?;
+                Some(typewriter)
+            });
+        }
+
+        // the last entry keeps its latest lines, dropping the stdin first (as it's rendered first),
+        // and then the output in the order it's rendered.
+        if let Some(entry) = self.history.last_mut() {
+            let mut excess = total.saturating_sub(max);
+            let stdin = excess.min(entry.stdin.len());
+            entry.stdin.drain(..stdin);
+            excess -= stdin;
+
+            let (stdout, stderr) = match entry.output.is_empty() {
+                true => {
+                    let stdout = excess.min(entry.stdout.len());
+                    (stdout, (excess - stdout).min(entry.stderr.len()))
+                }
+                false => {
+                    let dropped = entry.output.drain(..excess.min(entry.output.len()));
+                    dropped.fold((0, 0), |(stdout, stderr), line| match line {
+                        command::OutputLine::Stdout(_) => (stdout + 1, stderr),
+                        command::OutputLine::Stderr(_) => (stdout, stderr + 1),
+                    })
+                }
+            };
+            entry.stdout.drain(..stdout.min(entry.stdout.len()));
+            entry.stderr.drain(..stderr.min(entry.stderr.len()));
+        }
+    }
+
+    /// Cancel the pending outputs, rendering `^C` as their output. This returns whether any
+    /// output was pending.
+    fn cancel_pending(&mut self) -> bool {
+        let pending = std::mem::take(&mut self.pending);
+        for (index, pending, writer) in &pending {
+            pending.abort();
+            if let Some(entry) = self.history.get_mut(*index) {
+                write_lines(entry, writer.take());
+                append_lines(entry, vec![command::OutputLine::Stdout("^C".to_string())]);
+            }
+        }
+        !pending.is_empty()
+    }
+
+    /// Restore the terminal when the shell is paused, and set it up again (redrawing the shell
+    /// in full) when it's resumed. This returns whether the shell is paused, waiting for a tick
+    /// if so. The terminal is restored (and set up again) through the output of the `backend`.
+    /// (see [`App::with_pause_handle`])
+    fn suspend(&mut self, backend: &mut impl TerminalOutput) -> anyhow::Result<bool> {
+        let paused = self
+            .settings
+            .pause
+            .as_ref()
+            .is_some_and(|pause| pause.is_paused());
+        match (paused, self.suspended) {
+            (true, false) => self.restore_terminal(&mut backend.output())?,
+            (false, true) => {
+                self.enter_terminal(&mut backend.output())?;
+                self.redraw = true;
+            }
+            _ => {}
+        }
+        self.suspended = paused;
+
+        if paused {
+            std::thread::sleep(TICK_RATE);
+        }
+        Ok(paused)
+    }
+
+    /// Set up the terminal for the shell (unless the caller manages the terminal), then run the
+    /// setup hook. If the hook fails, the terminal is restored before the error is returned.
+    fn setup_terminal(&mut self, stdout: &mut impl io::Write) -> anyhow::Result<()> {
+        self.enter_terminal(stdout)?;
+
+        if let Some(hook) = self.settings.setup_hook.as_mut() {
+            if let Err(e) = hook(stdout) {
+                self.restore_terminal(stdout)?;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the teardown hook, then restore the terminal (unless the caller manages the
+    /// terminal). The terminal is restored even if the hook fails.
+    fn teardown_terminal(&mut self, stdout: &mut impl io::Write) -> anyhow::Result<()> {
+        let hook = match self.settings.teardown_hook.as_mut() {
+            Some(hook) => hook(stdout),
+            None => Ok(()),
+        };
+        self.restore_terminal(stdout)?;
+        hook
+    }
+
+    /// Enable the raw mode, the alternate screen and bracketed paste, unless the caller manages
+    /// the terminal.
+    fn enter_terminal(&mut self, stdout: &mut impl io::Write) -> anyhow::Result<()> {
+        if self.settings.manage_terminal {
+            crossterm::terminal::enable_raw_mode()?;
+            crossterm::execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+        }
+        Ok(())
+    }
+
+    /// Restore the terminal, unless the caller manages the terminal.
+    fn restore_terminal(&mut self, stdout: &mut impl io::Write) -> anyhow::Result<()> {
+        if self.settings.manage_terminal {
+            crossterm::terminal::disable_raw_mode()?;
+            crossterm::execute!(stdout, DisableBracketedPaste, LeaveAlternateScreen)?;
+        }
+        Ok(())
+    }
+
+    /// Record the event, if recording is enabled.
+    fn record(&mut self, event: &crossterm::event::Event) -> anyhow::Result<()> {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(event)?;
+        }
+        Ok(())
+    }
+
+    /// The groups of the outputs, in the order of their first output. (see [`App::with_groups`])
+    fn groups(&self) -> Vec<&str> {
+        let mut groups = Vec::new();
+        for group in self
+            .history
+            .iter()
+            .filter_map(|output| output.group.as_deref())
+        {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// The group whose outputs are rendered, i.e. the one switched to, or the group of the latest
+    /// output. This is `None` if the groups aren't enabled, or there are none yet.
+    fn active_group(&self) -> Option<&str> {
+        if !self.settings.groups {
+            return None;
+        }
+        self.active_group.as_deref().or_else(|| {
+            self.history
+                .iter()
+                .rev()
+                .find_map(|output| output.group.as_deref())
+        })
+    }
+
+    /// Switch to the next group (after the active one), wrapping around.
+    fn next_group(&mut self) {
+        let groups = self.groups();
+        let active = self.active_group();
+        let next = match active.and_then(|active| groups.iter().position(|g| *g == active)) {
+            Some(index) => groups.get((index + 1) % groups.len()),
+            None => groups.first(),
+        };
+        if let Some(next) = next.map(|next| next.to_string()) {
+            self.active_group = Some(next);
+            self.scroll_offset = 0;
+        }
+    }
+
+    /// The line listing the groups, with the active one highlighted, if the groups are enabled
+    /// and there are any.
+    fn group_tabs(&self) -> Option<Line<'static>> {
+        let active = self.active_group()?;
+        let tabs = self
+            .groups()
+            .into_iter()
+            .map(|group| match group == active {
+                true => Span::styled(format!(" {} ", group), selected_style(self.settings.colors)),
+                false => Span::styled(format!(" {} ", group), Style::default().dim()),
+            });
+        Some(Line::from(tabs.collect::<Vec<_>>()))
+    }
+
+    /// The number of rows that `PageUp` and `PageDown` scroll by, keeping a row of context.
+    fn page_size(&self) -> usize {
+        usize::from(self.viewport_height.saturating_sub(1)).max(1)
+    }
+
+    /// Recall the previous command from the history into the buffer. With the prefix search, this
+    /// is the previous command (before the one recalled last) that starts with the typed prefix.
+    fn recall_previous(&mut self) {
+        let State::Idle(ref mut cmd, ref mut cursor, ref mut comp) = self.state else {
+            return;
+        };
+        let recall = self.recall.take();
+        if recall.is_none()
+            && !cmd.is_empty()
+            && self.settings.history_recall_mode == HistoryRecallMode::WhenEmpty
+        {
+            return;
+        }
+        let stash = match recall {
+            Some(ref recall) => recall.stash.clone(),
+            None if self.settings.history_recall_mode == HistoryRecallMode::Stash => {
+                Some(cmd.clone())
+            }
+            None => None,
+        };
+        // browsing continues from the entry that was recalled last, with the same prefix.
+        let (before, prefix) = match recall {
+            Some(recall) => (recall.index, recall.prefix),
+            None if self.settings.prefix_history_search => (self.history.len(), cmd.clone()),
+            None => (self.history.len(), String::new()),
+        };
+
+        let before = before.min(self.history.len());
+        let index = self.history[..before]
+            .iter()
+            .rposition(|output| !output.command.is_empty() && output.command.starts_with(&prefix));
+        if let Some(index) = index {
+            *cmd = self.history[index].command.clone();
+            *cursor = cmd.len();
+            *comp = None;
+        }
+        // without an (older) match, the buffer is kept, along with the position in the history.
+        self.recall = Some(Recall {
+            index: index.unwrap_or(before),
+            prefix,
+            stash,
+        });
+    }
+
+    /// Navigate back down from a recalled command: recall the next (more recent) matching command,
+    /// or, past the most recent one, restore the stashed text (see [`HistoryRecallMode::Stash`]).
+    fn recall_next(&mut self) {
+        let State::Idle(ref mut cmd, ref mut cursor, ref mut comp) = self.state else {
+            return;
+        };
+        let Some(recall) = self.recall.take() else {
+            return;
+        };
+
+        let after = (recall.index + 1).min(self.history.len());
+        let index = self.history[after..]
+            .iter()
+            .position(|output| {
+                !output.command.is_empty() && output.command.starts_with(&recall.prefix)
+            })
+            .map(|index| index + after);
+        match index {
+            Some(index) => {
+                *cmd = self.history[index].command.clone();
+                self.recall = Some(Recall { index, ..recall });
+            }
+            None => *cmd = recall.stash.unwrap_or_default(),
+        }
+        *cursor = cmd.len();
+        *comp = None;
+    }
+
+    /// Insert the typed (or pasted) text at the cursor, after applying the control character
+    /// policy.
+    fn insert_text(&mut self, text: &str) {
+        let text = self.settings.control_char_policy.apply(text);
+        match self.state {
+            State::Idle(ref mut cmd, ref mut cursor, ref mut comp) => {
+                let available = self
+                    .settings
+                    .max_input_length
+                    .map_or(usize::MAX, |max| max.saturating_sub(cmd.chars().count()));
+                let text = match text.char_indices().nth(available) {
+                    Some((cut, _)) => {
+                        self.settings
+                            .input_limit_feedback
+                            .apply(&mut self.notice, &mut self.bell);
+                        &text[..cut]
+                    }
+                    None => &text[..],
+                };
+                cmd.insert_str(*cursor, text);
+                *cursor += text.len();
+
+                if let Some(comp) = comp.as_mut() {
+                    comp.refine(text, self.settings.case_insensitive_completion);
+                }
+            }
+            State::Running(ref mut _pre, ref mut stdin, _) => match stdin.last_mut() {
+                Some(line) => line.push_str(&text),
+                None => stdin.push(text),
+            },
+        }
+    }
+
+    /// The number of spaces that `Tab` inserts instead of completing, if any. (see [`TabMode`]
+    /// and [`MidLineTab`])
+    fn tab_indent(&self) -> Option<usize> {
+        let (before_cursor, mid_line) = match self.state {
+            // the completions that are shown are cycled through, regardless.
+            State::Idle(_, _, Some(_)) => return None,
+            State::Idle(ref cmd, cursor, None) => (&cmd[..cursor], cursor != cmd.len()),
+            State::Running(_, ref stdin, _) => {
+                (stdin.last().map(String::as_str).unwrap_or_default(), false)
+            }
+        };
+        let indent = match self.settings.tab_mode {
+            TabMode::Complete => None,
+            TabMode::InsertTab(indent) => Some(indent),
+            TabMode::Smart(indent) => {
+                let word = before_cursor.split_whitespace().next_back();
+                let completable = word.is_some_and(|word| before_cursor.ends_with(word));
+                (!completable).then_some(indent)
+            }
+        };
+        match (mid_line, self.settings.mid_line_tab) {
+            (true, MidLineTab::InsertSpaces(count)) => indent.or(Some(count)),
+            _ => indent,
+        }
+    }
+
+    /// Paste the text, keeping its newlines, i.e. starting a new stdin line at every newline
+    /// while the stdin is collected, or a new row of the command. (see
+    /// [`App::with_multiline_paste`])
+    fn paste_lines(&mut self, text: &str) {
+        for (index, line) in text.split('\n').enumerate() {
+            match self.state {
+                State::Running(_, ref mut stdin, _) if index > 0 => stdin.push(String::new()),
+                State::Idle(ref mut cmd, ref mut cursor, ref mut comp) if index > 0 => {
+                    cmd.insert(*cursor, '\n');
+                    *cursor += 1;
+                    *comp = None;
+                }
+                _ => {}
+            }
+            self.insert_text(line.strip_suffix('\r').unwrap_or(line));
+        }
+    }
+
+    /// Move the cursor to the position in the command, e.g. its start or its end, dismissing the
+    /// completions.
+    fn move_cursor_to(&mut self, position: impl Fn(&str) -> usize) {
+        if let State::Idle(ref cmd, ref mut cursor, ref mut comp) = self.state {
+            *cursor = position(cmd);
+            *comp = None;
+        }
+    }
+
+    /// Move the cursor to the left by one character.
     fn move_cursor_left(&mut self) {
         match self.state {
             State::Idle(_, 0, _) | State::Running(..) => {}
-            State::Idle(_, ref mut cursor, ref mut comp) => {
-                *cursor -= 1;
+            State::Idle(ref cmd, ref mut cursor, ref mut comp) => {
+                *cursor = previous_boundary(cmd, *cursor);
+                *comp = None;
+            }
+        }
+    }
+
+    /// Move the cursor to the right by one character.
+    fn move_cursor_right(&mut self) {
+        match self.state {
+            State::Idle(ref cmd, cursor, _) if cursor == cmd.len() => {}
+            State::Idle(ref cmd, ref mut cursor, ref mut comp) => {
+                *cursor += cmd[*cursor..].chars().next().map_or(0, char::len_utf8);
+                *comp = None;
+            }
+            State::Running(..) => {}
+        }
+    }
+
+    /// Move the cursor back by one.
+    fn cursor_backspace(&mut self) {
+        match self.state {
+            State::Idle(ref mut _cmd, 0, _) => {}
+            State::Idle(ref mut cmd, ref mut cursor, ref mut comp) => {
+                *cursor = previous_boundary(cmd, *cursor);
+                cmd.remove(*cursor);
                 *comp = None;
             }
+            State::Running(ref mut _pre, ref mut stdin, _) => {
+                stdin.last_mut().map(|i| i.pop());
+                if stdin.last().map_or(true, |i| i.is_empty()) {
+                    stdin.pop();
+                }
+            }
+        }
+    }
+
+    /// Push the output to the history (and tee it, if enabled). The output is typed out, if the
+    /// typewriter is enabled.
+    fn push_history(&mut self, output: command::CommandOutput) {
+        self.tee(&output);
+        self.append_history(output);
+    }
+
+    /// Append the output to the history, without teeing it.
+    fn append_history(&mut self, mut output: command::CommandOutput) {
+        auto_collapse(&mut output, &self.settings);
+        // the active group follows the new output.
+        if output.group.is_some() {
+            self.active_group = None;
+        }
+        if self.settings.typewriter.is_some() {
+            self.typewriter = Some(Typewriter {
+                index: self.history.len(),
+                started: Instant::now(),
+                revealed: 0,
+            });
+        }
+        self.history.push(output);
+        self.cap_history();
+    }
+
+    /// Tee the output to the sink, if enabled.
+    fn tee(&mut self, output: &command::CommandOutput) {
+        let Some(sink) = self.settings.output_tee.as_mut() else {
+            return;
+        };
+
+        let lines = output.lines();
+        let lines = lines.iter().map(|line| match line {
+            command::OutputLine::Stdout(line) | command::OutputLine::Stderr(line) => line,
+        });
+        let written = writeln!(
+            sink,
+            "{}{}{}",
+            output.prompt, PROMPT_SEPARATOR, output.command
+        )
+        .and_then(|_| {
+            output
+                .stdin
+                .iter()
+                .chain(lines)
+                .try_for_each(|line| writeln!(sink, "{}", line))
+        })
+        .and_then(|_| sink.flush());
+
+        if let Err(e) = written {
+            tracing::warn!("failed to tee the output: {}", e);
+        }
+    }
+
+    /// Continue the execution of the command.
+    fn continue_execution(&mut self) -> anyhow::Result<Next> {
+        let (prepare, stdin, raw) = match self.state {
+            State::Running(ref prep, ref stdin, ref raw) => {
+                (prep.clone(), stdin.clone(), raw.clone())
+            }
+            State::Idle(..) => return Ok(Next::Continue),
+        };
+
+        let recorded = self.recorded_command(&raw, &prepare);
+        let stdin = Some((stdin, prepare.mask_stdin));
+        self._final_execution(&prepare.command, stdin, recorded)
+            .map(|(next, _)| next)
+    }
+
+    /// Execute the command.
+    fn execute_command(&mut self) -> anyhow::Result<Next> {
+        let (cmd, cursor) = match self.state {
+            State::Idle(ref cmd, cursor, _) => (cmd.clone(), cursor),
+            State::Running(..) => return Ok(Next::Continue),
+        };
+        self.submitted = Some((cmd.clone(), cursor));
+
+        // with a separator, the segments after the first one are queued to be executed next.
+        let cmd = match self.settings.command_separator {
+            Some(separator) => {
+                let mut segments = split_commands(&cmd, separator);
+                let rest = segments.split_off(segments.len().min(1));
+                let first = segments.pop().unwrap_or_default();
+                // a queued segment is restored along with the segments of its line after it.
+                if rest.is_empty() && self.segments > 0 {
+                    let line = std::iter::once(&first).chain(self.queue.range(..self.segments));
+                    let line = line.cloned().collect::<Vec<_>>();
+                    self.submitted = Some((line.join(&format!("{} ", separator)), first.len()));
+                }
+                self.segments += rest.len();
+                for segment in rest.into_iter().rev() {
+                    self.queue.push_front(segment);
+                }
+                first
+            }
+            None => cmd,
+        };
+
+        let cmd = match self.settings.bang_expansion {
+            true => match expand_bangs(&cmd, &self.history) {
+                Ok(expanded) => expanded,
+                Err(event) => {
+                    let output = command::CommandOutput {
+                        prompt: full_prompt(&self.executor, &self.context),
+                        command: cmd,
+                        stderr: vec![format!("{}: event not found", event)],
+                        ..Default::default()
+                    };
+                    self.push_history(output);
+                    self.state = State::Idle(String::new(), 0, None);
+                    return Ok(Next::Continue);
+                }
+            },
+            false => cmd,
+        };
+
+        if self.settings.history_builtin.as_deref() == Some(cmd.trim()) {
+            let stdout = self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(index, output)| format!("{:>5}  {}", index + 1, output.command))
+                .collect();
+            let output = command::CommandOutput {
+                prompt: full_prompt(&self.executor, &self.context),
+                command: cmd,
+                stdin: Vec::new(),
+                stdout,
+                stderr: Vec::new(),
+                ..Default::default()
+            };
+            self.push_history(output);
+            self.state = State::Idle(String::new(), 0, None);
+            return Ok(Next::Continue);
+        }
+
+        let prepare = self.executor.prepare(&cmd);
+        self.state = State::Running(prepare.clone(), Vec::new(), cmd.clone());
+
+        match prepare.stdin_required {
+            true => Ok(Next::Continue),
+            false => {
+                let recorded = self.recorded_command(&cmd, &prepare);
+                self._final_execution(&cmd, None, recorded)
+                    .map(|(next, _)| next)
+            }
+        }
+    }
+
+    /// The command that is recorded in the history, if it's configured to be either the command
+    /// as typed (`raw`) or as prepared. (see [`App::with_history_command_source`])
+    fn recorded_command(&self, raw: &str, prepare: &command::Prepare) -> Option<String> {
+        self.settings
+            .history_command_source
+            .map(|source| match source {
+                HistoryCommandSource::Raw => raw.to_string(),
+                HistoryCommandSource::Transformed => prepare.command.clone(),
+            })
+    }
+
+    /// Execute the command and return the next action, along with (a copy of) the output of the
+    /// executor. The command in the output is replaced by `recorded`, if any, and the stdin is
+    /// masked in the history, if it's collected with a mask (see
+    /// [`command::Prepare::mask_stdin`]).
+    fn _final_execution(
+        &mut self,
+        cmd: &str,
+        stdin: Option<(Vec<String>, Option<char>)>,
+        recorded: Option<String>,
+    ) -> anyhow::Result<(Next, command::OutputAction)> {
+        let prompt = full_prompt(&self.executor, &self.context);
+        let mask = stdin.as_ref().and_then(|(_, mask)| *mask);
+        let stdin = stdin.map(|(stdin, _)| self.executor.preprocess_stdin(&self.context, stdin));
+        let submitted = self.submitted.take();
+        // kept for rendering the command, in case the output is pending (or in columns).
+        let pending_prompt = prompt.clone();
+        let pending_stdin = match mask {
+            Some(mask) => stdin
+                .as_ref()
+                .map(|stdin| stdin.iter().map(|line| masked(line, mask)).collect()),
+            None => stdin.clone(),
+        };
+        let started = Instant::now();
+        let timestamp = SystemTime::now();
+        let writer = command::OutputWriter::default();
+        let events = Arc::new(Mutex::new(SharedEvents {
+            replay: std::mem::take(&mut self.replay),
+            recorder: self.recorder.take(),
+            deferred: Vec::new(),
+        }));
+        let output = self.executor.execute_streaming(
+            &mut self.context,
+            command::CommandInput {
+                prompt,
+                command: cmd.to_string(),
+                stdin,
+                #[cfg(feature = "tokio")]
+                runtime: self.runtime.clone(),
+                #[cfg(feature = "tokio")]
+                handle: self.handle.clone(),
+                cursor_position: self.cursor_position,
+                keys: SharedEvents::key_reader(&events),
+            },
+            writer.clone(),
+        );
+        // the events are taken back, with the deferred events handled first.
+        let events = std::mem::take(&mut *events.lock().unwrap_or_else(|e| e.into_inner()));
+        self.replay = events.deferred.into_iter().chain(events.replay).collect();
+        self.recorder = events.recorder;
+        // the shell is back to a clean idle state (following the output, if enabled), even if the
+        // command failed.
+        self.state = State::Idle(String::new(), 0, None);
+        if self.settings.follow_output {
+            self.scroll_offset = 0;
+        }
+        *self.frequency.entry(command_name(cmd)).or_default() += 1;
+
+        let output = match output {
+            // the lines that are streamed while executing come before the output.
+            Ok(command::OutputAction::Command(mut output)) => {
+                // nothing is taken from the writer before, so there's nothing to replace.
+                prepend_lines(&mut output, writer.take().lines);
+                command::OutputAction::Command(output)
+            }
+            Ok(output) => output,
+            Err(e) if self.settings.error_recovery => {
+                if let (true, Some((buffer, cursor))) = (self.settings.restore_on_error, submitted)
+                {
+                    // the rest of the line is restored, rather than executed.
+                    let segments = std::mem::take(&mut self.segments).min(self.queue.len());
+                    self.queue.drain(..segments);
+                    self.state = State::Idle(buffer, cursor, None);
+                }
+                let mut stderr = e.to_string().lines().map(String::from).collect::<Vec<_>>();
+                // the traceback is the chain of the causes.
+                if self.settings.collapsible_tracebacks {
+                    stderr.extend(
+                        e.chain()
+                            .skip(1)
+                            .map(|cause| format!("caused by: {}", cause)),
+                    );
+                }
+                command::OutputAction::Command(command::CommandOutput {
+                    prompt: pending_prompt.clone(),
+                    command: cmd.to_string(),
+                    stdin: pending_stdin.clone().unwrap_or_default(),
+                    collapsed: self.settings.collapsible_tracebacks && stderr.len() > 1,
+                    stderr,
+                    ..Default::default()
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        // the unknown command is presented by the executor, or with the uniform error.
+        let output = match output {
+            command::OutputAction::NotFound => match self.executor.not_found(&self.context, cmd) {
+                Some(command::OutputAction::NotFound) | None => {
+                    command::OutputAction::Command(command::CommandOutput {
+                        prompt: pending_prompt.clone(),
+                        command: cmd.to_string(),
+                        stdin: pending_stdin.clone().unwrap_or_default(),
+                        stderr: vec![format!("{}: command not found", command_name(cmd))],
+                        ..Default::default()
+                    })
+                }
+                Some(output) => output,
+            },
+            output => output,
+        };
+
+        let action = output.clone();
+        let next = match output {
+            command::OutputAction::Command(mut command_output) => {
+                command_output.duration = Some(started.elapsed());
+                command_output.timestamp = Some(timestamp);
+                if let Some(recorded) = recorded {
+                    command_output.command = recorded;
+                }
+                if let Some(mask) = mask {
+                    for line in command_output.stdin.iter_mut() {
+                        *line = masked(line, mask);
+                    }
+                }
+                self.push_history(command_output);
+                Next::Continue
+            }
+            command::OutputAction::Columns(items) => {
+                self.push_history(command::CommandOutput {
+                    prompt: pending_prompt,
+                    command: recorded.unwrap_or_else(|| cmd.to_string()),
+                    stdin: pending_stdin.unwrap_or_default(),
+                    stdout: items,
+                    stderr: Vec::new(),
+                    duration: Some(started.elapsed()),
+                    timestamp: Some(timestamp),
+                    layout: command::OutputLayout::Columns,
+                    output: Vec::new(),
+                    modified: false,
+                    collapsed: false,
+                    group: None,
+                });
+                Next::Continue
+            }
+            command::OutputAction::Exit => match self.executor.should_exit(&self.context) {
+                true => Next::Exit("".to_string()),
+                false => Next::Continue,
+            },
+            command::OutputAction::Clear => match self.executor.should_clear(&self.context) {
+                true => {
+                    self.clear_history();
+                    Next::Clear
+                }
+                false => Next::Continue,
+            },
+            command::OutputAction::Redraw => Next::Redraw,
+            // mapped to the output for the unknown command, above.
+            command::OutputAction::NotFound => Next::Continue,
+            command::OutputAction::Image(png) => {
+                let output = command::CommandOutput {
+                    prompt: pending_prompt,
+                    command: recorded.unwrap_or_else(|| cmd.to_string()),
+                    stdin: pending_stdin.unwrap_or_default(),
+                    stdout: vec![graphics::placeholder(&png)],
+                    stderr: Vec::new(),
+                    duration: Some(started.elapsed()),
+                    timestamp: Some(timestamp),
+                    layout: command::OutputLayout::Lines,
+                    output: Vec::new(),
+                    modified: false,
+                    collapsed: false,
+                    group: None,
+                };
+                // the transcript displays the image, in place of the placeholder.
+                match graphics::escape(self.settings.graphics_protocol, &png) {
+                    Some(escape) => self.tee(&command::CommandOutput {
+                        prompt: output.prompt.clone(),
+                        command: output.command.clone(),
+                        stdin: output.stdin.clone(),
+                        stdout: vec![escape],
+                        ..Default::default()
+                    }),
+                    None => self.tee(&output),
+                }
+                self.append_history(output);
+                Next::Continue
+            }
+            command::OutputAction::Pending(pending) => {
+                self.pending.push((self.history.len(), pending, writer));
+                self.history.push(command::CommandOutput {
+                    prompt: pending_prompt,
+                    command: recorded.unwrap_or_else(|| cmd.to_string()),
+                    stdin: pending_stdin.unwrap_or_default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                    timestamp: Some(timestamp),
+                    ..Default::default()
+                });
+                Next::Continue
+            }
+        };
+
+        Ok((next, action))
+    }
+}
+
+/// Whether the blinking cursor is visible, `elapsed` after the blinking started. The cursor is
+/// visible during the first interval, and toggles at the end of every interval.
+fn cursor_visible(elapsed: Duration, interval: Option<Duration>) -> bool {
+    match interval {
+        Some(interval) if !interval.is_zero() => {
+            (elapsed.as_nanos() / interval.as_nanos()) % 2 == 0
+        }
+        _ => true,
+    }
+}
+
+/// The number of characters that are revealed by the typewriter, `elapsed` after it started.
+fn revealed_chars(elapsed: Duration, interval: Duration) -> usize {
+    match interval.is_zero() {
+        true => usize::MAX,
+        false => usize::try_from(elapsed.as_nanos() / interval.as_nanos()).unwrap_or(usize::MAX),
+    }
+}
+
+/// Append the lines to the output, keeping the order of the stdout and stderr lines. (see
+/// [`command::CommandOutput::output`])
+fn append_lines(output: &mut command::CommandOutput, lines: Vec<command::OutputLine>) {
+    if lines.is_empty() {
+        return;
+    }
+    if output.output.is_empty() {
+        output.output = output.lines();
+    }
+    for line in lines {
+        match line {
+            command::OutputLine::Stdout(ref line) => output.stdout.push(line.clone()),
+            command::OutputLine::Stderr(ref line) => output.stderr.push(line.clone()),
+        }
+        output.output.push(line);
+    }
+}
+
+/// Append the lines written to an [`command::OutputWriter`] to the output, after removing the
+/// (earlier written) lines that they replace.
+fn write_lines(output: &mut command::CommandOutput, written: command::Written) {
+    if written.replaced > 0 && output.output.is_empty() {
+        output.output = output.lines();
+    }
+    for _ in 0..written.replaced {
+        match output.output.pop() {
+            Some(command::OutputLine::Stdout(_)) => output.stdout.pop(),
+            Some(command::OutputLine::Stderr(_)) => output.stderr.pop(),
+            None => None,
+        };
+    }
+    append_lines(output, written.lines);
+}
+
+/// Prepend the lines to the output. (see [`append_lines`])
+fn prepend_lines(output: &mut command::CommandOutput, lines: Vec<command::OutputLine>) {
+    if lines.is_empty() {
+        return;
+    }
+    let rest = output.lines();
+    output.stdout.clear();
+    output.stderr.clear();
+    output.output.clear();
+    append_lines(output, lines);
+    append_lines(output, rest);
+}
+
+/// The number of characters in the stdout, counting the end of each line as a character.
+fn stdout_chars(stdout: &[String]) -> usize {
+    stdout.iter().map(|line| line.chars().count() + 1).sum()
+}
+
+/// The output, with only the first `revealed` characters of the stdout (see [`stdout_chars`]),
+/// and without the stderr.
+fn reveal(output: &command::CommandOutput, revealed: usize) -> command::CommandOutput {
+    let mut remaining = revealed;
+    let mut stdout = Vec::new();
+    for line in &output.stdout {
+        if remaining == 0 {
+            break;
+        }
+        stdout.push(line.chars().take(remaining).collect());
+        remaining = remaining.saturating_sub(line.chars().count() + 1);
+    }
+
+    command::CommandOutput {
+        prompt: output.prompt.clone(),
+        command: output.command.clone(),
+        stdin: output.stdin.clone(),
+        stdout,
+        stderr: Vec::new(),
+        duration: output.duration,
+        timestamp: output.timestamp,
+        layout: output.layout,
+        output: Vec::new(),
+        modified: output.modified,
+        collapsed: output.collapsed,
+        group: output.group.clone(),
+    }
+}
+
+/// Ring the terminal bell. Failing to ring the bell is not an error.
+fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = io::Write::write_all(&mut stdout, b"\x07").and_then(|_| io::Write::flush(&mut stdout));
+}
+
+/// Expand `!!` to the last command in the `history`, and `!N` to the command at the (1-based)
+/// index `N`. The other `!`s are kept as is. When a command is not found, the event (e.g. `!42`)
+/// is returned as the error.
+fn expand_bangs(cmd: &str, history: &[command::CommandOutput]) -> Result<String, String> {
+    let mut expanded = String::with_capacity(cmd.len());
+    let mut rest = cmd;
+    while let Some(index) = rest.find('!') {
+        expanded.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let (event, found) = match after.starts_with('!') {
+            true => ("!!", history.last()),
+            false if digits > 0 => {
+                let found = after[..digits]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|n| history.get(n));
+                (&rest[index..index + 1 + digits], found)
+            }
+            false => ("!", None),
+        };
+        match (event, found) {
+            ("!", _) => expanded.push('!'),
+            (_, Some(output)) => expanded.push_str(&output.command),
+            (event, None) => return Err(event.to_string()),
+        }
+        rest = &rest[index + event.len()..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Split the line into the commands separated by `separator`, ignoring the separators that are
+/// quoted or escaped. The commands are trimmed, and empty commands are skipped.
+fn split_commands(line: &str, separator: char) -> Vec<String> {
+    let mut commands = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+
+    for c in line.chars() {
+        match (c, quote) {
+            _ if escaped => escaped = false,
+            ('\\', _) => escaped = true,
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c == separator => {
+                commands.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    commands.push(current);
+
+    commands
+        .into_iter()
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// The name of the command, i.e. the first word.
+fn command_name(cmd: &str) -> String {
+    cmd.split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Rank the completion candidates (completing `cmd`) by how frequently the commands are executed.
+///
+/// The sort is stable, so the order of the executor is kept for equally frequent commands.
+fn rank_by_frequency(frequency: &HashMap<String, usize>, cmd: &str, candidates: &mut [String]) {
+    candidates.sort_by_cached_key(|candidate| {
+        let count = frequency
+            .get(&command_name(&format!("{}{}", cmd, candidate)))
+            .copied()
+            .unwrap_or_default();
+        std::cmp::Reverse(count)
+    });
+}
+
+/// Highlight the occurrences of `term` in the line.
+///
+/// The spans are split around the occurrences, keeping their style. Occurrences that span across
+/// multiple spans are not highlighted. Without `colors`, the occurrences are reversed instead.
+fn highlight<'a>(line: Line<'a>, term: &str, colors: bool) -> Line<'a> {
+    let style = match colors {
+        true => Style::default()
+            .bg(ratatui::style::Color::Yellow)
+            .fg(ratatui::style::Color::Black),
+        false => Style::default().reversed(),
+    };
+
+    let mut spans = Vec::with_capacity(line.spans.len());
+    for span in line.spans.iter() {
+        let mut rest = span.content.as_ref();
+        while let Some(index) = rest.find(term) {
+            let (before, after) = rest.split_at(index);
+            let (matched, after) = after.split_at(term.len());
+            if !before.is_empty() {
+                spans.push(Span::styled(before.to_string(), span.style));
+            }
+            spans.push(Span::styled(matched.to_string(), span.style.patch(style)));
+            rest = after;
+        }
+        if !rest.is_empty() {
+            spans.push(Span::styled(rest.to_string(), span.style));
+        }
+    }
+
+    Line { spans, ..line }
+}
+
+/// The style of the cursor and the selected completion. Without `colors`, these are reversed.
+fn selected_style(colors: bool) -> Style {
+    match colors {
+        true => Style::default()
+            .bg(ratatui::style::Color::White)
+            .fg(ratatui::style::Color::Black),
+        false => Style::default().reversed(),
+    }
+}
+
+/// Render the line with the default style, keeping only the reversed spans (see
+/// [`selected_style`]) so that the cursor, the selected completion and the highlights stay
+/// visible.
+fn strip_colors(line: Line<'static>) -> Line<'static> {
+    let spans = line
+        .spans
+        .into_iter()
+        .map(|span| {
+            let reversed = span.style.add_modifier.contains(Modifier::REVERSED);
+            let style = match reversed {
+                true => Style::default().reversed(),
+                false => Style::default(),
+            };
+            Span::styled(span.content, style)
+        })
+        .collect::<Vec<_>>();
+    Line {
+        spans,
+        style: Style::default(),
+        ..line
+    }
+}
+
+/// The column at which the command starts, i.e. the display width of the spans before it (see
+/// [`command_prefix`]).
+///
+/// This is measured in terminal cells (via `unicode-width`), not in bytes or chars, so that
+/// prompts containing wide glyphs (box-drawing, powerline, CJK, etc.) are accounted for correctly.
+fn command_column(prefix: &[Span<'_>]) -> usize {
+    prefix.iter().map(Span::width).sum()
+}
+
+/// The line with each character replaced by the `mask`. (see [`command::Prepare::mask_stdin`])
+fn masked(line: &str, mask: char) -> String {
+    line.chars().map(|_| mask).collect()
+}
+
+/// The prompt, followed by the segments of the prompt context and the prompt symbol, if any.
+/// (see [`command::Execute::prompt_context`] and [`command::Execute::prompt_symbol`])
+fn full_prompt<T: command::Execute>(executor: &T, ctx: &T::Context) -> String {
+    let mut prompt = executor.prompt(ctx);
+    let segments = executor
+        .prompt_context(ctx)
+        .into_iter()
+        .map(|(text, _)| text);
+    let symbol = executor.prompt_symbol(ctx).map(String::from);
+    for part in segments.chain(symbol).filter(|part| !part.is_empty()) {
+        prompt.push(' ');
+        prompt.push_str(&part);
+    }
+    prompt
+}
+
+/// The line of the prompt, if it's rendered on its own line (see
+/// [`App::with_prompt_on_own_line`]), and the spans rendered before the command on its line.
+fn command_prefix(
+    prompt: &str,
+    settings: &Settings,
+) -> (Option<Line<'static>>, Vec<Span<'static>>) {
+    match settings.prompt_on_own_line {
+        true => {
+            let prompt = Span::styled(prompt.to_string(), Style::default().blue());
+            (Some(Line::from(prompt)), Vec::new())
+        }
+        false => (None, prompt_spans(prompt)),
+    }
+}
+
+/// The spans rendered before the command: the prompt followed by the separator.
+fn prompt_spans(prompt: &str) -> Vec<Span<'static>> {
+    vec![
+        Span::styled(prompt.to_string(), Style::default().blue()),
+        Span::raw(PROMPT_SEPARATOR),
+    ]
+}
+
+/// Truncate the prompt to the maximum width (see [`App::with_prompt_max_width`]), replacing the
+/// end with an ellipsis. The width is measured in terminal cells.
+fn truncate_prompt(prompt: &str, settings: &Settings) -> String {
+    let max = match settings.prompt_max_width {
+        Some(max) if prompt.width() > usize::from(max) => usize::from(max),
+        _ => return prompt.to_string(),
+    };
+    let Some(available) = max.checked_sub(1) else {
+        return String::new();
+    };
+
+    let mut width = 0;
+    let mut truncated = prompt
+        .chars()
+        .take_while(|c| {
+            width += c.width().unwrap_or(0);
+            width <= available
+        })
+        .collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Split the line into rows at the newlines (see [`App::with_multiline_paste`]), keeping the
+/// styles. The rows after the first one start with the `gutter` (if any), and are indented by
+/// `indent` columns in total.
+fn split_rows(line: Line<'static>, indent: usize, gutter: Option<char>) -> Vec<Line<'static>> {
+    let mut continuation = gutter_spans(gutter);
+    let padding = indent.saturating_sub(continuation.iter().map(Span::width).sum());
+    continuation.push(Span::raw(" ".repeat(padding)));
+
+    let mut rows = vec![Line::default().style(line.style)];
+    for span in line.spans {
+        for (index, text) in span.content.split('\n').enumerate() {
+            if index > 0 {
+                rows.push(Line::from(continuation.clone()).style(line.style));
+            }
+            if let Some(row) = rows.last_mut() {
+                row.spans.push(Span::styled(text.to_string(), span.style));
+            }
+        }
+    }
+    rows
+}
+
+/// Wrap the line into rows of at most `width` cells, keeping the styles. Unlike the wrapping of
+/// the [`Paragraph`], the line is broken at any character rather than at the words, so that the
+/// cursor can be located in the rows. A wide character that doesn't fit starts the next row.
+fn wrap_cells(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = vec![Line::default().style(line.style)];
+    let mut filled = 0;
+    for span in line.spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let cells = c.width().unwrap_or_default();
+            if filled > 0 && filled + cells > width {
+                if let Some(row) = rows.last_mut() {
+                    row.spans
+                        .push(Span::styled(std::mem::take(&mut text), span.style));
+                }
+                rows.push(Line::default().style(line.style));
+                filled = 0;
+            }
+            text.push(c);
+            filled += cells;
+        }
+        if let Some(row) = rows.last_mut() {
+            row.spans.push(Span::styled(text, span.style));
+        }
+    }
+    rows
+}
+
+/// Crop the line to the `width` columns after the first `skip` columns, keeping the styles. The
+/// wide characters that don't fit entirely are dropped.
+fn crop_line(line: Line<'_>, skip: usize, width: usize) -> Line<'static> {
+    let mut column = 0;
+    let spans = line
+        .spans
+        .iter()
+        .map(|span| {
+            let content = span
+                .content
+                .chars()
+                .filter(|c| {
+                    let start = column;
+                    column += c.width().unwrap_or_default();
+                    start >= skip && column <= skip + width
+                })
+                .collect::<String>();
+            Span::styled(content, span.style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans).style(line.style)
+}
+
+/// The gap between the columns. (see [`columns`])
+const COLUMN_GAP: usize = 2;
+
+/// Lay out the items in columns (like `ls`) that fit the `width`, filling the columns top to
+/// bottom, then left to right. This returns the rendered rows.
+fn columns(items: &[String], width: usize) -> Vec<String> {
+    let item_width = items
+        .iter()
+        .map(|item| item.width())
+        .max()
+        .unwrap_or_default();
+    let count = ((width + COLUMN_GAP) / (item_width + COLUMN_GAP)).max(1);
+    let rows = items.len().div_ceil(count);
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            let mut cells = items.iter().skip(row).step_by(rows).peekable();
+            while let Some(item) = cells.next() {
+                line.push_str(item);
+                if cells.peek().is_some() {
+                    let padding = item_width + COLUMN_GAP - item.width();
+                    line.extend(std::iter::repeat(' ').take(padding));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Format the duration for humans, e.g. `350ms` or `1.2s`.
+fn format_duration(duration: Duration) -> String {
+    match duration.as_millis() {
+        millis @ 0..=999 => format!("{}ms", millis),
+        _ => format!("{:.1}s", duration.as_secs_f64()),
+    }
+}
+
+/// The spans of the gutter, i.e. the symbol followed by a space, or nothing if there's no gutter.
+fn gutter_spans(gutter: Option<char>) -> Vec<Span<'static>> {
+    gutter
+        .map(|gutter| Span::styled(format!("{} ", gutter), Style::default().dim()))
+        .into_iter()
+        .collect()
+}
+
+/// Complete the command at the cursor, merging the completions of the executor with the ones of
+/// the providers, if any. (see [`App::with_completion_provider`]) The providers complete at the
+/// cursor, so they are left out when the executor replaces another range (see
+/// [`command::Execute::completion_result`]).
+fn complete<T: command::Execute>(
+    executor: &T,
+    providers: &[Box<Provider<T>>],
+    ctx: &T::Context,
+    cmd: &str,
+    cursor: usize,
+) -> anyhow::Result<command::CompletionResult> {
+    let result = executor.completion_result(ctx, cmd, cursor)?;
+    if providers.is_empty() || result.range != (cursor..cursor) {
+        return Ok(result);
+    }
+
+    let mut completions = vec![(result.insert, result.candidates)];
+    for provider in providers {
+        completions.push(provider.complete(ctx, cmd, cursor)?);
+    }
+    let (insert, candidates) = command::merge_completions(completions);
+    Ok(command::CompletionResult {
+        range: cursor..cursor,
+        insert,
+        candidates,
+    })
+}
+
+/// The byte offset of the character before the `cursor` (a byte offset on a character boundary)
+/// in the command, i.e. the cursor moved to the left by one character.
+fn previous_boundary(cmd: &str, cursor: usize) -> usize {
+    cmd[..cursor]
+        .chars()
+        .next_back()
+        .map_or(0, |c| cursor - c.len_utf8())
+}
+
+/// Whether the output is rendered with the active `group`, i.e. it belongs to the group, or to
+/// no group. All the outputs are rendered without an active group. (see [`App::with_groups`])
+fn in_group(output: &command::CommandOutput, group: Option<&str>) -> bool {
+    match (group, output.group.as_deref()) {
+        (Some(group), Some(own)) => group == own,
+        _ => true,
+    }
+}
+
+/// Collapse the output, if it has more lines than the threshold. (see
+/// [`App::with_auto_collapse_threshold`])
+fn auto_collapse(output: &mut command::CommandOutput, settings: &Settings) {
+    if let Some(threshold) = settings.auto_collapse_threshold {
+        output.collapsed |= output.stdout.len() + output.stderr.len() > threshold;
+    }
+}
+
+/// The summary line that a collapsed output is rendered as, e.g. `… 120 lines (^O expand)`. An
+/// error (i.e. only stderr) is summarized by its first line, e.g. a traceback by its error. (see
+/// [`App::with_collapsible_tracebacks`])
+fn collapsed_summary(output: &command::CommandOutput, settings: &Settings) -> Line<'static> {
+    let (first, hidden) = match output.stderr.first() {
+        Some(first) if output.stdout.is_empty() => (Some(first), output.stderr.len() - 1),
+        _ => (None, output.stdout.len() + output.stderr.len()),
+    };
+    let lines = match (first, hidden) {
+        (None, 1) => "1 line".to_string(),
+        (None, lines) => format!("{} lines", lines),
+        (Some(_), 1) => "1 more line".to_string(),
+        (Some(_), lines) => format!("{} more lines", lines),
+    };
+    let summary = match settings.key_map.keys(Action::Expand) {
+        Some(keys) => format!("… {} ({} expand)", lines, keys),
+        None => format!("… {}", lines),
+    };
+    match first {
+        Some(first) => Line::from(vec![
+            Span::styled(first.clone(), Style::default().red()),
+            Span::styled(format!(" {}", summary), Style::default().dim()),
+        ]),
+        None => Line::styled(summary, Style::default().dim()),
+    }
+}
+
+/// The estimated rows of the rendered history entry, i.e. its lines without the wrapping. (see
+/// [`App::with_lazy_rendering`])
+fn estimated_rows(history: &command::CommandOutput, settings: &Settings) -> usize {
+    let prompt_line = usize::from(settings.prompt_on_own_line);
+    let output = match history.collapsed {
+        true => 1,
+        false => history.stdout.len() + history.stderr.len(),
+    };
+    let hint = settings.empty_output_hint && output == 0 && !history.command.trim().is_empty();
+    let output = output + usize::from(hint);
+    let command = 1 + history.command.matches('\n').count();
+    prompt_line + command + history.stdin.len() + output
+}
+
+/// Render the history of the commands.
+///
+/// The stdout and stderr lines are passed through [`command::Execute::transform_output`], in
+/// the order they were produced (see [`command::CommandOutput::lines`]).
+/// The stdin lines (and the continuation rows of the command) are prefixed with the gutter, and
+/// the command is followed by its duration, if enabled in the `settings`. The output in columns
+/// is laid out to fit the `width`, and the output is aligned with the command, if enabled. The
+/// empty output of a `complete` entry (i.e. neither pending nor being revealed) is hinted at, if
+/// enabled.
+fn render_history<T: command::Execute>(
+    executor: &T,
+    history: &command::CommandOutput,
+    settings: &Settings,
+    width: u16,
+    complete: bool,
+) -> Vec<Line<'static>> {
+    let prompt = truncate_prompt(&history.prompt, settings);
+    let marker = match history.modified {
+        true => format!("{} ", settings.modified_marker),
+        false => String::new(),
+    };
+    let marker = Span::styled(marker, Style::default().yellow());
+    let (prompt_line, prefix) = command_prefix(&prompt, settings);
+    // the marker goes before the prompt, wherever it's rendered.
+    let prompt_line = prompt_line.map(|mut line| {
+        line.spans.insert(0, marker.clone());
+        line
+    });
+    let mut command = match prompt_line {
+        Some(_) => prefix,
+        None => [vec![marker.clone()], prefix].concat(),
+    };
+    let column = command_column(&command);
+    command.push(Span::styled(
+        history.command.clone(),
+        Style::default().bold(),
+    ));
+    if let (true, Some(duration)) = (settings.durations, history.duration) {
+        command.push(Span::styled(
+            format!(" ({})", format_duration(duration)),
+            Style::default().dim(),
+        ));
+    }
+    let command = split_rows(Line::from(command), column, settings.gutter);
+    let gutter = gutter_spans(settings.gutter);
+    let stdin = history
+        .stdin
+        .iter()
+        .map(|line| {
+            let mut spans = gutter.clone();
+            spans.push(Span::raw(line.clone()));
+            Line::from(spans)
+        })
+        .collect::<Vec<_>>();
+    let indent = match settings.align_output {
+        true => column,
+        false => 0,
+    };
+    let stderr_line = |line: &str| {
+        executor
+            .transform_output(line)
+            .patch_style(Style::default().red())
+    };
+    let output = match history.layout {
+        _ if history.collapsed => vec![collapsed_summary(history, settings)],
+        _ if settings.empty_output_hint
+            && complete
+            && !history.command.trim().is_empty()
+            && history.stdout.is_empty()
+            && history.stderr.is_empty() =>
+        {
+            vec![Line::styled("(no output)", Style::default().dim())]
+        }
+        command::OutputLayout::Lines => history
+            .lines()
+            .iter()
+            .map(|line| match line {
+                command::OutputLine::Stdout(line) => executor.transform_output(line),
+                command::OutputLine::Stderr(line) => stderr_line(line),
+            })
+            .collect::<Vec<_>>(),
+        command::OutputLayout::Columns => {
+            columns(&history.stdout, usize::from(width).saturating_sub(indent))
+                .iter()
+                .map(|i| executor.transform_output(i))
+                .chain(history.stderr.iter().map(|i| stderr_line(i)))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let mut lines = prompt_line.into_iter().collect::<Vec<_>>();
+    lines.extend(command);
+    lines.extend(stdin);
+    lines.extend(output.into_iter().map(|mut line| {
+        if indent > 0 {
+            line.spans.insert(0, Span::raw(" ".repeat(indent)));
+        }
+        line
+    }));
+
+    lines
+}
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    //! The tests drive the [`App`] headlessly: the events are fed to [`App::input`] (or replayed
+    //! through [`App::step`]), and the frames are rendered to a [`TestBackend`].
+
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use crossterm::event::{Event, KeyEvent};
+    use ratatui::backend::TestBackend;
+    use ratatui::buffer::Buffer;
+
+    use super::*;
+    use crate::command::{
+        CommandInput, CommandOutput, Execute, OutputAction, PendingOutput, PendingResolver, Prepare,
+    };
+
+    /// An executor that echoes the commands, keeping them in the context. `cat` requires the
+    /// stdin, which it echoes instead.
+    struct Echo;
+
+    impl Execute for Echo {
+        type Context = Vec<String>;
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "$".to_string()
+        }
+
+        fn commands(&self, _: &Self::Context) -> Vec<String> {
+            vec!["git".to_string(), "grep".to_string(), "ls".to_string()]
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                stdin_required: cmd == "cat",
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            ctx: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            ctx.push(input.command.clone());
+            let stdin = input.stdin.unwrap_or_default();
+            let stdout = match input.command.as_str() {
+                "cat" => stdin.clone(),
+                command => vec![command.to_string()],
+            };
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdin,
+                stdout,
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The headless terminal discards its output.
+    impl TerminalOutput for TestBackend {
+        fn output(&mut self) -> &mut dyn io::Write {
+            // the sink is zero-sized, so nothing is allocated (or leaked).
+            Box::leak(Box::new(io::sink()))
+        }
+    }
+
+    /// A writer into the buffer that is shared with the test.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().expect("poisoned").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The app with the executor and the context, on a fresh runtime (if enabled).
+    fn app<E: Execute>(executor: E, context: E::Context) -> App<E> {
+        App::new_with_executor(
+            #[cfg(feature = "tokio")]
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .expect("failed to build the runtime"),
+            executor,
+            context,
+        )
+    }
+
+    /// The press of the key, without modifiers.
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    /// The press of `Ctrl` with the character.
+    fn ctrl(c: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    /// Type the text, key by key, pressing `Enter` for the newlines.
+    fn type_text<E: Execute>(app: &mut App<E>, text: &str) -> anyhow::Result<()> {
+        for c in text.chars() {
+            match c {
+                '\n' => app.input(key(KeyCode::Enter))?,
+                c => app.input(key(KeyCode::Char(c)))?,
+            };
+        }
+        Ok(())
+    }
+
+    /// Feed the events to the app through [`App::step`] (as if they were replayed), along with the
+    /// events that are already queued for replay, rendering to a headless terminal. This returns
+    /// the exit message, if the shell exits.
+    fn drive<E: Execute>(
+        app: &mut App<E>,
+        events: impl IntoIterator<Item = Event>,
+    ) -> anyhow::Result<Option<String>> {
+        app.replay.extend(events);
+        let mut terminal = Terminal::new(TestBackend::new(40, 10))?;
+        while !app.replay.is_empty() {
+            if let Some(msg) = app.step(&mut terminal)? {
+                return Ok(Some(msg));
+            }
+        }
+        Ok(None)
+    }
+
+    /// A path in the temporary directory, unique to the test (and the process).
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("shelgon-{}-{}", std::process::id(), name))
+    }
+
+    /// The command being typed and the cursor, if the shell is idle.
+    fn idle<E: Execute>(app: &App<E>) -> Option<(&str, usize)> {
+        match app.state {
+            State::Idle(ref cmd, cursor, _) => Some((cmd, cursor)),
+            State::Running(..) => None,
+        }
+    }
+
+    /// Render a frame of the app, returning the buffer of the terminal.
+    fn frame<E: Execute>(app: &mut App<E>, width: u16, height: u16) -> anyhow::Result<Buffer> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+        terminal.draw(|f| app.render(f))?;
+        Ok(terminal.backend().buffer().clone())
+    }
+
+    /// The text of the rows of the buffer, skipping the cells covered by the wide characters.
+    fn rows(buffer: &Buffer) -> Vec<String> {
+        let area = buffer.area;
+        (area.top()..area.bottom())
+            .map(|y| {
+                let mut row = String::new();
+                let mut x = area.left();
+                while x < area.right() {
+                    let symbol = buffer[(x, y)].symbol();
+                    row.push_str(symbol);
+                    x += u16::try_from(symbol.width().max(1)).unwrap_or(1);
+                }
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// The column (in cells) of the first occurrence of the text on the row, if any.
+    fn column_of(buffer: &Buffer, y: u16, text: &str) -> Option<u16> {
+        let area = buffer.area;
+        (area.left()..area.right()).find(|&x| {
+            let mut cells = (x..area.right()).map(|x| buffer[(x, y)].symbol());
+            text.chars()
+                .all(|c| cells.next() == Some(c.to_string().as_str()))
+        })
+    }
+
+    /// An executor with a powerline-style prompt, mixing wide and narrow glyphs.
+    struct Powerline;
+
+    impl Execute for Powerline {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "╭─ 🐉 ~/shelgon \u{e0b0}".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The command starts right after the display width of a wide prompt (and the separator),
+    /// both while it's typed and in the history.
+    #[test]
+    fn command_column_follows_wide_prompt() -> anyhow::Result<()> {
+        let mut app = app(Powerline, ());
+        let column = Powerline.prompt(&()).width() + PROMPT_SEPARATOR.width();
+        assert_ne!(column, Powerline.prompt(&()).chars().count() + 1);
+
+        app.run_command("ls", None)?;
+        type_text(&mut app, "git")?;
+        let buffer = frame(&mut app, 40, 4)?;
+
+        assert_eq!(column_of(&buffer, 0, "ls"), Some(column as u16));
+        assert_eq!(column_of(&buffer, 1, "git"), Some(column as u16));
+        assert_eq!(app.cursor_position(), Some((1, column as u16 + 3)));
+        Ok(())
+    }
+
+    /// The events recorded from a session, when replayed into a fresh shell, reproduce the same
+    /// history.
+    #[test]
+    fn replayed_recording_reproduces_history() -> anyhow::Result<()> {
+        let path = temp_path("session.rec");
+        let mut recorded = app(Echo, Vec::new()).with_record(&path)?;
+        drive(
+            &mut recorded,
+            [
+                key(KeyCode::Char('l')),
+                key(KeyCode::Char('x')),
+                key(KeyCode::Backspace),
+                key(KeyCode::Char('s')),
+                key(KeyCode::Enter),
+                Event::Paste("git log".to_string()),
+                key(KeyCode::Enter),
+                Event::Resize(60, 20),
+            ],
+        )?;
+
+        let mut replayed = app(Echo, Vec::new()).replay(&path)?;
+        drive(&mut replayed, [])?;
+        std::fs::remove_file(&path)?;
+
+        let commands = |app: &App<Echo>| {
+            app.history()
+                .iter()
+                .map(|output| (output.command.clone(), output.stdout.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(replayed.context(), &["ls", "git log"]);
+        assert_eq!(commands(&replayed), commands(&recorded));
+        Ok(())
+    }
+
+    /// The app returned once the shell exits keeps the history and the context.
+    #[test]
+    fn returned_app_keeps_history() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_manage_terminal(false);
+        shell.replay.extend([
+            Event::Paste("ls -a".to_string()),
+            key(KeyCode::Enter),
+            ctrl('d'),
+        ]);
+        let viewport = Viewport::Fixed(Rect::new(0, 0, 40, 10));
+        let (msg, shell) = shell.execute_on(Vec::new(), viewport)?;
+
+        assert_eq!(msg, "");
+        assert_eq!(shell.context(), &["ls -a"]);
+        assert_eq!(shell.history()[0].stdout, ["ls -a"]);
+        Ok(())
+    }
+
+    /// Without managing the terminal, the escapes that set up (and restore) the terminal aren't
+    /// written, while the hooks still are.
+    #[test]
+    fn unmanaged_terminal_is_left_alone() -> anyhow::Result<()> {
+        let contains = |out: &[u8], escape: &[u8]| out.windows(escape.len()).any(|w| w == escape);
+        let alternate_screen = b"\x1b[?1049";
+        let bracketed_paste = b"\x1b[?2004";
+
+        let mut shell = app(Echo, Vec::new())
+            .with_manage_terminal(false)
+            .with_setup_hook(|out| Ok(out.write_all(b"<setup>")?))
+            .with_teardown_hook(|out| Ok(out.write_all(b"<teardown>")?));
+        shell.replay.push_back(ctrl('d'));
+        let mut out = Vec::new();
+        shell.execute_on(&mut out, Viewport::Fixed(Rect::new(0, 0, 40, 10)))?;
+        assert!(out.starts_with(b"<setup>"));
+        assert!(contains(&out, b"<teardown>"));
+        assert!(!contains(&out, alternate_screen));
+        assert!(!contains(&out, bracketed_paste));
+
+        // the managed terminal is restored with them.
+        let mut out = Vec::new();
+        app(Echo, Vec::new()).restore_terminal(&mut out)?;
+        assert!(contains(&out, alternate_screen));
+        assert!(contains(&out, bracketed_paste));
+        Ok(())
+    }
+
+    /// The control characters that are typed or pasted are stripped, or rendered in the caret
+    /// notation, per the policy.
+    #[test]
+    fn control_chars_follow_policy() -> anyhow::Result<()> {
+        for (policy, expected) in [
+            (ControlCharPolicy::Strip, "echo abc"),
+            (ControlCharPolicy::Caret, "echo a^Gb^[^I^?c"),
+        ] {
+            let mut shell = app(Echo, Vec::new()).with_control_char_policy(policy);
+            type_text(&mut shell, "echo a")?;
+            shell.input(Event::Paste("\x07b\x1b".to_string()))?;
+            shell.input(key(KeyCode::Char('\t')))?;
+            shell.input(Event::Paste("\x7fc".to_string()))?;
+
+            assert_eq!(idle(&shell), Some((expected, expected.len())));
+        }
+        Ok(())
+    }
+
+    /// The session saved when the shell exits is restored by the next shell, with the structured
+    /// history intact.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn session_is_restored_after_exit() -> anyhow::Result<()> {
+        let path = temp_path("session.json");
+        let viewport = Viewport::Fixed(Rect::new(0, 0, 40, 10));
+        let mut shell = app(Echo, Vec::new())
+            .with_manage_terminal(false)
+            .with_session_file(&path);
+        shell.replay.extend([
+            Event::Paste("git status".to_string()),
+            key(KeyCode::Enter),
+            Event::Paste("cat".to_string()),
+            key(KeyCode::Enter),
+            Event::Paste("a \"quoted\" line\nü".to_string()),
+            ctrl('d'),
+            ctrl('d'),
+        ]);
+        let (_, shell) = shell.execute_on(Vec::new(), viewport)?;
+
+        let restored = app(Echo, Vec::new()).with_session_file(&path);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(restored.history().len(), 2);
+        assert_eq!(restored.history()[1].stdin, ["a \"quoted\" line", "ü"]);
+        assert!(restored.history()[0].timestamp.is_some());
+        assert_eq!(
+            serde_json::to_value(restored.history())?,
+            serde_json::to_value(shell.history())?
+        );
+        Ok(())
+    }
+
+    /// An executor that prints a secret token, which its transform redacts.
+    struct Vault;
+
+    impl Execute for Vault {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "vault>".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn transform_output(&self, line: &str) -> Line<'static> {
+            match line.split_once("token=") {
+                Some((before, _)) => Line::from(vec![
+                    Span::raw(before.to_string()),
+                    Span::styled("token=[redacted]", Style::default().magenta()),
+                ]),
+                None => Line::raw(line.to_string()),
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdout: vec!["login ok".to_string(), "token=hvs.s3cr3t".to_string()],
+                stderr: vec!["expiring token=hvs.s3cr3t".to_string()],
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The output lines are rendered as transformed, both the stdout and the stderr.
+    #[test]
+    fn transform_output_redacts_rendered_lines() -> anyhow::Result<()> {
+        let mut shell = app(Vault, ()).with_color(ColorMode::Always);
+        shell.run_command("vault login", None)?;
+        let buffer = frame(&mut shell, 40, 6)?;
+        let rows = rows(&buffer);
+
+        assert_eq!(
+            rows[..4],
+            [
+                "vault> vault login",
+                "login ok",
+                "token=[redacted]",
+                "expiring token=[redacted]"
+            ]
+        );
+        assert!(!rows.concat().contains("s3cr3t"));
+        // the stderr is still styled as an error, around the style of the transform.
+        assert_eq!(buffer[(0, 3)].fg, ratatui::style::Color::Red);
+        assert_eq!(buffer[(9, 3)].fg, ratatui::style::Color::Magenta);
+        Ok(())
+    }
+
+    /// The cursor position is tracked through the wrapping of the command, and the scrolling of
+    /// the history, landing on the cell of the cursor.
+    #[test]
+    fn cursor_position_matches_frame() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_color(ColorMode::Always);
+        assert_eq!(shell.cursor_position(), None);
+        for command in ["ls", "pwd", "whoami"] {
+            shell.run_command(command, None)?;
+        }
+        // `$ git status` fills the row, and the cursor wraps onto the next one, which the paragraph
+        // puts past a blank row, as the cursor is only whitespace.
+        type_text(&mut shell, "git status")?;
+        let buffer = frame(&mut shell, 12, 4)?;
+        assert_eq!(rows(&buffer)[..2], ["whoami", "$ git status"]);
+        assert_eq!(shell.cursor_position(), Some((3, 0)));
+        assert_eq!(buffer[(0, 3)].bg, ratatui::style::Color::White);
+
+        for _ in 0..3 {
+            shell.input(key(KeyCode::Left))?;
+        }
+        let buffer = frame(&mut shell, 12, 4)?;
+        assert_eq!(rows(&buffer)[3], "$ git status");
+        assert_eq!(shell.cursor_position(), Some((3, 9)));
+        assert_eq!(buffer[(9, 3)].symbol(), "t");
+        assert_eq!(buffer[(9, 3)].bg, ratatui::style::Color::White);
+        // the command is wrapped at the cell, rather than at the word, to keep the cursor in place.
+        let buffer = frame(&mut shell, 8, 4)?;
+        assert_eq!(rows(&buffer)[2..], ["$ git st", "atus"]);
+        assert_eq!(shell.cursor_position(), Some((3, 1)));
+        assert_eq!(buffer[(1, 3)].bg, ratatui::style::Color::White);
+
+        // the cursor scrolled out of the view has no position.
+        shell.input(key(KeyCode::PageUp))?;
+        frame(&mut shell, 12, 4)?;
+        assert_eq!(shell.cursor_position(), None);
+        Ok(())
+    }
+
+    /// The commands that are executed more often are completed first, and the rest keep the order
+    /// of the executor.
+    #[test]
+    fn completion_ranks_frequent_commands() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new());
+        for command in ["git status", "grep -rn todo", "ls", "grep -c fixme"] {
+            shell.run_command(command, None)?;
+        }
+        type_text(&mut shell, "g")?;
+        shell.input(key(KeyCode::Tab))?;
+
+        let State::Idle(_, _, Some(ref completions)) = shell.state else {
+            anyhow::bail!("the completions aren't listed");
+        };
+        assert_eq!(completions.candidates, ["rep", "it"]);
+        Ok(())
+    }
+
+    /// The output is teed as plain text as the commands are executed, and is still rendered.
+    #[test]
+    fn output_is_teed_live() -> anyhow::Result<()> {
+        let sink = Shared::default();
+        let mut shell = app(Echo, Vec::new()).with_output_tee(Box::new(sink.clone()));
+        let teed = || String::from_utf8(sink.0.lock().expect("poisoned").clone());
+
+        drive(
+            &mut shell,
+            [Event::Paste("ls -l".to_string()), key(KeyCode::Enter)],
+        )?;
+        assert_eq!(teed()?, "$ ls -l\nls -l\n");
+
+        let typed = "cat\nmeow\npurr".chars().map(|c| match c {
+            '\n' => key(KeyCode::Enter),
+            c => key(KeyCode::Char(c)),
+        });
+        drive(&mut shell, typed.chain([ctrl('d')]))?;
+        assert_eq!(teed()?, "$ ls -l\nls -l\n$ cat\nmeow\npurr\nmeow\npurr\n");
+
+        let buffer = frame(&mut shell, 40, 8)?;
+        let rendered = [
+            "$ ls -l", "ls -l", "$ cat", "meow", "purr", "meow", "purr", "$",
+        ];
+        assert_eq!(rows(&buffer), rendered);
+        Ok(())
+    }
+
+    /// `Esc` dismisses the completions, leaving the command and the cursor as they are.
+    #[test]
+    fn escape_dismisses_completions() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_mid_line_tab(MidLineTab::Complete);
+        type_text(&mut shell, "g -v")?;
+        for _ in 0..3 {
+            shell.input(key(KeyCode::Left))?;
+        }
+        shell.input(key(KeyCode::Tab))?;
+        let buffer = frame(&mut shell, 20, 3)?;
+        assert_eq!(rows(&buffer), ["$ g -v", "  git -v", "  grep -v"]);
+
+        shell.input(key(KeyCode::Esc))?;
+        assert_eq!(idle(&shell), Some(("g -v", 1)));
+        let buffer = frame(&mut shell, 20, 3)?;
+        assert_eq!(rows(&buffer), ["$ g -v", "", ""]);
+        Ok(())
+    }
+
+    /// The commands spawn on (and block on) the shared runtime, through the handle.
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn commands_use_shared_runtime() -> anyhow::Result<()> {
+        /// An executor that reports the thread that its task is spawned on.
+        struct Spawner;
+
+        impl Execute for Spawner {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "~>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                let task = input
+                    .handle
+                    .spawn(async { std::thread::current().name().map(String::from) });
+                let thread = input.handle.block_on(task)?.unwrap_or_default();
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    stdout: vec![thread],
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let shared = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("shared-worker")
+            .build()?;
+        let mut shell = app(Spawner, ()).with_runtime_handle(shared.handle().clone());
+        shell.run_command("whereami", None)?;
+
+        assert_eq!(shell.history()[0].stdout, ["shared-worker"]);
+        assert!(shell.runtime_handle().block_on(async { true }));
+        Ok(())
+    }
+
+    /// The stdin status is rendered below the stdin lines, as they are collected.
+    #[test]
+    fn stdin_status_counts_lines() -> anyhow::Result<()> {
+        /// An executor that reads the queries from the stdin, counting the collected lines.
+        struct Sql;
+
+        impl Execute for Sql {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "sql>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    stdin_required: true,
+                    ..Default::default()
+                }
+            }
+
+            fn stdin_status(
+                &self,
+                _: &(),
+                prep: &Prepare,
+                collected: &[String],
+            ) -> Option<Line<'static>> {
+                let status = format!("{}: {} line(s)", prep.command, collected.len());
+                Some(Line::styled(status, Style::default().italic()))
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let mut shell = app(Sql, ());
+        type_text(&mut shell, "query\n")?;
+        let buffer = frame(&mut shell, 24, 3)?;
+        assert_eq!(rows(&buffer), ["sql> query", "", "query: 0 line(s)"]);
+
+        type_text(&mut shell, "select 1;\nselect 2;")?;
+        let buffer = frame(&mut shell, 24, 4)?;
+        let expected = ["sql> query", "select 1;", "select 2;", "query: 2 line(s)"];
+        assert_eq!(rows(&buffer), expected);
+        assert!(buffer[(0, 3)].modifier.contains(Modifier::ITALIC));
+        Ok(())
+    }
+
+    /// The preview is rendered dimmed below the command, following the command as it is typed.
+    #[test]
+    fn preview_follows_typed_command() -> anyhow::Result<()> {
+        /// An executor that previews the expansion of the `$(...)` substitutions from the
+        /// variables in the context.
+        struct Expand;
+
+        impl Execute for Expand {
+            type Context = Vec<(&'static str, &'static str)>;
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "%".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn preview(&self, ctx: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+                buffer.contains("$(").then(|| {
+                    let expanded = ctx.iter().fold(buffer.to_string(), |line, (name, value)| {
+                        line.replace(&format!("$({})", name), value)
+                    });
+                    vec![Line::raw(format!("= {}", expanded))]
+                })
+            }
+
+            fn execute(
+                &self,
+                _: &mut Self::Context,
+                _: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Command(CommandOutput::default()))
+            }
+        }
+
+        let mut shell = app(Expand, vec![("whoami", "root"), ("pwd", "/srv")]).with_preview(true);
+        type_text(&mut shell, "echo")?;
+        assert_eq!(rows(&frame(&mut shell, 24, 2)?), ["% echo", ""]);
+
+        type_text(&mut shell, " $(whoami)")?;
+        let buffer = frame(&mut shell, 24, 2)?;
+        assert_eq!(rows(&buffer), ["% echo $(whoami)", "= echo root"]);
+        assert!(buffer[(0, 1)].modifier.contains(Modifier::DIM));
+
+        type_text(&mut shell, "@$(pwd)")?;
+        let buffer = frame(&mut shell, 24, 2)?;
+        assert_eq!(rows(&buffer)[1], "= echo root@/srv");
+        Ok(())
+    }
+
+    /// The context and the executor that are changed through the accessors are reflected in the
+    /// prompt.
+    #[test]
+    fn accessors_change_prompt() -> anyhow::Result<()> {
+        /// An executor that prompts with the user (its configuration) and the working directory
+        /// (the context).
+        struct Login {
+            user: String,
+        }
+
+        impl Execute for Login {
+            type Context = PathBuf;
+
+            fn prompt(&self, ctx: &Self::Context) -> String {
+                format!("{}:{}$", self.user, ctx.display())
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                _: &mut Self::Context,
+                _: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Command(CommandOutput::default()))
+            }
+        }
+
+        let user = "ash".to_string();
+        let mut shell = app(Login { user }, PathBuf::from("/home/ash"));
+        assert_eq!(rows(&frame(&mut shell, 32, 1)?), ["ash:/home/ash$"]);
+
+        shell.context_mut().push("pokedex");
+        assert_eq!(shell.context(), Path::new("/home/ash/pokedex"));
+        assert_eq!(rows(&frame(&mut shell, 32, 1)?), ["ash:/home/ash/pokedex$"]);
+
+        shell.executor_mut().user = "misty".to_string();
+        assert_eq!(shell.executor().user, "misty");
+        assert_eq!(
+            rows(&frame(&mut shell, 32, 1)?),
+            ["misty:/home/ash/pokedex$"]
+        );
+        Ok(())
+    }
+
+    /// With `Ctrl+C` aborting, the stdin is discarded without executing the command, while
+    /// `Ctrl+D` still submits it.
+    #[test]
+    fn stdin_interrupt_aborts() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_stdin_interrupt(StdinAction::Abort);
+        type_text(&mut shell, "cat\nmeow")?;
+        shell.input(ctrl('c'))?;
+        assert_eq!(idle(&shell), Some(("", 0)));
+        assert!(shell.history().is_empty());
+        assert!(shell.context().is_empty());
+
+        type_text(&mut shell, "cat\npurr\nhiss")?;
+        shell.input(ctrl('d'))?;
+        assert_eq!(shell.history()[0].stdout, ["purr", "hiss"]);
+        assert_eq!(shell.context(), &["cat"]);
+
+        // by default, `Ctrl+C` submits the stdin as well.
+        let mut shell = app(Echo, Vec::new());
+        type_text(&mut shell, "cat\nmeow")?;
+        shell.input(ctrl('c'))?;
+        assert_eq!(shell.history()[0].stdout, ["meow"]);
+        Ok(())
+    }
+
+    /// Every occurrence of the highlighted term in the history is highlighted, until cleared.
+    #[test]
+    fn highlight_marks_every_occurrence() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_color(ColorMode::Always);
+        for command in ["grep todo src", "ls", "vim todo.md todo.txt"] {
+            shell.run_command(command, None)?;
+        }
+        shell.highlight("todo");
+        let buffer = frame(&mut shell, 24, 7)?;
+        let highlighted = |buffer: &Buffer| {
+            let area = buffer.area;
+            (area.top()..area.bottom())
+                .flat_map(|y| (area.left()..area.right()).map(move |x| (x, y)))
+                .filter(|&(x, y)| buffer[(x, y)].bg == ratatui::style::Color::Yellow)
+                .map(|(x, y)| buffer[(x, y)].symbol().to_string())
+                .collect::<String>()
+        };
+        // both the command line and the output of the entries are highlighted.
+        assert_eq!(highlighted(&buffer), "todo".repeat(6));
+        assert_eq!(buffer[(7, 0)].bg, ratatui::style::Color::Yellow);
+        assert_eq!(buffer[(4, 5)].bg, ratatui::style::Color::Yellow);
+        assert_eq!(buffer[(12, 5)].bg, ratatui::style::Color::Yellow);
+
+        shell.clear_highlight();
+        assert_eq!(highlighted(&frame(&mut shell, 24, 7)?), "");
+        Ok(())
+    }
+
+    /// The pending entry is rendered as pending, until its output is resolved (on a tick) in
+    /// place.
+    #[test]
+    fn pending_output_resolves_in_place() -> anyhow::Result<()> {
+        /// An executor whose outputs are resolved later, through the resolvers in the context.
+        struct Deferred;
+
+        impl Execute for Deferred {
+            type Context = Vec<PendingResolver>;
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                ">>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                ctx: &mut Self::Context,
+                _: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                let (pending, resolver) = PendingOutput::new();
+                ctx.push(resolver);
+                Ok(OutputAction::Pending(pending))
+            }
+        }
+
+        let mut shell = app(Deferred, Vec::new());
+        shell.run_command("cargo build", None)?;
+        shell.run_command("cargo test", None)?;
+        shell.tick();
+        let rendered = [">> cargo build", "…", ">> cargo test", "…", ">>"];
+        assert_eq!(rows(&frame(&mut shell, 20, 5)?), rendered);
+
+        let build = shell.context_mut().remove(0);
+        build.resolve(Ok(CommandOutput {
+            prompt: ">>".to_string(),
+            command: "cargo build".to_string(),
+            stdout: vec!["Finished".to_string()],
+            ..Default::default()
+        }));
+        // the resolved output is only taken on the tick.
+        assert!(shell.history()[0].stdout.is_empty());
+        shell.tick();
+        assert_eq!(shell.history()[0].stdout, ["Finished"]);
+        let rendered = [">> cargo build", "Finished", ">> cargo test", "…", ">>"];
+        assert_eq!(rows(&frame(&mut shell, 20, 5)?), rendered);
+
+        // the error is rendered as the stderr of the entry.
+        let test = shell.context_mut().remove(0);
+        test.resolve(Err(anyhow::anyhow!("1 test failed")));
+        shell.tick();
+        assert_eq!(shell.history()[1].stderr, ["1 test failed"]);
+        assert!(shell.pending.is_empty());
+        Ok(())
+    }
+
+    /// The line is split on the separator (outside of the quotes), and the commands are executed
+    /// in order, each with its own entry.
+    #[test]
+    fn separator_runs_each_command() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_command_separator(';');
+        let line = r#"echo a; echo "b; c";; git commit -m 'x;y'"#;
+        let exit = drive(
+            &mut shell,
+            [
+                Event::Paste(line.to_string()),
+                key(KeyCode::Enter),
+                ctrl('d'),
+            ],
+        )?;
+
+        // the queued commands are executed before the next key press.
+        assert!(exit.is_some());
+        let commands = ["echo a", r#"echo "b; c""#, "git commit -m 'x;y'"];
+        assert_eq!(shell.context(), &commands);
+        let entries = shell.history().iter().map(|entry| entry.command.as_str());
+        assert_eq!(entries.collect::<Vec<_>>(), commands);
+        Ok(())
+    }
+
+    /// The builtin lists the commands in the history with their index, without reaching the
+    /// executor, under the configured name.
+    #[test]
+    fn history_builtin_lists_commands() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_history_builtin("hist");
+        type_text(&mut shell, "git init\nls -a\nhist\n")?;
+
+        assert_eq!(shell.context(), &["git init", "ls -a"]);
+        let listed = &shell.history()[2];
+        assert_eq!(listed.command, "hist");
+        assert_eq!(listed.stdout, ["    1  git init", "    2  ls -a"]);
+
+        // the other names still reach the executor.
+        type_text(&mut shell, "history\n")?;
+        assert_eq!(shell.context(), &["git init", "ls -a", "history"]);
+        Ok(())
+    }
+
+    /// `Tab` with nothing to complete gives the configured feedback: a notice (until the next key
+    /// press) or the bell.
+    #[test]
+    fn empty_completion_gives_feedback() -> anyhow::Result<()> {
+        let mut shell =
+            app(Echo, Vec::new()).with_empty_completion_feedback(EmptyCompletionFeedback::Message);
+        type_text(&mut shell, "zip")?;
+        shell.input(key(KeyCode::Tab))?;
+        let buffer = frame(&mut shell, 20, 2)?;
+        assert_eq!(rows(&buffer), ["$ zip", "(no completions)"]);
+        assert!(buffer[(0, 1)].modifier.contains(Modifier::DIM));
+        assert!(!shell.bell);
+
+        shell.input(key(KeyCode::Char('s')))?;
+        assert_eq!(rows(&frame(&mut shell, 20, 2)?), ["$ zips", ""]);
+
+        let mut shell =
+            app(Echo, Vec::new()).with_empty_completion_feedback(EmptyCompletionFeedback::Bell);
+        type_text(&mut shell, "zip")?;
+        shell.input(key(KeyCode::Tab))?;
+        assert!(shell.bell);
+        assert_eq!(shell.notice, None);
+        Ok(())
+    }
+
+    /// The teardown hook runs before the terminal is restored, which happens even if the hook
+    /// fails. A failing setup hook stops the shell before it starts.
+    #[test]
+    fn hooks_wrap_terminal_management() -> anyhow::Result<()> {
+        let position = |out: &[u8], needle: &[u8]| {
+            out.windows(needle.len())
+                .position(|window| window == needle)
+        };
+        let leave_alternate_screen = b"\x1b[?1049l";
+
+        let mut shell =
+            app(Echo, Vec::new()).with_teardown_hook(|out| Ok(out.write_all(b"\x1b[<u")?));
+        let mut out = Vec::new();
+        shell.teardown_terminal(&mut out)?;
+        let hook = position(&out, b"\x1b[<u").expect("the hook isn't run");
+        let restored = position(&out, leave_alternate_screen).expect("the terminal isn't restored");
+        assert!(hook < restored);
+
+        let mut shell = app(Echo, Vec::new())
+            .with_teardown_hook(|_| Err(anyhow::anyhow!("keyboard flags stuck")));
+        let mut out = Vec::new();
+        let error = shell
+            .teardown_terminal(&mut out)
+            .expect_err("the hook failed");
+        assert_eq!(error.to_string(), "keyboard flags stuck");
+        assert!(position(&out, leave_alternate_screen).is_some());
+
+        let teardown = Arc::new(Mutex::new(false));
+        let torn_down = teardown.clone();
+        let mut shell = app(Echo, Vec::new())
+            .with_manage_terminal(false)
+            .with_setup_hook(|_| Err(anyhow::anyhow!("unsupported terminal")))
+            .with_teardown_hook(move |_| {
+                *torn_down.lock().expect("poisoned") = true;
+                Ok(())
+            });
+        shell.replay.push_back(ctrl('d'));
+        let viewport = Viewport::Fixed(Rect::new(0, 0, 40, 10));
+        let error = shell.execute_on(Vec::new(), viewport).err();
+        assert_eq!(
+            error.map(|e| e.to_string()).as_deref(),
+            Some("unsupported terminal")
+        );
+        assert!(!*teardown.lock().expect("poisoned"));
+        Ok(())
+    }
+
+    /// The blinking cursor toggles at the end of every interval, and is shown again on the key
+    /// press. The clock is controlled by moving the start of the blinking back.
+    #[test]
+    fn cursor_blinks_across_intervals() -> anyhow::Result<()> {
+        let interval = Duration::from_millis(500);
+        let mut shell = app(Echo, Vec::new())
+            .with_color(ColorMode::Always)
+            .with_cursor_blink(interval);
+        type_text(&mut shell, "ls")?;
+
+        let mut cursor_at = |elapsed: u64| -> anyhow::Result<ratatui::style::Color> {
+            let elapsed = Duration::from_millis(elapsed);
+            shell.blink_epoch = Instant::now()
+                .checked_sub(elapsed)
+                .ok_or_else(|| anyhow::anyhow!("the clock can't be moved back"))?;
+            Ok(frame(&mut shell, 10, 1)?[(4, 0)].bg)
+        };
+        let shown = ratatui::style::Color::White;
+        assert_eq!(cursor_at(100)?, shown);
+        assert_ne!(cursor_at(600)?, shown);
+        assert_eq!(cursor_at(1100)?, shown);
+        assert_ne!(cursor_at(1900)?, shown);
+
+        // typing restarts the blinking, with the cursor shown.
+        shell.input(key(KeyCode::Char('s')))?;
+        assert_eq!(frame(&mut shell, 10, 1)?[(5, 0)].bg, shown);
+        assert_eq!(shell.tick_rate(), Some(interval));
+        Ok(())
+    }
+
+    /// Cycling past the last (or the first) candidate wraps around when enabled, and stays put
+    /// otherwise.
+    #[test]
+    fn completion_cycling_wraps() -> anyhow::Result<()> {
+        let selected = |shell: &App<Echo>| match shell.state {
+            State::Idle(_, _, Some(ref completions)) => completions.selected,
+            _ => None,
+        };
+        for (wrap, forward, backward) in
+            [(true, [0, 1, 0], [1, 0, 1]), (false, [0, 1, 1], [0, 0, 0])]
+        {
+            let mut shell = app(Echo, Vec::new()).with_completion_wrap(wrap);
+            type_text(&mut shell, "g")?;
+            shell.input(key(KeyCode::Tab))?;
+            assert_eq!(selected(&shell), None);
+
+            for (code, expected) in [(KeyCode::Tab, forward), (KeyCode::BackTab, backward)] {
+                for index in expected {
+                    shell.input(key(code))?;
+                    assert_eq!(selected(&shell), Some(index));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The stdout is revealed character by character as the ticks go by, until it is complete;
+    /// `Ctrl+C` reveals it at once. The clock is controlled by moving the start of the reveal back.
+    #[test]
+    fn typewriter_reveals_progressively() -> anyhow::Result<()> {
+        let reveal_at = |shell: &mut App<Echo>, elapsed: u64| -> anyhow::Result<Vec<String>> {
+            if let Some(typewriter) = shell.typewriter.as_mut() {
+                typewriter.started = Instant::now()
+                    .checked_sub(Duration::from_millis(elapsed))
+                    .ok_or_else(|| anyhow::anyhow!("the clock can't be moved back"))?;
+            }
+            shell.tick();
+            Ok(rows(&frame(shell, 12, 6)?))
+        };
+        let mut shell = app(Echo, Vec::new()).with_typewriter(Duration::from_millis(100));
+        shell.run_command("cat", Some(vec!["hi".to_string(), "there".to_string()]))?;
+
+        // the stdin is rendered right away, followed by the characters (and the line ends) of
+        // the stdout that are revealed.
+        let entry = ["$ cat", "hi", "there"];
+        assert_eq!(
+            reveal_at(&mut shell, 0)?[..4],
+            [&entry[..], &["$"]].concat()
+        );
+        assert_eq!(reveal_at(&mut shell, 350)?[3..5], ["hi", "$"]);
+        assert_eq!(reveal_at(&mut shell, 650)?[3..6], ["hi", "the", "$"]);
+        assert!(shell.typewriter.is_some());
+        assert_eq!(reveal_at(&mut shell, 900)?[3..6], ["hi", "there", "$"]);
+        assert!(shell.typewriter.is_none());
+
+        shell.run_command("cat", Some(vec!["bye".to_string()]))?;
+        assert_eq!(reveal_at(&mut shell, 150)?[3..6], ["bye", "b", "$"]);
+        shell.input(ctrl('c'))?;
+        assert!(shell.typewriter.is_none());
+        assert_eq!(rows(&frame(&mut shell, 12, 6)?)[3..6], ["bye", "bye", "$"]);
+        Ok(())
+    }
+
+    /// The gutter starts the stdin lines and the continuation rows of the command, both while
+    /// they are typed and in the history.
+    #[test]
+    fn gutter_marks_continuation_and_stdin() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new())
+            .with_gutter(Some('│'))
+            .with_multiline_paste(true);
+        shell.input(Event::Paste("git add .\ngit commit".to_string()))?;
+        let buffer = frame(&mut shell, 16, 2)?;
+        assert_eq!(rows(&buffer), ["$ git add .", "│ git commit"]);
+        assert!(buffer[(0, 1)].modifier.contains(Modifier::DIM));
+        assert_eq!(shell.cursor_position(), Some((1, 12)));
+
+        shell.input(key(KeyCode::Enter))?;
+        type_text(&mut shell, "cat\nfoo")?;
+        let entry = ["$ git add .", "│ git commit"];
+        let buffer = rows(&frame(&mut shell, 16, 8)?);
+        assert_eq!(buffer[..2], entry);
+        assert_eq!(buffer[4..6], ["$ cat", "│ foo"]);
+        shell.input(ctrl('d'))?;
+        let buffer = rows(&frame(&mut shell, 16, 8)?);
+        assert_eq!(buffer[..2], entry);
+        assert_eq!(buffer[4..], ["$ cat", "│ foo", "foo", "$"]);
+        Ok(())
+    }
+
+    /// `Ctrl+C` aborts the task of the pending output, rendering `^C`, and the shell is idle
+    /// again.
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn interrupt_aborts_pending_task() -> anyhow::Result<()> {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// Sets the flag when the task that holds it is dropped, i.e. aborted.
+        struct Dropped(Arc<AtomicBool>);
+
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        /// An executor whose commands never finish, until aborted.
+        struct Forever;
+
+        impl Execute for Forever {
+            type Context = Arc<AtomicBool>;
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "⏳".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                aborted: &mut Self::Context,
+                input: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                let guard = Dropped(aborted.clone());
+                let pending = PendingOutput::spawn(&input.handle, async move {
+                    let _guard = guard;
+                    std::future::pending().await
+                });
+                Ok(OutputAction::Pending(pending))
+            }
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()?;
+        let aborted = Arc::new(AtomicBool::new(false));
+        let mut shell = app(Forever, aborted.clone()).with_runtime_handle(runtime.handle().clone());
+        drive(
+            &mut shell,
+            [Event::Paste("sync".to_string()), key(KeyCode::Enter)],
+        )?;
+        assert_eq!(shell.pending.len(), 1);
+        assert!(!aborted.load(Ordering::SeqCst));
+
+        drive(&mut shell, [ctrl('c')])?;
+        assert!(shell.pending.is_empty());
+        assert_eq!(shell.history()[0].stdout, ["^C"]);
+        assert_eq!(idle(&shell), Some(("", 0)));
+        // the task is dropped by the runtime, once it sees the abort.
+        let started = Instant::now();
+        while !aborted.load(Ordering::SeqCst) && started.elapsed() < Duration::from_secs(5) {
+            std::thread::yield_now();
+        }
+        assert!(aborted.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    /// The time that the command took is recorded, and rendered dimmed after the command.
+    #[test]
+    fn duration_is_recorded_and_rendered() -> anyhow::Result<()> {
+        /// An executor that sleeps for the number of milliseconds in the command.
+        struct Nap;
+
+        impl Execute for Nap {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "z>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                std::thread::sleep(Duration::from_millis(input.command.parse()?));
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let mut shell = app(Nap, ()).with_durations(true);
+        shell.run_command("30", None)?;
+        let duration = shell.history()[0].duration.unwrap_or_default();
+        assert!(duration >= Duration::from_millis(30), "{:?}", duration);
+
+        let buffer = frame(&mut shell, 20, 2)?;
+        let row = &rows(&buffer)[0];
+        let rendered = format!("z> 30 ({}ms)", duration.as_millis());
+        assert_eq!(row, &rendered);
+        assert!(buffer[(6, 0)].modifier.contains(Modifier::DIM));
+
+        // without the setting, the duration is only recorded.
+        let mut shell = app(Nap, ());
+        shell.run_command("1", None)?;
+        assert!(shell.history()[0].duration.is_some());
+        assert_eq!(rows(&frame(&mut shell, 20, 2)?)[0], "z> 1");
+        Ok(())
+    }
+
+    /// `Tab` in the middle of the command is ignored, completes the text up to the cursor, or
+    /// inserts spaces, per the mode.
+    #[test]
+    fn mid_line_tab_follows_mode() -> anyhow::Result<()> {
+        for (mode, expected, candidates) in [
+            (MidLineTab::Ignore, ("l -la", 1), None),
+            (MidLineTab::Complete, ("ls -la", 2), Some(0)),
+            (MidLineTab::InsertSpaces(3), ("l    -la", 4), None),
+        ] {
+            let mut shell = app(Echo, Vec::new()).with_mid_line_tab(mode);
+            type_text(&mut shell, "l -la")?;
+            shell.input(key(KeyCode::Home))?;
+            shell.input(key(KeyCode::Right))?;
+            shell.input(key(KeyCode::Tab))?;
+
+            assert_eq!(idle(&shell), Some(expected), "{:?}", mode);
+            let listed = match shell.state {
+                State::Idle(_, _, ref completions) => {
+                    completions.as_ref().map(|c| c.candidates.len())
+                }
+                State::Running(..) => None,
+            };
+            assert_eq!(listed, candidates, "{:?}", mode);
+        }
+        Ok(())
+    }
+
+    /// The items are laid out in as many columns as fit the width (column by column), and laid
+    /// out again when the width changes.
+    #[test]
+    fn columns_fit_width() -> anyhow::Result<()> {
+        /// An executor that lists the first elements of the periodic table, in columns.
+        struct Elements;
+
+        impl Execute for Elements {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "pt>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), _: CommandInput) -> anyhow::Result<OutputAction> {
+                let elements = "H He Li Be B C N O F Ne Na Mg Al Si P S Cl Ar K Ca";
+                Ok(OutputAction::Columns(
+                    elements.split(' ').map(String::from).collect(),
+                ))
+            }
+        }
+
+        let mut shell = app(Elements, ());
+        shell.run_command("ls", None)?;
+
+        // 5 columns of 2 cells, with the gaps of 2, fit in 20 cells.
+        let rendered = [
+            "pt> ls",
+            "H   B   F   Al  Cl",
+            "He  C   Ne  Si  Ar",
+            "Li  N   Na  P   K",
+            "Be  O   Mg  S   Ca",
+            "pt>",
+        ];
+        assert_eq!(rows(&frame(&mut shell, 20, 6)?), rendered);
+
+        // 3 columns fit in 12 cells.
+        let buffer = rows(&frame(&mut shell, 12, 9)?);
+        assert_eq!(
+            buffer[1..8],
+            [
+                "H   O   P",
+                "He  F   S",
+                "Li  Ne  Cl",
+                "Be  Na  Ar",
+                "B   Mg  K",
+                "C   Al  Ca",
+                "N   Si",
+            ]
+        );
+        Ok(())
+    }
+
+    /// The redraw clears the terminal (every row of the viewport) and draws the frame again,
+    /// without touching the history.
+    #[test]
+    fn redraw_action_draws_again() -> anyhow::Result<()> {
+        /// An executor that redraws the shell on `refresh`, and counts the other commands.
+        struct Dashboard;
+
+        impl Execute for Dashboard {
+            type Context = usize;
+
+            fn prompt(&self, count: &Self::Context) -> String {
+                format!("[{}]", count)
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                count: &mut usize,
+                input: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                if input.command == "refresh" {
+                    return Ok(OutputAction::Redraw);
+                }
+                *count += 1;
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let cleared_rows = |command: &str| -> anyhow::Result<(usize, App<Dashboard>)> {
+            let mut shell = app(Dashboard, 0).with_manage_terminal(false);
+            shell.replay.extend([
+                Event::Paste(command.to_string()),
+                key(KeyCode::Enter),
+                ctrl('d'),
+            ]);
+            let mut out = Vec::new();
+            let (_, shell) = shell.execute_on(&mut out, Viewport::Fixed(Rect::new(0, 0, 20, 3)))?;
+            let clear_below = b"\x1b[J";
+            let cleared = out
+                .windows(clear_below.len())
+                .filter(|w| w == clear_below)
+                .count();
+            Ok((cleared, shell))
+        };
+
+        let (cleared, shell) = cleared_rows("uptime")?;
+        assert_eq!(cleared, 0);
+        assert_eq!(*shell.context(), 1);
+        let (cleared, shell) = cleared_rows("refresh")?;
+        assert_eq!(cleared, 3);
+        assert_eq!(*shell.context(), 0);
+        assert!(shell.history().is_empty());
+        Ok(())
+    }
+
+    /// The history records (and recalls) the command as typed, or as expanded by the executor.
+    #[test]
+    fn history_records_command_source() -> anyhow::Result<()> {
+        /// An executor that expands the `ll` alias.
+        struct Alias;
+
+        impl Execute for Alias {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "#".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                let command = match cmd.strip_prefix("ll") {
+                    Some(rest) => format!("ls -la{}", rest),
+                    None => cmd.to_string(),
+                };
+                Prepare {
+                    command,
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: format!("exec {}", input.command),
+                    ..Default::default()
+                }))
+            }
+        }
+
+        for (source, expected) in [
+            (Some(HistoryCommandSource::Raw), "ll /tmp"),
+            (Some(HistoryCommandSource::Transformed), "ls -la /tmp"),
+            (None, "exec ll /tmp"),
+        ] {
+            let mut shell = app(Alias, ());
+            if let Some(source) = source {
+                shell = shell.with_history_command_source(source);
+            }
+            type_text(&mut shell, "ll /tmp\n")?;
+            assert_eq!(shell.history()[0].command, expected);
+
+            shell.input(key(KeyCode::Up))?;
+            assert_eq!(idle(&shell), Some((expected, expected.len())));
+        }
+        Ok(())
+    }
+
+    /// The long command is scrolled (rather than wrapped) so that the visible slice follows the
+    /// cursor.
+    #[test]
+    fn horizontal_scroll_follows_cursor() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_horizontal_scroll(true);
+        type_text(&mut shell, "echo the quick brown fox")?;
+        let view = |shell: &mut App<Echo>| -> anyhow::Result<(String, Option<(u16, u16)>)> {
+            let buffer = rows(&frame(shell, 12, 2)?);
+            assert_eq!(buffer[1], "", "the command is wrapped");
+            Ok((buffer[0].clone(), shell.cursor_position()))
+        };
+
+        assert_eq!(
+            view(&mut shell)?,
+            ("k brown fox".to_string(), Some((0, 11)))
+        );
+        shell.input(key(KeyCode::Home))?;
+        assert_eq!(
+            view(&mut shell)?,
+            ("$ echo the q".to_string(), Some((0, 2)))
+        );
+        for _ in 0..14 {
+            shell.input(key(KeyCode::Right))?;
+        }
+        assert_eq!(
+            view(&mut shell)?,
+            ("o the quick".to_string(), Some((0, 11)))
+        );
+        Ok(())
+    }
+
+    /// `Tab` while collecting the stdin completes the line that is being typed, through the
+    /// executor, listing the candidates when it's ambiguous.
+    #[test]
+    fn stdin_line_is_completed() -> anyhow::Result<()> {
+        /// An executor that sends mail, completing the recipients in the stdin.
+        struct Mail;
+
+        impl Execute for Mail {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "✉".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    stdin_required: true,
+                    ..Default::default()
+                }
+            }
+
+            fn stdin_completion(
+                &self,
+                _: &(),
+                _: &Prepare,
+                partial: &str,
+            ) -> anyhow::Result<(String, Vec<String>)> {
+                let Some(name) = partial.strip_prefix("to: ") else {
+                    return Ok((String::new(), Vec::new()));
+                };
+                let matches = ["alice@", "bob@", "beth@"]
+                    .into_iter()
+                    .filter_map(|address| address.strip_prefix(name))
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                Ok(match matches.as_slice() {
+                    [only] => (only.clone(), Vec::new()),
+                    _ => (String::new(), matches),
+                })
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    stdin: input.stdin.unwrap_or_default(),
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let mut shell = app(Mail, ());
+        type_text(&mut shell, "send\nto: al")?;
+        shell.input(key(KeyCode::Tab))?;
+        type_text(&mut shell, "\nto: b")?;
+        shell.input(key(KeyCode::Tab))?;
+        let rendered = ["✉ send", "to: alice@", "to: b", "to: bob@  to: beth@"];
+        assert_eq!(rows(&frame(&mut shell, 24, 4)?), rendered);
+
+        type_text(&mut shell, "e")?;
+        shell.input(key(KeyCode::Tab))?;
+        shell.input(ctrl('d'))?;
+        assert_eq!(shell.history()[0].stdin, ["to: alice@", "to: beth@"]);
+        Ok(())
+    }
+
+    /// The oldest entries are evicted to keep the lines (of the stdin, the stdout and the stderr)
+    /// under the cap, and the latest entry keeps its latest lines on its own.
+    #[test]
+    fn total_lines_stay_capped() -> anyhow::Result<()> {
+        /// An executor that prints the numbers up to the one in the command, like `seq`, and then
+        /// up to the second number (if any) to the stderr, echoing the stdin.
+        struct Seq;
+
+        impl Execute for Seq {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "seq".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                let mut lasts = input.command.split(' ').map(str::parse::<usize>);
+                let stdout = lasts.next().transpose()?.unwrap_or_default();
+                let stderr = lasts.next().transpose()?.unwrap_or_default();
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    stdin: input.stdin.unwrap_or_default(),
+                    stdout: (1..=stdout).map(|n| n.to_string()).collect(),
+                    stderr: (1..=stderr).map(|n| n.to_string()).collect(),
+                    command: input.command,
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let total = |shell: &App<Seq>| {
+            shell
+                .history()
+                .iter()
+                .map(|e| e.stdin.len() + e.stdout.len() + e.stderr.len())
+                .sum::<usize>()
+        };
+        let commands = |shell: &App<Seq>| {
+            shell
+                .history()
+                .iter()
+                .map(|e| e.command.clone())
+                .collect::<Vec<_>>()
+        };
+        let mut shell = app(Seq, ()).with_max_total_lines(50);
+        for (command, kept, lines) in [
+            ("20", &["20"][..], 20),
+            ("30", &["20", "30"], 50),
+            ("15", &["30", "15"], 45),
+            ("80", &["80"], 50),
+        ] {
+            shell.run_command(command, None)?;
+            assert_eq!(commands(&shell), kept);
+            assert_eq!(total(&shell), lines);
+        }
+        let latest = &shell.history()[0].stdout;
+        assert_eq!((latest[0].as_str(), latest[49].as_str()), ("31", "80"));
+
+        // the stdin is dropped first, then the stdout, and then the stderr.
+        let lines = |count: usize| (1..=count).map(|n| format!("in {}", n)).collect::<Vec<_>>();
+        let mut shell = app(Seq, ()).with_max_total_lines(10);
+        shell.run_command("6 6", Some(lines(2)))?;
+        assert_eq!(total(&shell), 10);
+        let latest = &shell.history()[0];
+        assert!(latest.stdin.is_empty());
+        assert_eq!((latest.stdout.len(), latest.stdout[0].as_str()), (4, "3"));
+        assert_eq!(latest.stderr.len(), 6);
+
+        shell.run_command("0 50", Some(lines(50)))?;
+        assert_eq!(commands(&shell), ["0 50"]);
+        assert_eq!(total(&shell), 10);
+        let latest = &shell.history()[0];
+        assert!(latest.stdin.is_empty());
+        assert_eq!(
+            (latest.stderr[0].as_str(), latest.stderr[9].as_str()),
+            ("41", "50")
+        );
+        Ok(())
+    }
+
+    /// With the error recovery, the error of the command (after collecting its stdin) is rendered
+    /// as its stderr, and the shell is idle again with a clean command line.
+    #[test]
+    fn error_resets_to_idle() -> anyhow::Result<()> {
+        /// An executor that loads the numbers from the stdin, failing on anything else.
+        struct Load;
+
+        impl Execute for Load {
+            type Context = Vec<i64>;
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "load>".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    stdin_required: true,
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                numbers: &mut Vec<i64>,
+                input: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                for line in input.stdin.unwrap_or_default() {
+                    let number = line
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("{:?}: {}", line, e))?;
+                    numbers.push(number);
+                }
+                Ok(OutputAction::Command(CommandOutput::default()))
+            }
+        }
+
+        let mut shell = app(Load, Vec::new()).with_error_recovery(true);
+        type_text(&mut shell, "numbers\n4\n2x")?;
+        shell.input(ctrl('d'))?;
+        assert!(matches!(shell.state, State::Idle(ref cmd, 0, None) if cmd.is_empty()));
+        let failed = &shell.history()[0];
+        assert_eq!(failed.command, "numbers");
+        assert_eq!(failed.stdin, ["4", "2x"]);
+        assert_eq!(failed.stderr, [r#""2x": invalid digit found in string"#]);
+
+        // the shell works as before.
+        type_text(&mut shell, "numbers\n42")?;
+        shell.input(ctrl('d'))?;
+        assert_eq!(shell.context(), &[4, 42]);
+
+        // without the recovery, the error ends the shell.
+        let mut shell = app(Load, Vec::new());
+        type_text(&mut shell, "numbers\nx")?;
+        assert!(shell.input(ctrl('d')).is_err());
+        Ok(())
+    }
+
+    /// With the alignment, the output lines are indented to the column of the command, past the
+    /// prompt; without it, they start at the left edge.
+    #[test]
+    fn output_aligns_to_command() -> anyhow::Result<()> {
+        let mut aligned = app(Vault, ()).with_output_align_to_command(true);
+        aligned.run_command("vault login", None)?;
+        assert_eq!(
+            rows(&frame(&mut aligned, 40, 6)?)[..4],
+            [
+                "vault> vault login",
+                "       login ok",
+                "       token=[redacted]",
+                "       expiring token=[redacted]"
+            ]
+        );
+
+        let mut flush = app(Vault, ());
+        flush.run_command("vault login", None)?;
+        assert_eq!(rows(&frame(&mut flush, 40, 6)?)[1], "login ok");
+        Ok(())
+    }
+
+    /// The placeholder is rendered, dimmed, above the prompt until the first command runs.
+    #[test]
+    fn placeholder_shows_until_first_command() -> anyhow::Result<()> {
+        let hint = vec![
+            "Type a command, Tab to complete".to_string(),
+            "Ctrl+D to exit".to_string(),
+        ];
+        let mut shell = app(Echo, Vec::new()).with_empty_placeholder(hint);
+
+        let buffer = frame(&mut shell, 40, 5)?;
+        assert_eq!(
+            rows(&buffer)[..3],
+            ["Type a command, Tab to complete", "Ctrl+D to exit", "$"]
+        );
+        assert!(buffer[(0, 0)].modifier.contains(Modifier::DIM));
+
+        shell.run_command("ls", None)?;
+        let rows = rows(&frame(&mut shell, 40, 5)?);
+        assert_eq!(rows[..3], ["$ ls", "ls", "$"]);
+        assert!(!rows.concat().contains("Ctrl+D"));
+        Ok(())
+    }
+
+    /// An executor that counts the commands, and clears the screen on `clear`.
+    struct Tally;
+
+    impl Execute for Tally {
+        type Context = usize;
+
+        fn prompt(&self, count: &Self::Context) -> String {
+            format!("[{}]", count)
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            count: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            *count += 1;
+            Ok(match input.command.as_str() {
+                "clear" => OutputAction::Clear,
+                _ => OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    command: input.command,
+                    stdin: input.stdin.unwrap_or_default(),
+                    stdout: vec![format!("#{}", count)],
+                    ..Default::default()
+                }),
+            })
+        }
+    }
+
+    /// The command runs as if typed: the action is returned to the caller, and the history (and
+    /// the context) are updated along.
+    #[test]
+    fn run_command_returns_action() -> anyhow::Result<()> {
+        let mut shell = app(Tally, 0);
+
+        let action = shell.run_command("sum", Some(vec!["1".to_string(), "2".to_string()]))?;
+        let OutputAction::Command(output) = action else {
+            anyhow::bail!("expected the output of the command");
+        };
+        assert_eq!(output.stdout, ["#1"]);
+        assert_eq!(shell.history.len(), 1);
+        assert_eq!(shell.history[0].prompt, "[0]");
+        assert_eq!(shell.history[0].stdin, ["1", "2"]);
+        assert_eq!(shell.history[0].stdout, ["#1"]);
+        assert!(shell.history[0].duration.is_some());
+
+        shell.run_command("avg", None)?;
+        assert_eq!(shell.history.len(), 2);
+        assert_eq!(shell.history[1].prompt, "[1]");
+
+        let action = shell.run_command("clear", None)?;
+        assert!(matches!(action, OutputAction::Clear));
+        assert!(shell.history.is_empty());
+        assert_eq!(shell.context, 3);
+        assert!(matches!(shell.state, State::Idle(ref cmd, 0, None) if cmd.is_empty()));
+        Ok(())
+    }
+
+    /// With the prefix search, `Up` recalls the commands starting with what's typed, skipping the
+    /// rest, older with every press; `Down` walks back.
+    #[test]
+    fn prefix_search_recalls_matching() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_prefix_history_search(true);
+        for command in ["git log", "grep -r todo", "git status", "ls"] {
+            shell.run_command(command, None)?;
+        }
+
+        type_text(&mut shell, "gi")?;
+        shell.input(key(KeyCode::Up))?;
+        assert_eq!(idle(&shell), Some(("git status", 10)));
+        shell.input(key(KeyCode::Up))?;
+        assert_eq!(idle(&shell).map(|(cmd, _)| cmd), Some("git log"));
+        // past the oldest match, the buffer is kept.
+        shell.input(key(KeyCode::Up))?;
+        assert_eq!(idle(&shell).map(|(cmd, _)| cmd), Some("git log"));
+        shell.input(key(KeyCode::Down))?;
+        assert_eq!(idle(&shell).map(|(cmd, _)| cmd), Some("git status"));
+
+        // without it, `Up` recalls the most recent command.
+        let mut shell = app(Echo, Vec::new());
+        for command in ["git status", "ls"] {
+            shell.run_command(command, None)?;
+        }
+        type_text(&mut shell, "gi")?;
+        shell.input(key(KeyCode::Up))?;
+        assert_eq!(idle(&shell).map(|(cmd, _)| cmd), Some("ls"));
+        Ok(())
+    }
+
+    /// The thumb of the scrollbar follows the view: at the bottom of the track for the latest
+    /// output, moving up as the view is scrolled up. The text keeps clear of the reserved column.
+    #[test]
+    fn scrollbar_tracks_scroll_offset() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_scrollbar(true);
+        for index in 0..15 {
+            shell.run_command(&format!("echo {}", "x".repeat(index * 3)), None)?;
+        }
+        let thumb = |buffer: &Buffer| {
+            (0..buffer.area.height)
+                .filter(|y| buffer[(19, *y)].symbol() == "█")
+                .collect::<Vec<_>>()
+        };
+
+        let buffer = frame(&mut shell, 20, 8)?;
+        assert_eq!((shell.scroll_offset, shell.max_scroll), (0, 63));
+        assert_eq!(thumb(&buffer), [7]);
+        // the text wraps before the reserved column, which only has the track.
+        assert!((0..8).all(|y| ["║", "█"].contains(&buffer[(19, y)].symbol())));
+        assert_eq!(rows(&buffer)[7], "$                  █");
+
+        shell.input(key(KeyCode::PageUp))?;
+        let buffer = frame(&mut shell, 20, 8)?;
+        assert_eq!(shell.scroll_offset, 7);
+        assert_eq!(thumb(&buffer), [6]);
+
+        // the offset is clamped to the top, where the thumb is at the top of the track.
+        shell.scroll_offset = usize::MAX;
+        let buffer = frame(&mut shell, 20, 8)?;
+        assert_eq!(shell.scroll_offset, 63);
+        assert_eq!(thumb(&buffer), [0]);
+        assert!(rows(&buffer)[0].starts_with("$ echo "));
+        Ok(())
+    }
+
+    /// An executor with a heredoc, whose stdin is prompted for; `tee` reads the stdin without a
+    /// prompt of its own.
+    struct Heredoc;
+
+    impl Execute for Heredoc {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "sh>".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                stdin_required: cmd.starts_with("cat <<") || cmd == "tee",
+                stdin_prompt: cmd.starts_with("cat <<").then(|| "heredoc>".to_string()),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdout: input.stdin.unwrap_or_default(),
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// While the stdin is collected, its lines are prefixed with the prompt of the command, as
+    /// prepared; without one, the lines are left bare.
+    #[test]
+    fn stdin_prompt_prefixes_collected_lines() -> anyhow::Result<()> {
+        let mut shell = app(Heredoc, ()).with_color(ColorMode::Always);
+        type_text(&mut shell, "cat <<EOF\nfirst\nsec")?;
+        let buffer = frame(&mut shell, 30, 5)?;
+        assert_eq!(
+            rows(&buffer)[..3],
+            ["sh> cat <<EOF", "heredoc> first", "heredoc> sec"]
+        );
+        assert_eq!(buffer[(0, 1)].fg, ratatui::style::Color::Blue);
+        assert_eq!(shell.cursor_position(), Some((2, 12)));
+
+        let mut shell = app(Heredoc, ());
+        type_text(&mut shell, "tee\nfirst\nsec")?;
+        assert_eq!(
+            rows(&frame(&mut shell, 30, 5)?)[..3],
+            ["sh> tee", "first", "sec"]
+        );
+        Ok(())
+    }
+
+    /// Without colors, every cell falls back to the default style, with the same layout as with
+    /// colors.
+    #[test]
+    fn colorless_falls_back_to_default_style() -> anyhow::Result<()> {
+        let render = |mode| -> anyhow::Result<Buffer> {
+            let mut shell = app(Vault, ()).with_color(mode);
+            shell.run_command("vault login", None)?;
+            type_text(&mut shell, "vault read")?;
+            frame(&mut shell, 40, 6)
+        };
+        let colored = render(ColorMode::Always)?;
+        let plain = render(ColorMode::Never)?;
+
+        assert_eq!(rows(&plain), rows(&colored));
+        assert_eq!(rows(&plain)[4], "vault> vault read");
+        assert_eq!(colored[(0, 0)].fg, ratatui::style::Color::Blue);
+        assert_eq!(colored[(0, 3)].fg, ratatui::style::Color::Red);
+        // only the cursor keeps its (reversed) style, to stay visible.
+        for (index, cell) in plain.content().iter().enumerate() {
+            let mut default = ratatui::buffer::Cell::default();
+            default.set_symbol(cell.symbol());
+            if plain.pos_of(index) == (17, 4) {
+                default.modifier = Modifier::REVERSED;
+            }
+            assert_eq!(*cell, default);
+        }
+        Ok(())
+    }
+
+    /// An executor that streams the progress of a build through the writer, before its summary.
+    struct Build;
+
+    impl Execute for Build {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "build".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            ctx: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            self.execute_streaming(ctx, input, command::OutputWriter::default())
+        }
+
+        fn execute_streaming(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+            writer: command::OutputWriter,
+        ) -> anyhow::Result<OutputAction> {
+            for target in input.command.split_whitespace().skip(1) {
+                writer.stdout_line(&format!("compiling {}", target));
+                if target.ends_with('!') {
+                    writer.stderr_line(&format!("warning: {} is unstable", target));
+                }
+            }
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdout: vec!["finished".to_string()],
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The lines written through the writer are kept in the history in the order of writing,
+    /// across the streams, ahead of the output that the command returns.
+    #[test]
+    fn written_lines_stay_in_order() -> anyhow::Result<()> {
+        let mut shell = app(Build, ());
+        shell.run_command("make core net! cli", None)?;
+
+        let stdout = |line: &str| command::OutputLine::Stdout(line.to_string());
+        assert_eq!(
+            shell.history[0].lines(),
+            [
+                stdout("compiling core"),
+                stdout("compiling net!"),
+                command::OutputLine::Stderr("warning: net! is unstable".to_string()),
+                stdout("compiling cli"),
+                stdout("finished"),
+            ]
+        );
+        assert_eq!(
+            rows(&frame(&mut shell, 40, 7)?)[1..6],
+            [
+                "compiling core",
+                "compiling net!",
+                "warning: net! is unstable",
+                "compiling cli",
+                "finished"
+            ]
+        );
+        Ok(())
+    }
+
+    /// With the expansion, `!!` re-runs the last command, and `!N` the N-th one, recorded as
+    /// expanded; an unknown event is reported without executing anything.
+    #[test]
+    fn bangs_rerun_history() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_bang_expansion(true);
+        type_text(&mut shell, "grep -r fixme\nls\n!!\n!1 src\n!9\necho hi!\n")?;
+        assert_eq!(
+            shell.context,
+            ["grep -r fixme", "ls", "ls", "grep -r fixme src", "echo hi!"]
+        );
+        assert_eq!(shell.history[2].command, "ls");
+        assert_eq!(shell.history[3].command, "grep -r fixme src");
+        assert_eq!(shell.history[4].command, "!9");
+        assert_eq!(shell.history[4].stderr, ["!9: event not found"]);
+
+        // without it, the command is executed as typed.
+        let mut shell = app(Echo, Vec::new());
+        type_text(&mut shell, "ls\n!!\n")?;
+        assert_eq!(shell.context, ["ls", "!!"]);
+        Ok(())
+    }
+
+    /// `Enter` on a blank buffer executes it, does nothing, or renders a fresh prompt; with the
+    /// block submission, a blank stdin line submits the stdin instead.
+    #[test]
+    fn blank_line_follows_behavior() -> anyhow::Result<()> {
+        let blank = |behavior| -> anyhow::Result<App<Echo>> {
+            let mut shell = app(Echo, Vec::new()).with_blank_line_behavior(behavior);
+            type_text(&mut shell, "  \n")?;
+            Ok(shell)
+        };
+
+        let shell = blank(BlankLine::Execute)?;
+        assert_eq!(shell.context, ["  "]);
+        assert_eq!(shell.history.len(), 1);
+
+        let shell = blank(BlankLine::Ignore)?;
+        assert!(shell.context.is_empty() && shell.history.is_empty());
+        assert_eq!(idle(&shell), Some(("  ", 2)));
+
+        let shell = blank(BlankLine::FreshPrompt)?;
+        assert!(shell.context.is_empty());
+        assert_eq!(shell.history.len(), 1);
+        assert_eq!(shell.history[0].prompt, "$");
+        assert!(shell.history[0].stdout.is_empty());
+        assert_eq!(idle(&shell), Some(("", 0)));
+
+        let mut shell = blank(BlankLine::SubmitBlock)?;
+        assert!(shell.context.is_empty() && shell.history.is_empty());
+        shell.input(key(KeyCode::Backspace))?;
+        shell.input(key(KeyCode::Backspace))?;
+        type_text(&mut shell, "cat\nfirst\nsecond\n\n")?;
+        assert_eq!(shell.context, ["cat"]);
+        assert_eq!(shell.history[0].stdin, ["first", "second"]);
+        Ok(())
+    }
+
+    /// The completion candidates are rendered in the configured style, and the selected one in
+    /// its own style.
+    #[test]
+    fn completion_menu_uses_theme() -> anyhow::Result<()> {
+        use ratatui::style::Color;
+
+        let menu = Style::default().fg(Color::White).bg(Color::Indexed(236));
+        let mut shell = app(Echo, Vec::new())
+            .with_color(ColorMode::Always)
+            .with_completion_style(menu)
+            .with_completion_selected_style(Style::default().bg(Color::Green));
+        for command in ["git status", "grep -rn todo", "grep -c fixme"] {
+            shell.run_command(command, None)?;
+        }
+        type_text(&mut shell, "g")?;
+        shell.input(key(KeyCode::Tab))?;
+        let buffer = frame(&mut shell, 30, 10)?;
+        assert_eq!(rows(&buffer)[6..9], ["$ g", "  grep", "  git"]);
+        let style = |y| (buffer[(2, y)].fg, buffer[(2, y)].bg);
+        assert_eq!(style(7), (Color::White, Color::Indexed(236)));
+        assert_eq!(style(8), (Color::White, Color::Indexed(236)));
+
+        // cycling selects the first candidate, in the selected style.
+        shell.input(key(KeyCode::Tab))?;
+        let buffer = frame(&mut shell, 30, 10)?;
+        let style = |y| (buffer[(2, y)].fg, buffer[(2, y)].bg);
+        assert_eq!(style(7), (Color::Reset, Color::Green));
+        assert_eq!(style(8), (Color::White, Color::Indexed(236)));
+        // the indentation isn't styled.
+        assert_eq!(buffer[(0, 7)].bg, Color::Reset);
+        Ok(())
+    }
+
+    /// The enqueued commands run in order once the shell is idle, without any events, and wait
+    /// for the command that is being typed.
+    #[test]
+    fn queued_commands_run_in_order() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new());
+        let mut terminal = Terminal::new(TestBackend::new(40, 10))?;
+        shell.enqueue_command("git fetch");
+        shell.enqueue_command(String::from("git rebase origin/main"));
+        shell.step(&mut terminal)?;
+        shell.step(&mut terminal)?;
+        assert_eq!(shell.context(), &["git fetch", "git rebase origin/main"]);
+        assert!(shell.queue.is_empty());
+
+        shell.input(key(KeyCode::Char('l')))?;
+        shell.enqueue_command("pwd");
+        shell
+            .replay
+            .extend([key(KeyCode::Char('s')), key(KeyCode::Enter)]);
+        shell.step(&mut terminal)?;
+        shell.step(&mut terminal)?;
+        assert_eq!(shell.context()[2..], ["ls"]);
+        shell.step(&mut terminal)?;
+        assert_eq!(shell.context()[2..], ["ls", "pwd"]);
+        let entries = shell.history().iter().map(|entry| entry.command.as_str());
+        assert_eq!(
+            entries.collect::<Vec<_>>(),
+            ["git fetch", "git rebase origin/main", "ls", "pwd"]
+        );
+        Ok(())
+    }
+
+    /// An executor whose prompt is the working directory, however deep.
+    struct Cwd;
+
+    impl Execute for Cwd {
+        type Context = String;
+
+        fn prompt(&self, cwd: &Self::Context) -> String {
+            cwd.clone()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The overlong prompt is truncated by cells, ending with an ellipsis, keeping the command on
+    /// the same row; without the maximum width, it wraps.
+    #[test]
+    fn overlong_prompt_is_truncated() -> anyhow::Result<()> {
+        let cwd = "~/src/日本語/shelgon/crates/core/src".to_string();
+        let mut shell = app(Cwd, cwd.clone()).with_prompt_max_width(12);
+        shell.run_command("make", None)?;
+        type_text(&mut shell, "git log")?;
+
+        let rows = rows(&frame(&mut shell, 30, 4)?);
+        assert_eq!(rows[..2], ["~/src/日本… make", "~/src/日本… git log"]);
+        assert_eq!("~/src/日本…".width(), 11);
+        assert_eq!(shell.cursor_position(), Some((1, 19)));
+
+        let mut shell = app(Cwd, cwd);
+        type_text(&mut shell, "git log")?;
+        let rows = self::rows(&frame(&mut shell, 30, 4)?);
+        assert_eq!(
+            rows[..2],
+            ["~/src/日本語/shelgon/crates/co", "re/src git log"]
+        );
+        Ok(())
+    }
+
+    /// An editor that vetoes exiting and clearing while there are unsaved changes.
+    struct Editor;
+
+    impl Execute for Editor {
+        /// Whether there are unsaved changes.
+        type Context = bool;
+
+        fn prompt(&self, dirty: &Self::Context) -> String {
+            match dirty {
+                true => "ed*".to_string(),
+                false => "ed".to_string(),
+            }
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn should_exit(&self, dirty: &Self::Context) -> bool {
+            !dirty
+        }
+
+        fn should_clear(&self, dirty: &Self::Context) -> bool {
+            !dirty
+        }
+
+        fn execute(
+            &self,
+            dirty: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            match input.command.as_str() {
+                "quit" => return Ok(OutputAction::Exit),
+                "clear" => return Ok(OutputAction::Clear),
+                "write" => *dirty = false,
+                _ => *dirty = true,
+            }
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// With unsaved changes, the exit and the clear are vetoed, by the keys and by the actions,
+    /// and the shell keeps running; once saved, they go through.
+    #[test]
+    fn veto_keeps_shell_running() -> anyhow::Result<()> {
+        let mut shell = app(Editor, false);
+        let typed = |text: &str| {
+            text.chars()
+                .map(|c| match c {
+                    '\n' => key(KeyCode::Enter),
+                    c => key(KeyCode::Char(c)),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut events = typed("append\nquit\nclear\n");
+        events.extend([ctrl('d'), ctrl('c'), ctrl('l')]);
+        assert_eq!(drive(&mut shell, events)?, None);
+        assert!(shell.context);
+        let entries = shell.history().iter().map(|entry| entry.command.as_str());
+        assert_eq!(entries.collect::<Vec<_>>(), ["append"]);
+
+        assert_eq!(drive(&mut shell, typed("write\nclear\n"))?, None);
+        assert!(shell.history().is_empty());
+        assert_eq!(drive(&mut shell, typed("quit\n"))?, Some(String::new()));
+
+        let mut shell = app(Editor, false);
+        assert_eq!(drive(&mut shell, [ctrl('d')])?, Some(String::new()));
+        Ok(())
+    }
+
+    /// The escape reads the stdin from the file, after the lines typed so far, and submits it; a
+    /// file that can't be read is reported, and the stdin is still collected.
+    #[test]
+    fn stdin_is_read_from_file() -> anyhow::Result<()> {
+        let path = temp_path("stdin-read.csv");
+        std::fs::write(&path, "id,name\n1,ada\n2,grace\n")?;
+        let mut shell = app(Echo, Vec::new()).with_stdin_read_escape(":read");
+
+        type_text(&mut shell, "cat\n# users\n:read /nonexistent/users.csv\n")?;
+        assert!(matches!(shell.state, State::Running(..)));
+        let notice = shell.notice.clone().unwrap_or_default();
+        assert!(
+            notice.starts_with("cannot read /nonexistent/users.csv: "),
+            "{}",
+            notice
+        );
+
+        // the path is corrected on the same line.
+        for _ in "/nonexistent/users.csv".chars() {
+            shell.input(key(KeyCode::Backspace))?;
+        }
+        type_text(&mut shell, &path.display().to_string())?;
+        shell.input(key(KeyCode::Enter))?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(shell.context(), &["cat"]);
+        assert_eq!(
+            shell.history()[0].stdin,
+            ["# users", "id,name", "1,ada", "2,grace"]
+        );
+        assert_eq!(idle(&shell), Some(("", 0)));
+        Ok(())
+    }
+
+    /// In the stash mode, the text that was being typed comes back when navigating down past the
+    /// most recent command; the other modes overwrite it, or keep it from being recalled over.
+    #[test]
+    fn stashed_text_is_restored() -> anyhow::Result<()> {
+        let recall = |mode, keys: &[KeyCode]| -> anyhow::Result<String> {
+            let mut shell = app(Echo, Vec::new()).with_history_recall_mode(mode);
+            for command in ["cargo build", "make test"] {
+                shell.run_command(command, None)?;
+            }
+            type_text(&mut shell, "docker ps")?;
+            for code in keys {
+                shell.input(key(*code))?;
+            }
+            Ok(idle(&shell)
+                .map(|(cmd, _)| cmd.to_string())
+                .unwrap_or_default())
+        };
+        let (up, down) = (KeyCode::Up, KeyCode::Down);
+
+        assert_eq!(recall(HistoryRecallMode::Stash, &[up, up])?, "cargo build");
+        assert_eq!(
+            recall(HistoryRecallMode::Stash, &[up, up, down])?,
+            "make test"
+        );
+        assert_eq!(
+            recall(HistoryRecallMode::Stash, &[up, up, down, down])?,
+            "docker ps"
+        );
+        // once restored, navigating further down keeps it.
+        assert_eq!(
+            recall(HistoryRecallMode::Stash, &[up, down, down])?,
+            "docker ps"
+        );
+
+        assert_eq!(
+            recall(HistoryRecallMode::Overwrite, &[up, up])?,
+            "cargo build"
+        );
+        assert_eq!(recall(HistoryRecallMode::Overwrite, &[up, down])?, "");
+        assert_eq!(recall(HistoryRecallMode::WhenEmpty, &[up])?, "docker ps");
+        Ok(())
+    }
+
+    /// A download that streams its progress, resolved (along with the writer) by the test.
+    struct Download;
+
+    impl Execute for Download {
+        type Context = Vec<(command::OutputWriter, PendingResolver)>;
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "dl".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            ctx: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            self.execute_streaming(ctx, input, command::OutputWriter::default())
+        }
+
+        fn execute_streaming(
+            &self,
+            ctx: &mut Self::Context,
+            _: CommandInput,
+            writer: command::OutputWriter,
+        ) -> anyhow::Result<OutputAction> {
+            let (pending, resolver) = PendingOutput::new();
+            ctx.push((writer, resolver));
+            Ok(OutputAction::Pending(pending))
+        }
+    }
+
+    /// The progress is updated in place, by the carriage return and by the update, across the
+    /// ticks: a single line remains, with the final value.
+    #[test]
+    fn progress_line_is_updated_in_place() -> anyhow::Result<()> {
+        let mut shell = app(Download, Vec::new());
+        shell.run_command("fetch rust.tar.gz", None)?;
+        let (writer, resolver) = shell.context_mut().remove(0);
+
+        writer.stdout_line("resolving host");
+        writer.stdout_line("Progress: 10%");
+        shell.tick();
+        assert_eq!(
+            rows(&frame(&mut shell, 30, 5)?)[1..4],
+            ["resolving host", "Progress: 10%", "…"]
+        );
+
+        // the line that is already taken is replaced, as is the one that isn't yet.
+        writer.update_last_line("Progress: 55%");
+        writer.stdout_line("\rProgress: 80%");
+        shell.tick();
+        assert_eq!(
+            rows(&frame(&mut shell, 30, 5)?)[1..4],
+            ["resolving host", "Progress: 80%", "…"]
+        );
+
+        writer.stdout_line("\rProgress: 100%");
+        shell.tick();
+        resolver.resolve(Ok(CommandOutput {
+            stdout: vec!["saved rust.tar.gz".to_string()],
+            ..Default::default()
+        }));
+        shell.tick();
+        assert_eq!(
+            shell.history()[0].stdout,
+            ["resolving host", "Progress: 100%", "saved rust.tar.gz"]
+        );
+        Ok(())
+    }
+
+    /// The transcript keeps the colors of the rendered history: the prompt in blue, and the
+    /// stderr in red (around the style of the transform).
+    #[test]
+    fn transcript_keeps_prompt_and_stderr_colors() -> anyhow::Result<()> {
+        let mut shell = app(Vault, ()).with_color(ColorMode::Always);
+        shell.run_command("vault login", None)?;
+        let html = shell.transcript_html();
+        assert!(html.contains(r#"<span style="color: #0000ee">vault&gt;</span>"#));
+        assert!(html.contains(concat!(
+            r#"<span style="color: #cd0000">expiring </span>"#,
+            r#"<span style="color: #cd00cd">token=[redacted]</span>"#
+        )));
+        assert!(!html.contains("s3cr3t"));
+        Ok(())
+    }
+
+    /// The input past the limit is cut, whether typed, pasted or inserted by `Tab` in the middle
+    /// of the line, with the feedback.
+    #[test]
+    fn input_is_capped_at_limit() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new())
+            .with_max_input_length(10)
+            .with_input_limit_feedback(InputLimitFeedback::Message)
+            .with_mid_line_tab(MidLineTab::InsertSpaces(4));
+
+        shell.input(Event::Paste("grep -rn".to_string()))?;
+        assert_eq!(idle(&shell), Some(("grep -rn", 8)));
+        assert_eq!(shell.notice, None);
+
+        // two of the four spaces fit.
+        shell.input(key(KeyCode::Home))?;
+        shell.input(key(KeyCode::Tab))?;
+        assert_eq!(idle(&shell), Some(("  grep -rn", 2)));
+        assert_eq!(shell.notice.as_deref(), Some("(input too long)"));
+
+        shell.input(key(KeyCode::End))?;
+        type_text(&mut shell, "x")?;
+        assert_eq!(idle(&shell), Some(("  grep -rn", 10)));
+        shell.input(Event::Paste(" todo".to_string()))?;
+        assert_eq!(idle(&shell), Some(("  grep -rn", 10)));
+        assert_eq!(shell.notice.as_deref(), Some("(input too long)"));
+
+        // the pasted text is cut at the limit, keeping what fits.
+        let mut shell = app(Echo, Vec::new()).with_max_input_length(6);
+        shell.input(Event::Paste("cargo build".to_string()))?;
+        assert_eq!(idle(&shell), Some(("cargo ", 6)));
+        Ok(())
+    }
+
+    /// The shown completions are refined regardless of the case, and the applied candidate keeps
+    /// its own case; otherwise, the case has to match.
+    #[test]
+    fn completion_ignores_case() -> anyhow::Result<()> {
+        let refined = |ignore_case| -> anyhow::Result<App<Echo>> {
+            let mut shell = app(Echo, Vec::new()).with_case_insensitive_completion(ignore_case);
+            shell.input(key(KeyCode::Tab))?;
+            type_text(&mut shell, "GI")?;
+            Ok(shell)
+        };
+        let candidates = |shell: &App<Echo>| match shell.state {
+            State::Idle(_, _, Some(ref completions)) => completions.candidates.clone(),
+            _ => Vec::new(),
+        };
+
+        let mut shell = refined(true)?;
+        assert_eq!(candidates(&shell), ["git"]);
+        shell.input(key(KeyCode::Tab))?;
+        shell.input(key(KeyCode::Enter))?;
+        assert_eq!(idle(&shell), Some(("git", 3)));
+        assert!(shell.context().is_empty());
+
+        let shell = refined(false)?;
+        assert!(candidates(&shell).is_empty());
+        assert_eq!(idle(&shell), Some(("GI", 2)));
+        Ok(())
+    }
+
+    /// A key-value store, whose `set` modifies the state and `get` doesn't.
+    struct Store;
+
+    impl Execute for Store {
+        type Context = std::collections::HashMap<String, String>;
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "kv>".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            store: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            let words = input.command.split_whitespace().collect::<Vec<_>>();
+            let (stdout, modified) = match words[..] {
+                ["set", key, value] => {
+                    store.insert(key.to_string(), value.to_string());
+                    (Vec::new(), true)
+                }
+                ["get", key] => (store.get(key).cloned().into_iter().collect(), false),
+                _ => anyhow::bail!("usage: set <key> <value> | get <key>"),
+            };
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdout,
+                modified,
+                ..Default::default()
+            }))
         }
     }
 
-    /// Move the cursor to the right by one.
-    fn move_cursor_right(&mut self) {
-        match self.state {
-            State::Idle(ref cmd, cursor, _) if cursor == cmd.len() => {}
-            State::Idle(_, ref mut cursor, _) => {
-                *cursor += 1;
+    /// Only the command that modified the state is marked, in yellow, before its prompt.
+    #[test]
+    fn modified_commands_are_marked() -> anyhow::Result<()> {
+        let mut shell = app(Store, Default::default())
+            .with_color(ColorMode::Always)
+            .with_modified_marker("●");
+        shell.run_command("set color blue", None)?;
+        shell.run_command("get color", None)?;
+
+        let buffer = frame(&mut shell, 30, 4)?;
+        assert_eq!(
+            rows(&buffer),
+            ["● kv> set color blue", "kv> get color", "blue", "kv>"]
+        );
+        assert_eq!(buffer[(0, 0)].fg, ratatui::style::Color::Yellow);
+        assert_eq!(buffer[(2, 0)].fg, ratatui::style::Color::Blue);
+        assert_eq!(buffer[(0, 1)].fg, ratatui::style::Color::Blue);
+        Ok(())
+    }
+
+    /// Clearing the screen keeps the history and the scroll, redrawing in full on the next step;
+    /// clearing the history empties it, and scrolls back to the bottom.
+    #[test]
+    fn screen_and_history_clear_separately() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new());
+        for index in 0..12 {
+            shell.run_command(&format!("ls {}", index), None)?;
+        }
+        frame(&mut shell, 40, 10)?;
+        shell.input(key(KeyCode::PageUp))?;
+        let offset = shell.scroll_offset;
+        assert!(offset > 0);
+
+        shell.clear_screen();
+        assert!(shell.redraw);
+        assert_eq!(shell.history().len(), 12);
+        let mut terminal = Terminal::new(TestBackend::new(40, 10))?;
+        shell.replay.push_back(Event::FocusGained);
+        shell.step(&mut terminal)?;
+        assert!(!shell.redraw);
+        assert_eq!((shell.history().len(), shell.scroll_offset), (12, offset));
+
+        shell.clear_history();
+        assert!(shell.history().is_empty());
+        assert_eq!(shell.scroll_offset, 0);
+        assert!(!shell.redraw);
+        assert_eq!(rows(&frame(&mut shell, 40, 3)?), ["$", "", ""]);
+        Ok(())
+    }
+
+    /// The command takes the completing style only while the completions are shown.
+    #[test]
+    fn command_is_styled_while_completing() -> anyhow::Result<()> {
+        use ratatui::style::Color;
+
+        let mut shell = app(Echo, Vec::new())
+            .with_color(ColorMode::Always)
+            .with_completing_command_style(Style::default().italic().fg(Color::Cyan));
+        type_text(&mut shell, "gr")?;
+        let style = |shell: &mut App<Echo>| -> anyhow::Result<(Color, Modifier)> {
+            let buffer = frame(shell, 30, 5)?;
+            Ok((buffer[(2, 0)].fg, buffer[(2, 0)].modifier))
+        };
+        assert_eq!(style(&mut shell)?, (Color::Reset, Modifier::BOLD));
+
+        shell.input(key(KeyCode::Tab))?;
+        assert!(matches!(shell.state, State::Idle(_, _, Some(_))));
+        assert_eq!(style(&mut shell)?, (Color::Cyan, Modifier::ITALIC));
+
+        shell.input(key(KeyCode::Esc))?;
+        assert_eq!(style(&mut shell)?, (Color::Reset, Modifier::BOLD));
+        Ok(())
+    }
+
+    /// The prompt takes a line of its own, with the command and the cursor below it, in the
+    /// history as well as for the command being typed.
+    #[test]
+    fn prompt_renders_on_own_line() -> anyhow::Result<()> {
+        let cwd = "~/src/shelgon (main)".to_string();
+        let mut shell = app(Cwd, cwd.clone()).with_prompt_on_own_line(true);
+        shell.run_command("cargo fmt", None)?;
+        type_text(&mut shell, "git pu")?;
+
+        let rows = rows(&frame(&mut shell, 30, 5)?);
+        assert_eq!(rows[..4], [cwd.as_str(), "cargo fmt", &cwd, "git pu"]);
+        assert_eq!(shell.cursor_position(), Some((3, 6)));
+
+        let mut shell = app(Cwd, cwd);
+        type_text(&mut shell, "git pu")?;
+        assert_eq!(
+            self::rows(&frame(&mut shell, 30, 5)?)[0],
+            "~/src/shelgon (main) git pu"
+        );
+        assert_eq!(shell.cursor_position(), Some((0, 27)));
+        Ok(())
+    }
+
+    /// A clock in the prompt, controlled by the test.
+    struct Clock(Arc<std::sync::atomic::AtomicU64>);
+
+    impl Execute for Clock {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            let seconds = self.0.load(std::sync::atomic::Ordering::SeqCst);
+            format!("[{:02}:{:02}]", seconds / 60, seconds % 60)
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
             }
-            State::Running(..) => {}
+        }
+
+        fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Redraw)
         }
     }
 
-    /// Move the cursor back by one.
-    fn cursor_backspace(&mut self) {
-        match self.state {
-            State::Idle(ref mut _cmd, 0, _) => {}
-            State::Idle(ref mut cmd, ref mut cursor, ref mut comp) => {
-                cmd.remove(*cursor - 1);
-                *cursor -= 1;
-                *comp = None;
+    /// With the refresh, the shell wakes up every tick without input, and every frame recomputes
+    /// the prompt as the clock advances.
+    #[test]
+    fn prompt_refreshes_every_tick() -> anyhow::Result<()> {
+        let seconds = Arc::new(std::sync::atomic::AtomicU64::new(59));
+        assert_eq!(app(Clock(seconds.clone()), ()).tick_rate(), None);
+
+        let mut shell = app(Clock(seconds.clone()), ()).with_prompt_refresh(true);
+        assert_eq!(shell.tick_rate(), Some(TICK_RATE));
+        type_text(&mut shell, "uptime")?;
+
+        let mut terminal = Terminal::new(TestBackend::new(20, 2))?;
+        let mut prompts = Vec::new();
+        for _ in 0..3 {
+            terminal.draw(|f| shell.render(f))?;
+            shell.tick();
+            prompts.push(rows(terminal.backend().buffer())[0].clone());
+            seconds.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+        assert_eq!(
+            prompts,
+            ["[00:59] uptime", "[01:00] uptime", "[01:01] uptime"]
+        );
+        Ok(())
+    }
+
+    /// The pasted lines become stdin lines, with the partial line left to be typed on; without
+    /// the split, the paste goes into the line being typed.
+    #[test]
+    fn pasted_lines_split_into_stdin() -> anyhow::Result<()> {
+        let stdin = |shell: &App<Echo>| match shell.state {
+            State::Running(_, ref stdin, _) => stdin.clone(),
+            State::Idle(..) => Vec::new(),
+        };
+
+        let mut shell = app(Echo, Vec::new());
+        type_text(&mut shell, "cat\nfrom: ")?;
+        shell.input(Event::Paste(
+            "ada\r\nto: grace\nsubject: notes\nbo".to_string(),
+        ))?;
+        assert_eq!(
+            stdin(&shell),
+            ["from: ada", "to: grace", "subject: notes", "bo"]
+        );
+        type_text(&mut shell, "dy")?;
+        shell.input(ctrl('d'))?;
+        assert_eq!(
+            shell.history()[0].stdin,
+            ["from: ada", "to: grace", "subject: notes", "body"]
+        );
+
+        let mut shell = app(Echo, Vec::new()).with_stdin_paste_lines(false);
+        type_text(&mut shell, "cat\n")?;
+        shell.input(Event::Paste("one\ntwo\nthree".to_string()))?;
+        assert_eq!(stdin(&shell), ["onetwothree"]);
+        Ok(())
+    }
+
+    /// Executing a command snaps the scrolled view back to the bottom, or, without following the
+    /// output, keeps the view on the same content as the output is added below.
+    #[test]
+    fn scrolled_view_follows_output() -> anyhow::Result<()> {
+        for follow in [true, false] {
+            let mut shell = app(Echo, Vec::new()).with_follow_output(follow);
+            for index in 0..10 {
+                shell.run_command(&format!("ls {}", index), None)?;
             }
-            State::Running(ref mut _pre, ref mut stdin) => {
-                stdin.last_mut().map(|i| i.pop());
-                if stdin.last().map_or(true, |i| i.is_empty()) {
-                    stdin.pop();
+            frame(&mut shell, 30, 6)?;
+            shell.input(key(KeyCode::PageUp))?;
+            let before = rows(&frame(&mut shell, 30, 6)?);
+            assert_eq!(shell.scroll_offset, 5);
+
+            shell.run_command("git status", None)?;
+            let after = rows(&frame(&mut shell, 30, 6)?);
+            match follow {
+                true => {
+                    assert_eq!(shell.scroll_offset, 0);
+                    assert_eq!(after[3..], ["$ git status", "git status", "$"]);
+                }
+                false => {
+                    assert_eq!(shell.scroll_offset, 7);
+                    assert_eq!(after, before);
                 }
             }
         }
+        Ok(())
     }
 
-    /// Continue the execution of the command.
-    fn continue_execution(&mut self) -> anyhow::Result<Next> {
-        let (prepare, stdin) = match self.state {
-            State::Running(ref prep, ref stdin) => (prep.clone(), stdin.clone()),
-            State::Idle(..) => return Ok(Next::Continue),
+    /// `Tab` completes, inserts the indent, or (in the smart mode) completes a word that is being
+    /// typed and indents otherwise; while the stdin is collected, the indent goes into the line.
+    #[test]
+    fn tab_follows_mode() -> anyhow::Result<()> {
+        let tab = |mode, typed: &str| -> anyhow::Result<(String, bool)> {
+            let mut shell = app(Echo, Vec::new()).with_tab_mode(mode);
+            type_text(&mut shell, typed)?;
+            shell.input(key(KeyCode::Tab))?;
+            Ok(match shell.state {
+                State::Idle(ref cmd, _, ref comp) => (cmd.clone(), comp.is_some()),
+                State::Running(_, ref stdin, _) => (stdin.join("\n"), false),
+            })
         };
+        let owned = |cmd: &str, listed| (cmd.to_string(), listed);
 
-        self._final_execution(&prepare.command, Some(stdin))
+        assert_eq!(tab(TabMode::Complete, "gi")?, owned("git", true));
+        assert_eq!(tab(TabMode::Complete, "ls ")?, owned("ls ", true));
+        assert_eq!(tab(TabMode::InsertTab(4), "gi")?, owned("gi    ", false));
+        assert_eq!(tab(TabMode::Smart(2), "gi")?, owned("git", true));
+        assert_eq!(tab(TabMode::Smart(2), "ls ")?, owned("ls   ", false));
+        assert_eq!(tab(TabMode::Smart(2), "")?, owned("  ", false));
+        assert_eq!(tab(TabMode::InsertTab(3), "cat\nx")?, owned("x   ", false));
+        Ok(())
     }
 
-    /// Execute the command.
-    fn execute_command(&mut self) -> anyhow::Result<Next> {
-        let (cmd, _) = match self.state {
-            State::Idle(ref cmd, cursor, _) => (cmd.clone(), cursor),
-            State::Running(..) => return Ok(Next::Continue),
+    /// An executor that documents `tar` and `chmod`, the usage first and the options after.
+    struct Manual;
+
+    impl Execute for Manual {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "%".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn help(&self, _: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+            let usage = match buffer.split_whitespace().next()? {
+                "tar" => "usage: tar [-cxtv] [-f archive] [file ...]",
+                "chmod" => "usage: chmod mode file ...",
+                _ => return None,
+            };
+            let mut lines = vec![Line::styled(usage, Style::default().green())];
+            if buffer.contains(" -") {
+                lines.push(Line::raw("  -v  verbose"));
+            }
+            Some(lines)
+        }
+
+        fn execute(&self, _: &mut Self::Context, _: CommandInput) -> anyhow::Result<OutputAction> {
+            Ok(OutputAction::Clear)
+        }
+    }
+
+    /// The help of the command is rendered below it as it's typed, and follows the buffer; it's
+    /// only rendered when enabled.
+    #[test]
+    fn inline_help_follows_buffer() -> anyhow::Result<()> {
+        let mut shell = app(Manual, ()).with_inline_help(true);
+        let below = |shell: &mut App<Manual>| -> anyhow::Result<Vec<String>> {
+            Ok(rows(&frame(shell, 50, 4)?)[1..3].to_vec())
         };
 
-        let prepare = self.executor.prepare(&cmd);
-        self.state = State::Running(prepare.clone(), Vec::new());
+        type_text(&mut shell, "ta")?;
+        assert_eq!(below(&mut shell)?, ["", ""]);
+        type_text(&mut shell, "r")?;
+        assert_eq!(
+            below(&mut shell)?,
+            ["usage: tar [-cxtv] [-f archive] [file ...]", ""]
+        );
+        type_text(&mut shell, " -")?;
+        assert_eq!(
+            below(&mut shell)?,
+            [
+                "usage: tar [-cxtv] [-f archive] [file ...]",
+                "  -v  verbose"
+            ]
+        );
+        for _ in "tar -".chars() {
+            shell.input(key(KeyCode::Backspace))?;
+        }
+        type_text(&mut shell, "chmod")?;
+        assert_eq!(below(&mut shell)?, ["usage: chmod mode file ...", ""]);
 
-        match prepare.stdin_required {
-            true => Ok(Next::Continue),
-            false => self._final_execution(&cmd, None),
+        let mut shell = app(Manual, ());
+        type_text(&mut shell, "tar")?;
+        assert_eq!(below(&mut shell)?, ["", ""]);
+        Ok(())
+    }
+
+    /// The lines streamed between the ticks are taken at once, on the tick, and rendered together
+    /// in the next frame: a burst of lines costs a single draw.
+    #[test]
+    fn streamed_burst_renders_in_one_frame() -> anyhow::Result<()> {
+        let mut shell = app(Download, Vec::new());
+        shell.run_command("tail -f app.log", None)?;
+        let (writer, _resolver) = shell.context_mut().remove(0);
+        let mut terminal = Terminal::new(TestBackend::new(30, 4))?;
+        let mut step = |shell: &mut App<Download>| -> anyhow::Result<usize> {
+            let before = terminal.get_frame().count();
+            shell.replay.push_back(Event::FocusGained);
+            shell.step(&mut terminal)?;
+            Ok(terminal.get_frame().count() - before)
+        };
+        assert_eq!(step(&mut shell)?, 1);
+
+        let burst = std::thread::spawn(move || {
+            for index in 0..200 {
+                writer.stdout_line(&format!("GET /api/{}", index));
+            }
+        });
+        burst
+            .join()
+            .map_err(|_| anyhow::anyhow!("the writer panicked"))?;
+        // the step draws the frame first, then its tick takes the whole burst at once.
+        assert_eq!(step(&mut shell)?, 1);
+        assert_eq!(shell.history()[0].stdout.len(), 200);
+
+        assert_eq!(step(&mut shell)?, 1);
+        let rows = rows(terminal.backend().buffer());
+        assert_eq!(rows[1..3], ["GET /api/199", "…"]);
+        Ok(())
+    }
+
+    /// The prefix that the recalled command matched is styled apart from the recalled rest, until
+    /// the command is edited.
+    #[test]
+    fn recalled_match_is_highlighted() -> anyhow::Result<()> {
+        use ratatui::style::Color;
+
+        let mut shell = app(Echo, Vec::new())
+            .with_color(ColorMode::Always)
+            .with_prefix_history_search(true)
+            .with_history_match_style(Style::default().fg(Color::Green));
+        for command in ["cargo test", "ls"] {
+            shell.run_command(command, None)?;
         }
+        type_text(&mut shell, "car")?;
+        shell.input(key(KeyCode::Up))?;
+
+        let buffer = frame(&mut shell, 30, 6)?;
+        assert_eq!(rows(&buffer)[4], "$ cargo test");
+        let style = |x: u16| (buffer[(x, 4)].fg, buffer[(x, 4)].modifier);
+        for x in 2..5 {
+            assert_eq!(style(x), (Color::Green, Modifier::BOLD));
+        }
+        for x in 5..12 {
+            assert_eq!(style(x), (Color::Reset, Modifier::BOLD));
+        }
+
+        // once edited, the whole command is styled the same.
+        type_text(&mut shell, "s")?;
+        let buffer = frame(&mut shell, 30, 6)?;
+        assert_eq!(buffer[(2, 4)].fg, Color::Reset);
+        Ok(())
     }
 
-    /// Execute the command and return the next action.
-    fn _final_execution(&mut self, cmd: &str, stdin: Option<Vec<String>>) -> anyhow::Result<Next> {
-        let prompt = self.executor.prompt(&self.context);
-        let output = self.executor.execute(
-            &mut self.context,
-            command::CommandInput {
-                prompt,
-                command: cmd.to_string(),
-                stdin,
-                #[cfg(feature = "tokio")]
-                runtime: self.runtime.clone(),
+    /// The screen is cleared before the first frame only when enabled; otherwise, nothing is
+    /// cleared, keeping what's already on the screen.
+    #[test]
+    fn screen_is_cleared_on_start() -> anyhow::Result<()> {
+        let start = |clear: bool| -> anyhow::Result<Vec<u8>> {
+            let mut shell = app(Echo, Vec::new())
+                .with_manage_terminal(false)
+                .with_clear_on_start(clear);
+            shell.replay.push_back(ctrl('d'));
+            let mut out = Vec::new();
+            shell.execute_on(&mut out, Viewport::Fixed(Rect::new(0, 0, 20, 3)))?;
+            Ok(out)
+        };
+        let clear_below = b"\x1b[J";
+        let cleared = |out: &[u8]| {
+            out.windows(clear_below.len())
+                .filter(|w| w == clear_below)
+                .count()
+        };
+
+        let out = start(true)?;
+        assert_eq!(cleared(&out), 3);
+        // the clear comes before the prompt is drawn.
+        let first_clear = out
+            .windows(clear_below.len())
+            .position(|w| w == clear_below);
+        let prompt = out.iter().position(|b| *b == b'$');
+        assert!(first_clear < prompt, "{:?}", String::from_utf8_lossy(&out));
+
+        let out = start(false)?;
+        assert_eq!(cleared(&out), 0);
+        assert!(out.contains(&b'$'));
+        Ok(())
+    }
+
+    thread_local! {
+        /// The raw mode of the simulated terminal, and the number of times it was enabled.
+        static RAW_MODE: std::cell::Cell<(bool, usize)> = const { std::cell::Cell::new((true, 0)) };
+    }
+
+    /// The raw mode that is lost is re-enabled on the next tick, only with the watchdog, and the
+    /// raw mode that is intact is left alone.
+    #[test]
+    fn watchdog_restores_raw_mode() {
+        let simulated = RawMode {
+            is_enabled: || Ok(RAW_MODE.get().0),
+            enable: || {
+                RAW_MODE.set((true, RAW_MODE.get().1 + 1));
+                Ok(())
             },
+        };
+        let mut shell = app(Echo, Vec::new()).with_raw_mode_watchdog(true);
+        shell.raw_mode = simulated;
+        assert_eq!(shell.tick_rate(), Some(TICK_RATE));
+
+        shell.tick();
+        assert_eq!(RAW_MODE.get(), (true, 0));
+        // e.g. a child process that disabled the raw mode on exit.
+        RAW_MODE.set((false, 0));
+        shell.tick();
+        assert_eq!(RAW_MODE.get(), (true, 1));
+        shell.tick();
+        assert_eq!(RAW_MODE.get(), (true, 1));
+
+        let mut shell = app(Echo, Vec::new());
+        shell.raw_mode = simulated;
+        RAW_MODE.set((false, 0));
+        shell.tick();
+        assert_eq!(RAW_MODE.get(), (false, 0));
+    }
+
+    /// A calculator that adds the numbers, failing on anything else.
+    struct Calc;
+
+    impl Execute for Calc {
+        type Context = ();
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            "calc>".to_string()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            _: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            let mut sum = 0;
+            for term in input.command.split('+') {
+                sum += term
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|e| anyhow::anyhow!("{:?}: {}", term.trim(), e))?;
+            }
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                stdout: vec![sum.to_string()],
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The failed command is restored as submitted, with the cursor where it was, so that it can
+    /// be fixed and retried; otherwise, the buffer is reset.
+    #[test]
+    fn failed_command_is_restored() -> anyhow::Result<()> {
+        let mut shell = app(Calc, ())
+            .with_error_recovery(true)
+            .with_restore_on_error(true);
+        type_text(&mut shell, "12 + x4")?;
+        shell.input(key(KeyCode::Left))?;
+        shell.input(key(KeyCode::Enter))?;
+        assert_eq!(idle(&shell), Some(("12 + x4", 6)));
+        assert_eq!(
+            shell.history()[0].stderr,
+            [r#""x4": invalid digit found in string"#]
+        );
+
+        shell.input(key(KeyCode::Backspace))?;
+        shell.input(key(KeyCode::Enter))?;
+        assert_eq!(shell.history()[1].stdout, ["16"]);
+        assert_eq!(idle(&shell), Some(("", 0)));
+
+        let mut shell = app(Calc, ()).with_error_recovery(true);
+        type_text(&mut shell, "1 + one\n")?;
+        assert_eq!(
+            shell.history()[0].stderr,
+            [r#""one": invalid digit found in string"#]
+        );
+        assert_eq!(idle(&shell), Some(("", 0)));
+        Ok(())
+    }
+
+    /// The pasted block keeps its lines in the buffer, rendered as rows aligned with the first
+    /// one, and is executed as a whole once submitted.
+    #[test]
+    fn pasted_block_renders_as_rows() -> anyhow::Result<()> {
+        let block = "for f in *.rs; do\n  wc -l \"$f\"\n  head -1 \"$f\"\ndone\necho ok";
+        let mut shell = app(Echo, Vec::new()).with_multiline_paste(true);
+        shell.input(Event::Paste(block.replace('\n', "\r\n")))?;
+        assert_eq!(idle(&shell), Some((block, block.len())));
+
+        let rows = rows(&frame(&mut shell, 30, 6)?);
+        assert_eq!(
+            rows[..5],
+            [
+                "$ for f in *.rs; do",
+                "    wc -l \"$f\"",
+                "    head -1 \"$f\"",
+                "  done",
+                "  echo ok"
+            ]
+        );
+        assert_eq!(shell.cursor_position(), Some((4, 9)));
+
+        shell.input(key(KeyCode::Enter))?;
+        assert_eq!(shell.context(), &[block]);
+        Ok(())
+    }
+
+    /// A completion with a range outside the command is ignored (the command is kept), with the
+    /// feedback of an empty completion, rather than stopping the shell.
+    #[test]
+    fn invalid_completion_range_is_ignored() -> anyhow::Result<()> {
+        /// An executor that completes a range past the end of the command.
+        struct Overshoot;
+
+        impl Execute for Overshoot {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                ">".to_string()
+            }
+
+            fn completion_result(
+                &self,
+                _: &Self::Context,
+                buffer: &str,
+                _: usize,
+            ) -> anyhow::Result<command::CompletionResult> {
+                Ok(command::CompletionResult {
+                    range: buffer.len()..buffer.len() + 4,
+                    insert: "tail".to_string(),
+                    candidates: vec!["head".to_string()],
+                })
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                _: &mut Self::Context,
+                _: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Exit)
+            }
+        }
+
+        let mut shell =
+            app(Overshoot, ()).with_empty_completion_feedback(EmptyCompletionFeedback::Bell);
+        type_text(&mut shell, "cat")?;
+        shell.input(key(KeyCode::Tab))?;
+        assert_eq!(idle(&shell), Some(("cat", 3)));
+        assert!(shell.bell);
+        let listed = match shell.state {
+            State::Idle(_, _, ref completions) => completions.as_ref().map(|c| c.candidates.len()),
+            State::Running(..) => None,
+        };
+        assert_eq!(listed, Some(0));
+        Ok(())
+    }
+
+    /// `Right` dismisses the completions (like the other cursor moves), so that the text typed
+    /// since the completion stays before the cursor, even past a wide character.
+    #[test]
+    fn cursor_right_dismisses_completions() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_mid_line_tab(MidLineTab::Complete);
+        type_text(&mut shell, "a日")?;
+        shell.input(key(KeyCode::Left))?;
+        shell.input(key(KeyCode::Tab))?;
+        shell.input(key(KeyCode::Char('x')))?;
+        shell.input(key(KeyCode::Right))?;
+
+        assert_eq!(idle(&shell), Some(("ax日", 5)));
+        assert!(matches!(shell.state, State::Idle(_, _, None)));
+        assert_eq!(rows(&frame(&mut shell, 20, 1)?), ["$ ax日"]);
+        Ok(())
+    }
+
+    /// The keys read by a command are taken from the replay, and recorded (so that the recording
+    /// replays them to the command again), while a paste read in between is left to the shell.
+    #[test]
+    fn command_keys_are_replayed_and_recorded() -> anyhow::Result<()> {
+        /// An executor that reads the keys of the answer (up to `Enter`) for `ask`, and echoes the
+        /// other commands.
+        struct Ask;
+
+        impl Execute for Ask {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "?".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                let mut answer = String::new();
+                if input.command == "ask" {
+                    while let KeyCode::Char(c) = input.keys.read_key()?.code {
+                        answer.push(c);
+                    }
+                }
+                Ok(OutputAction::Command(CommandOutput {
+                    prompt: input.prompt,
+                    stdout: vec![answer],
+                    command: input.command,
+                    ..Default::default()
+                }))
+            }
+        }
+
+        let path = temp_path("keys.rec");
+        let mut recorded = app(Ask, ()).with_record(&path)?;
+        drive(
+            &mut recorded,
+            [
+                key(KeyCode::Char('a')),
+                key(KeyCode::Char('s')),
+                key(KeyCode::Char('k')),
+                key(KeyCode::Enter),
+                key(KeyCode::Char('n')),
+                Event::Paste("ls".to_string()),
+                key(KeyCode::Char('o')),
+                key(KeyCode::Enter),
+            ],
         )?;
-        self.state = State::Idle(String::new(), 0, None);
+        assert_eq!(recorded.history()[0].stdout, ["no"]);
+        assert_eq!(idle(&recorded), Some(("ls", 2)));
+
+        let mut replayed = app(Ask, ()).replay(&path)?;
+        drive(&mut replayed, [])?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(replayed.history()[0].stdout, ["no"]);
+        assert_eq!(idle(&replayed), Some(("ls", 2)));
+        Ok(())
+    }
+
+    /// The accented and CJK characters are typed, moved over and deleted as whole characters,
+    /// with the cursor (in bytes) on their boundaries, and on screen past their width.
+    #[test]
+    fn multibyte_characters_edit_whole() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new());
+        type_text(&mut shell, "é日x")?;
+        // the rendered row, and the cursor on screen as `(row, column)`.
+        let screen = |shell: &mut App<Echo>| -> anyhow::Result<_> {
+            let row = rows(&frame(shell, 20, 1)?).remove(0);
+            Ok((row, shell.cursor_position()))
+        };
+        assert_eq!(idle(&shell), Some(("é日x", 6)));
+        assert_eq!(screen(&mut shell)?, ("$ é日x".to_string(), Some((0, 6))));
+
+        for (code, cursor, column) in [
+            (KeyCode::Left, 5, 5),
+            (KeyCode::Left, 2, 3),
+            (KeyCode::Right, 5, 5),
+            (KeyCode::Home, 0, 2),
+            (KeyCode::Right, 2, 3),
+            (KeyCode::End, 6, 6),
+            (KeyCode::Left, 5, 5),
+        ] {
+            shell.input(key(code))?;
+            assert_eq!(idle(&shell), Some(("é日x", cursor)), "{:?}", code);
+            let (_, position) = screen(&mut shell)?;
+            assert_eq!(position, Some((0, column)), "{:?}", code);
+        }
+
+        shell.input(key(KeyCode::Backspace))?;
+        assert_eq!(idle(&shell), Some(("éx", 2)));
+        assert_eq!(screen(&mut shell)?, ("$ éx".to_string(), Some((0, 3))));
+        shell.input(key(KeyCode::Backspace))?;
+        assert_eq!(idle(&shell), Some(("x", 0)));
+        assert_eq!(screen(&mut shell)?, ("$ x".to_string(), Some((0, 2))));
+        Ok(())
+    }
+
+    /// The image is rendered as its placeholder, and teed as the escape of the protocol (or as
+    /// the placeholder, without a protocol), with the PNG encoded as base64.
+    #[test]
+    fn images_are_teed_with_protocol() -> anyhow::Result<()> {
+        /// An executor that shows a PNG of 2x3, padded to the size in the command.
+        struct Show;
 
-        match output {
-            command::OutputAction::Command(command_output) => self.history.push(command_output),
-            command::OutputAction::Exit => {
-                return Ok(Next::Exit("".to_string()));
+        impl Execute for Show {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "img".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
             }
-            command::OutputAction::Clear => {
-                self.history.clear();
-                return Ok(Next::Clear);
+
+            fn execute(&self, _: &mut (), input: CommandInput) -> anyhow::Result<OutputAction> {
+                let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x02\0\0\0\x03".to_vec();
+                png.resize(input.command.parse()?, 0);
+                Ok(OutputAction::Image(png))
             }
         }
 
-        Ok(Next::Continue)
+        let teed = |protocol, command: &str| -> anyhow::Result<String> {
+            let sink = Shared::default();
+            let mut shell = app(Show, ())
+                .with_graphics_protocol(protocol)
+                .with_output_tee(Box::new(sink.clone()));
+            shell.run_command(command, None)?;
+            let rendered = [
+                format!("img {}", command),
+                "[image: 2x3 PNG]".into(),
+                "img".into(),
+            ];
+            assert_eq!(rows(&frame(&mut shell, 30, 3)?), rendered);
+            let teed = sink.0.lock().expect("poisoned").clone();
+            Ok(String::from_utf8(teed)?)
+        };
+
+        let encoded = "iVBORw0KGgoAAAANSUhEUgAAAAIAAAAD";
+        assert_eq!(
+            teed(GraphicsProtocol::None, "24")?,
+            "img 24\n[image: 2x3 PNG]\n"
+        );
+        assert_eq!(
+            teed(GraphicsProtocol::Kitty, "24")?,
+            format!("img 24\n\x1b_Ga=T,f=100,m=0;{}\x1b\\\n", encoded)
+        );
+        assert_eq!(
+            teed(GraphicsProtocol::Iterm2, "24")?,
+            format!("img 24\n\x1b]1337;File=inline=1;size=24:{}\x07\n", encoded)
+        );
+
+        // 4024 bytes are 5368 in base64, sent in two chunks.
+        let kitty = teed(GraphicsProtocol::Kitty, "4024")?;
+        let first = format!("img 4024\n\x1b_Ga=T,f=100,m=1;{}", encoded);
+        assert!(kitty.starts_with(&first));
+        assert_eq!(
+            kitty.find("\x1b\\\x1b_Gm=0;"),
+            Some(first.len() - encoded.len() + 4096)
+        );
+        assert!(kitty.ends_with("AAAAAA==\x1b\\\n"));
+        assert_eq!(
+            kitty.len(),
+            first.len() - encoded.len() + 5368 + "\x1b\\\x1b_Gm=0;\x1b\\\n".len()
+        );
+        Ok(())
     }
-}
 
-/// Render the history of the commands.
-fn render_history(history: &command::CommandOutput) -> Vec<Line<'_>> {
-    let command = Line::from(vec![
-        Span::styled(history.prompt.clone(), Style::default().blue()),
-        Span::raw(" "),
-        Span::styled(history.command.clone(), Style::default().bold()),
-    ]);
-    let stdin = history
-        .stdin
-        .iter()
-        .cloned()
-        .map(Span::raw)
-        .map(Line::from)
-        .collect::<Vec<_>>();
-    let stdout = history
-        .stdout
-        .iter()
-        .cloned()
-        .map(Span::raw)
-        .map(Line::from)
-        .collect::<Vec<_>>();
-    let stderr = history
-        .stderr
-        .iter()
-        .cloned()
-        .map(|i| Span::styled(i, Style::default().red()))
-        .map(Line::from)
-        .collect::<Vec<_>>();
+    /// With the lazy rendering, only the entries in view are rendered (however long the history
+    /// is), and scrolling up into the skipped entries renders the same rows as rendering all.
+    #[test]
+    fn lazy_rendering_is_bounded() -> anyhow::Result<()> {
+        /// An executor that counts the output lines that it transforms for rendering.
+        struct Counted(Arc<AtomicUsize>);
 
-    let mut lines = vec![command];
-    lines.extend(stdin);
-    lines.extend(stdout);
-    lines.extend(stderr);
+        impl Execute for Counted {
+            type Context = ();
 
-    lines
+            fn prompt(&self, _: &Self::Context) -> String {
+                "#".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn transform_output(&self, line: &str) -> Line<'static> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Line::raw(line.to_string())
+            }
+
+            fn execute(&self, _: &mut (), _: CommandInput) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Exit)
+            }
+        }
+
+        let shell = |lazy: bool| {
+            let transformed = Arc::new(AtomicUsize::new(0));
+            let mut shell = app(Counted(transformed.clone()), ()).with_lazy_rendering(lazy);
+            shell.history.extend((0..5000).map(|n| CommandOutput {
+                prompt: "#".to_string(),
+                command: format!("entry {}", n),
+                stdout: vec![format!("output {}", n)],
+                ..Default::default()
+            }));
+            (shell, transformed)
+        };
+        let (mut lazy, transformed) = shell(true);
+        let (mut eager, _) = shell(false);
+
+        // each entry is 2 rows, so the 10 rows show the last 4 entries (and a half), and the prompt.
+        let rendered = rows(&frame(&mut lazy, 30, 10)?);
+        assert!(transformed.swap(0, Ordering::Relaxed) <= 10);
+        assert_eq!(rendered[0], "output 4995");
+        assert_eq!(rendered[8], "output 4999");
+        assert_eq!(rendered, rows(&frame(&mut eager, 30, 10)?));
+
+        // the view, 2000 rows up, is within the entries that were skipped (and estimated).
+        for shell in [&mut lazy, &mut eager] {
+            shell.scroll_offset = 2000;
+        }
+        // the entries are rendered up to the view, i.e. 2010 rows of the 10001.
+        let rendered = rows(&frame(&mut lazy, 30, 10)?);
+        assert!(transformed.load(Ordering::Relaxed) <= 2010 / 2 + 1);
+        assert_eq!(rendered[0], "output 3995");
+        assert_eq!(rendered, rows(&frame(&mut eager, 30, 10)?));
+        Ok(())
+    }
+
+    /// The footer summarizes the key bindings on the bottom row (below the shell), and follows
+    /// the rebound keys.
+    #[test]
+    fn key_hints_render_footer() -> anyhow::Result<()> {
+        let mut shell = app(Echo, Vec::new()).with_key_hints(true);
+        type_text(&mut shell, "ls")?;
+        let buffer = frame(&mut shell, 80, 3)?;
+        assert_eq!(
+            rows(&buffer),
+            [
+                "$ ls",
+                "",
+                "^L clear  ^D exit  ^C cancel  Tab complete  ↑ history  ^F highlight",
+            ]
+        );
+        assert!(buffer[(0, 2)].modifier.contains(Modifier::DIM));
+
+        // `Ctrl+L` is moved to `Ctrl+K`, and `Ctrl+G` also cancels.
+        let key_map = KeyMap::default()
+            .unbind(KeyCode::Char('l'), KeyModifiers::CONTROL)
+            .bind(KeyCode::Char('k'), KeyModifiers::CONTROL, Action::Clear)
+            .bind(KeyCode::Char('g'), KeyModifiers::CONTROL, Action::Interrupt);
+        let mut shell = app(Echo, Vec::new())
+            .with_key_hints(true)
+            .with_key_map(key_map);
+        assert_eq!(
+            rows(&frame(&mut shell, 80, 2)?)[1],
+            "^K clear  ^D exit  ^C/^G cancel  Tab complete  ↑ history  ^F highlight"
+        );
+        Ok(())
+    }
+
+    /// While paused, the events are neither read (staying queued) nor handled, and the terminal
+    /// is restored through the output the shell is executed on. Once resumed, the events are
+    /// handled.
+    #[test]
+    fn paused_shell_ignores_events() -> anyhow::Result<()> {
+        let pause = command::PauseHandle::default();
+        let mut shell = app(Echo, Vec::new()).with_pause_handle(pause.clone());
+        let output = Shared::default();
+        let viewport = Viewport::Fixed(Rect::new(0, 0, 20, 4));
+        let backend = CrosstermBackend::new(output.clone());
+        let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
+
+        pause.pause();
+        shell.replay.extend([
+            key(KeyCode::Char('l')),
+            key(KeyCode::Char('s')),
+            key(KeyCode::Enter),
+        ]);
+        for _ in 0..2 {
+            assert_eq!(shell.step(&mut terminal)?, None);
+        }
+        assert_eq!(shell.replay.len(), 3);
+        assert_eq!(idle(&shell), Some(("", 0)));
+        assert!(shell.history().is_empty());
+        // the alternate screen is left (and the bracketed paste disabled), and nothing is drawn.
+        let written = String::from_utf8(output.0.lock().expect("poisoned").clone())?;
+        assert_eq!(written, "\x1b[?2004l\x1b[?1049l");
+
+        // the raw mode isn't enabled again, as there's no terminal.
+        shell.settings.manage_terminal = false;
+        pause.resume();
+        while !shell.replay.is_empty() {
+            shell.step(&mut terminal)?;
+        }
+        assert_eq!(shell.context(), &["ls"]);
+        assert_eq!(shell.history()[0].stdout, ["ls"]);
+        Ok(())
+    }
+
+    /// A failed command of a line with several commands is restored along with the rest of the
+    /// line, which is dropped from the queue, so that it only runs once the line is resubmitted.
+    #[test]
+    fn restored_line_drops_queued_segments() -> anyhow::Result<()> {
+        let executed = |line: &str, fix: &[Event]| -> anyhow::Result<_> {
+            let mut shell = app(Calc, ())
+                .with_error_recovery(true)
+                .with_restore_on_error(true)
+                .with_command_separator(';');
+            let typed = line.chars().map(|c| key(KeyCode::Char(c)));
+            drive(
+                &mut shell,
+                typed.chain([key(KeyCode::Enter), Event::FocusGained]),
+            )?;
+            let restored = idle(&shell).map(|(cmd, cursor)| (cmd.to_string(), cursor));
+            assert!(shell.queue.is_empty());
+
+            let resubmit = [key(KeyCode::Enter), Event::FocusGained, Event::FocusGained];
+            drive(&mut shell, fix.iter().cloned().chain(resubmit))?;
+            let commands = shell.history().iter().map(|e| e.command.clone());
+            Ok((restored, commands.collect::<Vec<_>>()))
+        };
+
+        // the first command fails, restoring the whole line, with the cursor at its end.
+        let fix = [
+            key(KeyCode::Home),
+            key(KeyCode::Right),
+            key(KeyCode::Right),
+            key(KeyCode::Right),
+            key(KeyCode::Right),
+            key(KeyCode::Right),
+            key(KeyCode::Backspace),
+            key(KeyCode::Char('3')),
+        ];
+        let (restored, commands) = executed("1 + x; 4", &fix)?;
+        assert_eq!(restored, Some(("1 + x; 4".to_string(), 8)));
+        assert_eq!(commands, ["1 + x", "1 + 3", "4"]);
+
+        // a queued command fails, restoring it with the rest, with the cursor at its end.
+        let fix = [key(KeyCode::Backspace), key(KeyCode::Char('2'))];
+        let (restored, commands) = executed("5; 1 + y; 6", &fix)?;
+        assert_eq!(restored, Some(("1 + y; 6".to_string(), 5)));
+        assert_eq!(commands, ["5", "1 + y", "1 + 2", "6"]);
+        Ok(())
+    }
+
+    /// The completions of the providers are merged with the executor's own (in the order the
+    /// providers are registered), without the duplicates, and listed in the menu.
+    #[test]
+    fn completion_providers_merge() -> anyhow::Result<()> {
+        /// A provider of the words starting with the typed text.
+        struct Words(&'static [&'static str]);
+
+        impl command::CompletionProvider for Words {
+            type Context = Vec<String>;
+
+            fn complete(
+                &self,
+                _: &Self::Context,
+                input: &str,
+                cursor: usize,
+            ) -> anyhow::Result<(String, Vec<String>)> {
+                let words = self
+                    .0
+                    .iter()
+                    .filter_map(|word| word.strip_prefix(&input[..cursor]));
+                Ok((String::new(), words.map(String::from).collect()))
+            }
+        }
+
+        let mut shell = app(Echo, Vec::new())
+            .with_completion_provider(Words(&["gc", "git", "gl"]))
+            .with_completion_provider(Words(&["grep", "gl", "go"]));
+        type_text(&mut shell, "g")?;
+        shell.input(key(KeyCode::Tab))?;
+
+        let State::Idle(_, _, Some(ref completions)) = shell.state else {
+            anyhow::bail!("the completions aren't listed");
+        };
+        assert_eq!(completions.candidates, ["it", "rep", "c", "l", "o"]);
+        let rendered = rows(&frame(&mut shell, 20, 6)?);
+        assert_eq!(rendered, ["$ g", "  git", "  grep", "  gc", "  gl", "  go"]);
+
+        // a single (merged) candidate is completed in place.
+        let mut shell = app(Echo, Vec::new())
+            .with_completion_provider(Words(&["lsblk"]))
+            .with_completion_provider(Words(&["lsblk", "git"]));
+        type_text(&mut shell, "lsb")?;
+        shell.input(key(KeyCode::Tab))?;
+        assert_eq!(idle(&shell), Some(("lsblk", 5)));
+        Ok(())
+    }
+
+    /// The task spawned when the executor is created (on the runtime of the shell) notifies the
+    /// shell, which pushes the output into the history on a tick.
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawned_task_notifies_history() -> anyhow::Result<()> {
+        /// An executor that spawns a task notifying that the file has changed.
+        struct Watch(command::Notifier);
+
+        impl Execute for Watch {
+            type Context = ();
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "$".to_string()
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(&self, _: &mut (), _: CommandInput) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Exit)
+            }
+        }
+
+        impl command::New for Watch {
+            fn new() -> anyhow::Result<(Self, Self::Context)> {
+                let notifier = command::Notifier::default();
+                let task = notifier.clone();
+                tokio::spawn(async move {
+                    task.notify(CommandOutput {
+                        prompt: "!".to_string(),
+                        command: "watch".to_string(),
+                        stdout: vec!["the file has changed".to_string()],
+                        ..Default::default()
+                    });
+                });
+                Ok((Self(notifier), ()))
+            }
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()?;
+        let shell = App::<Watch>::new(runtime)?;
+        let notifier = shell.executor().0.clone();
+        let mut shell = shell.with_notifier(notifier);
+
+        // the shell ticks on every step, until the task has run.
+        for _ in 0..100 {
+            drive(&mut shell, [Event::FocusGained])?;
+            if !shell.history().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(shell.history().len(), 1);
+        assert_eq!(shell.history()[0].stdout, ["the file has changed"]);
+        assert_eq!(
+            rows(&frame(&mut shell, 30, 3)?),
+            ["! watch", "the file has changed", "$"]
+        );
+        Ok(())
+    }
+
+    /// A key bound to the interrupt (`Ctrl+G`, along with `Ctrl+C`) ends the stdin collection
+    /// the same way, i.e. aborts the command (or submits the stdin, by default).
+    #[test]
+    fn rebound_interrupt_aborts_stdin() -> anyhow::Result<()> {
+        let key_map =
+            KeyMap::default().bind(KeyCode::Char('g'), KeyModifiers::CONTROL, Action::Interrupt);
+        let mut shell = app(Heredoc, ())
+            .with_key_map(key_map.clone())
+            .with_stdin_interrupt(StdinAction::Abort);
+        for interrupt in ['c', 'g'] {
+            type_text(&mut shell, "tee\nlost")?;
+            assert_eq!(idle(&shell), None);
+            shell.input(ctrl(interrupt))?;
+            assert_eq!(idle(&shell), Some(("", 0)), "^{}", interrupt);
+            assert!(shell.history().is_empty(), "^{}", interrupt);
+        }
+
+        let mut shell = app(Heredoc, ()).with_key_map(key_map);
+        type_text(&mut shell, "tee\nkept")?;
+        shell.input(ctrl('g'))?;
+        assert_eq!(shell.history()[0].stdout, ["kept"]);
+        Ok(())
+    }
 }
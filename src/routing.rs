@@ -0,0 +1,494 @@
+//! Routing of commands to multiple executors, based on the command prefix.
+//!
+//! Some shells host multiple command namespaces, each of which is best implemented by its own
+//! executor (e.g. `sql:` and `sh:`). [`RoutingExecutor`] composes such executors into a single
+//! [`Execute`] implementation, dispatching each command to the executor registered for its prefix.
+//!
+//! # Example
+//!
+//! ```rust
+//! use shelgon::command::{self, CommandInput, CommandOutput, Execute, OutputAction};
+//! use shelgon::routing::RoutingExecutor;
+//!
+//! struct Echo(&'static str);
+//!
+//! impl Execute for Echo {
+//!     type Context = ();
+//!
+//!     fn prompt(&self, _: &Self::Context) -> String {
+//!         "$".to_string()
+//!     }
+//!
+//!     fn prepare(&self, cmd: &str) -> command::Prepare {
+//!         command::Prepare {
+//!             command: cmd.to_string(),
+//!             stdin_required: false,
+//!             ..Default::default()
+//!         }
+//!     }
+//!
+//!     fn execute(&self, _: &mut Self::Context, input: CommandInput) -> anyhow::Result<OutputAction> {
+//!         Ok(OutputAction::Command(CommandOutput {
+//!             prompt: input.prompt,
+//!             command: input.command.clone(),
+//!             stdin: Vec::new(),
+//!             stdout: vec![format!("{}: {}", self.0, input.command)],
+//!             stderr: Vec::new(),
+//!             ..Default::default()
+//!         }))
+//!     }
+//! }
+//!
+//! // `sql:select 1` is executed by the `sql` executor, as `select 1`.
+//! let router = RoutingExecutor::new(Echo("default"))
+//!     .route("sql:", Echo("sql"))
+//!     .route("sh:", Echo("sh"));
+//!
+//! assert_eq!(router.prepare("sql:select 1").command, "sql:select 1");
+//! ```
+
+use ratatui::style::Style;
+use ratatui::text::Line;
+
+use crate::command::{
+    merge_completions, CommandInput, CompletionResult, Execute, OutputAction, OutputWriter, Prepare,
+};
+
+/// A boxed executor, sharing the context `C`.
+type BoxedExecutor<C> = Box<dyn Execute<Context = C>>;
+
+///
+/// [`RoutingExecutor`] dispatches the commands to the executor registered for the command prefix,
+/// falling back to a default executor when no prefix matches.
+///
+/// The prefix is stripped from the command before it is handed to the executor, i.e. the executor
+/// registered for `sql:` receives `select 1` for the command `sql:select 1`. When multiple
+/// prefixes match, the longest one wins.
+///
+/// All the executors share the same context. The prompt (and the other hooks that aren't specific
+/// to a command) is provided by the fallback executor. The command names (see
+/// [`Execute::commands`]) are the ones of the fallback executor, along with the ones of the other
+/// executors, with their prefix prepended.
+///
+pub struct RoutingExecutor<C> {
+    /// The executors, along with the prefix they are registered for.
+    routes: Vec<(String, BoxedExecutor<C>)>,
+    /// The executor that is used when no prefix matches.
+    fallback: BoxedExecutor<C>,
+}
+
+impl<C> RoutingExecutor<C> {
+    /// Create a new [`RoutingExecutor`], with the executor used when no prefix matches.
+    pub fn new(fallback: impl Execute<Context = C> + 'static) -> Self {
+        Self {
+            routes: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Register the executor for the commands starting with `prefix`.
+    pub fn route(
+        mut self,
+        prefix: impl Into<String>,
+        executor: impl Execute<Context = C> + 'static,
+    ) -> Self {
+        self.routes.push((prefix.into(), Box::new(executor)));
+        self
+    }
+
+    /// Find the route for the command, returning the matched prefix and the executor.
+    fn dispatch(&self, cmd: &str) -> (&str, &dyn Execute<Context = C>) {
+        self.routes
+            .iter()
+            .filter(|(prefix, _)| cmd.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(prefix, executor)| (prefix.as_str(), executor.as_ref()))
+            .unwrap_or(("", self.fallback.as_ref()))
+    }
+
+    /// Find the route for the prepared command, returning the executor, along with the prepared
+    /// command as it's seen by the executor (i.e. without the prefix).
+    fn dispatch_prepared(&self, prepare: &Prepare) -> (Prepare, &dyn Execute<Context = C>) {
+        let (prefix, executor) = self.dispatch(&prepare.command);
+        let prepare = Prepare {
+            command: prepare.command[prefix.len()..].to_string(),
+            ..prepare.clone()
+        };
+        (prepare, executor)
+    }
+}
+
+impl<C> Execute for RoutingExecutor<C> {
+    type Context = C;
+
+    fn prompt(&self, ctx: &Self::Context) -> String {
+        self.fallback.prompt(ctx)
+    }
+
+    fn prompt_symbol(&self, ctx: &Self::Context) -> Option<char> {
+        self.fallback.prompt_symbol(ctx)
+    }
+
+    fn prompt_context(&self, ctx: &Self::Context) -> Vec<(String, Style)> {
+        self.fallback.prompt_context(ctx)
+    }
+
+    fn completion(
+        &self,
+        ctx: &Self::Context,
+        incomplete_command: &str,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        let (prefix, executor) = self.dispatch(incomplete_command);
+        let completion = executor.completion(ctx, &incomplete_command[prefix.len()..])?;
+        if !prefix.is_empty() {
+            return Ok(completion);
+        }
+
+        // when no prefix matches yet, the prefixes themselves are completed, along with the
+        // completions of the fallback.
+        let prefixes = self
+            .routes
+            .iter()
+            .filter(|(prefix, _)| prefix.starts_with(incomplete_command))
+            .map(|(prefix, _)| prefix[incomplete_command.len()..].to_string())
+            .collect();
+        Ok(merge_completions(vec![
+            completion,
+            (String::new(), prefixes),
+        ]))
+    }
+
+    fn completion_result(
+        &self,
+        ctx: &Self::Context,
+        buffer: &str,
+        cursor: usize,
+    ) -> anyhow::Result<CompletionResult> {
+        // the route is found once, by the command before the cursor, so that the prefix is within it.
+        let (prefix, executor) = self.dispatch(&buffer[..cursor]);
+        // without a prefix, the prefixes themselves are completed. (see `completion`)
+        if prefix.is_empty() {
+            let (insert, candidates) = self.completion(ctx, &buffer[..cursor])?;
+            return Ok(CompletionResult {
+                range: cursor..cursor,
+                insert,
+                candidates,
+            });
+        }
+
+        let result =
+            executor.completion_result(ctx, &buffer[prefix.len()..], cursor - prefix.len())?;
+        // the range is shifted past the stripped prefix.
+        Ok(CompletionResult {
+            range: result.range.start + prefix.len()..result.range.end + prefix.len(),
+            ..result
+        })
+    }
+
+    fn commands(&self, ctx: &Self::Context) -> Vec<String> {
+        let mut commands = self.fallback.commands(ctx);
+        for (prefix, executor) in self.routes.iter() {
+            commands.extend(
+                executor
+                    .commands(ctx)
+                    .into_iter()
+                    .map(|command| format!("{}{}", prefix, command)),
+            );
+        }
+        commands
+    }
+
+    fn prepare(&self, cmd: &str) -> Prepare {
+        let (prefix, executor) = self.dispatch(cmd);
+        let prepare = executor.prepare(&cmd[prefix.len()..]);
+
+        // the prefix is kept, so that the command can be routed again when it's executed.
+        Prepare {
+            command: format!("{}{}", prefix, prepare.command),
+            ..prepare
+        }
+    }
+
+    fn preview(&self, ctx: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+        let (prefix, executor) = self.dispatch(buffer);
+        executor.preview(ctx, &buffer[prefix.len()..])
+    }
+
+    fn help(&self, ctx: &Self::Context, buffer: &str) -> Option<Vec<Line<'static>>> {
+        let (prefix, executor) = self.dispatch(buffer);
+        executor.help(ctx, &buffer[prefix.len()..])
+    }
+
+    fn stdin_status(
+        &self,
+        ctx: &Self::Context,
+        prepare: &Prepare,
+        collected: &[String],
+    ) -> Option<Line<'static>> {
+        let (prepare, executor) = self.dispatch_prepared(prepare);
+        executor.stdin_status(ctx, &prepare, collected)
+    }
+
+    fn stdin_completion(
+        &self,
+        ctx: &Self::Context,
+        prepare: &Prepare,
+        partial: &str,
+    ) -> anyhow::Result<(String, Vec<String>)> {
+        let (prepare, executor) = self.dispatch_prepared(prepare);
+        executor.stdin_completion(ctx, &prepare, partial)
+    }
+
+    fn transform_output(&self, line: &str) -> Line<'static> {
+        self.fallback.transform_output(line)
+    }
+
+    fn should_exit(&self, ctx: &Self::Context) -> bool {
+        self.fallback.should_exit(ctx)
+    }
+
+    fn should_clear(&self, ctx: &Self::Context) -> bool {
+        self.fallback.should_clear(ctx)
+    }
+
+    fn preprocess_stdin(&self, ctx: &Self::Context, stdin: Vec<String>) -> Vec<String> {
+        self.fallback.preprocess_stdin(ctx, stdin)
+    }
+
+    fn not_found(&self, ctx: &Self::Context, command: &str) -> Option<OutputAction> {
+        let (prefix, executor) = self.dispatch(command);
+        let output = executor.not_found(ctx, &command[prefix.len()..])?;
+        Some(with_prefix(prefix, output))
+    }
+
+    fn execute(&self, ctx: &mut Self::Context, cmd: CommandInput) -> anyhow::Result<OutputAction> {
+        let (prefix, executor) = self.dispatch(&cmd.command);
+        let command = cmd.command[prefix.len()..].to_string();
+
+        let output = executor.execute(ctx, CommandInput { command, ..cmd })?;
+        Ok(with_prefix(prefix, output))
+    }
+
+    fn execute_streaming(
+        &self,
+        ctx: &mut Self::Context,
+        cmd: CommandInput,
+        writer: OutputWriter,
+    ) -> anyhow::Result<OutputAction> {
+        let (prefix, executor) = self.dispatch(&cmd.command);
+        let command = cmd.command[prefix.len()..].to_string();
+
+        let output = executor.execute_streaming(ctx, CommandInput { command, ..cmd }, writer)?;
+        Ok(with_prefix(prefix, output))
+    }
+}
+
+/// Restore the prefix (stripped before the execution) on the command of the output.
+fn with_prefix(prefix: &str, output: OutputAction) -> OutputAction {
+    match output {
+        OutputAction::Command(mut output) => {
+            output.command = format!("{}{}", prefix, output.command);
+            OutputAction::Command(output)
+        }
+        output => output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{CommandOutput, Execute};
+
+    use super::*;
+
+    /// An executor that logs the commands it ran (in the shared context), tagged with its name.
+    struct Tagged(&'static str, &'static [&'static str]);
+
+    impl Execute for Tagged {
+        type Context = Vec<String>;
+
+        fn prompt(&self, _: &Self::Context) -> String {
+            format!("{}>", self.0)
+        }
+
+        fn commands(&self, _: &Self::Context) -> Vec<String> {
+            self.1.iter().map(|command| command.to_string()).collect()
+        }
+
+        fn prepare(&self, cmd: &str) -> Prepare {
+            Prepare {
+                command: cmd.to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn execute(
+            &self,
+            log: &mut Self::Context,
+            input: CommandInput,
+        ) -> anyhow::Result<OutputAction> {
+            log.push(format!("{} ran {}", self.0, input.command));
+            Ok(OutputAction::Command(CommandOutput {
+                prompt: input.prompt,
+                command: input.command,
+                ..Default::default()
+            }))
+        }
+    }
+
+    /// The router for `a:` and `b:`, falling back to `sh`.
+    fn router() -> RoutingExecutor<Vec<String>> {
+        RoutingExecutor::new(Tagged("sh", &["help"]))
+            .route("a:", Tagged("a", &["foo"]))
+            .route("b:", Tagged("b", &["bar", "baz"]))
+    }
+
+    /// Execute the command with the router, returning the command of the output.
+    fn execute(
+        router: &RoutingExecutor<Vec<String>>,
+        log: &mut Vec<String>,
+        command: &str,
+    ) -> anyhow::Result<String> {
+        #[cfg(feature = "tokio")]
+        let runtime = std::sync::Arc::new(tokio::runtime::Builder::new_current_thread().build()?);
+        let input = CommandInput {
+            prompt: router.prompt(log),
+            command: router.prepare(command).command,
+            stdin: None,
+            cursor_position: None,
+            keys: Default::default(),
+            #[cfg(feature = "tokio")]
+            handle: runtime.handle().clone(),
+            #[cfg(feature = "tokio")]
+            runtime,
+        };
+        match router.execute(log, input)? {
+            OutputAction::Command(output) => Ok(output.command),
+            _ => anyhow::bail!("expected the output of {:?}", command),
+        }
+    }
+
+    /// The commands are dispatched by their prefix (stripped for the executor, and restored in the
+    /// output), and the rest to the fallback.
+    #[test]
+    fn dispatches_by_prefix() -> anyhow::Result<()> {
+        let router = router();
+        let mut log = Vec::new();
+
+        assert_eq!(execute(&router, &mut log, "a:foo")?, "a:foo");
+        assert_eq!(execute(&router, &mut log, "b:bar")?, "b:bar");
+        assert_eq!(execute(&router, &mut log, "help")?, "help");
+        assert_eq!(log, ["a ran foo", "b ran bar", "sh ran help"]);
+        Ok(())
+    }
+
+    /// The command names of the routed executors are listed with their prefix, and completed.
+    #[test]
+    fn commands_carry_prefixes() -> anyhow::Result<()> {
+        let router = router();
+
+        assert_eq!(
+            router.commands(&Vec::new()),
+            ["help", "a:foo", "b:bar", "b:baz"]
+        );
+        let (fixed, variable) = router.completion(&Vec::new(), "b:b")?;
+        assert_eq!(
+            (fixed.as_str(), variable),
+            ("a", vec!["r".to_string(), "z".to_string()])
+        );
+        Ok(())
+    }
+
+    /// The token under the cursor (in the middle of the command) is replaced through the range,
+    /// shifted past the prefix of the route found before the cursor, even if a longer prefix
+    /// matches the whole command.
+    #[test]
+    fn completion_replaces_mid_line_token() -> anyhow::Result<()> {
+        /// An executor that replaces the word under the cursor with the first of its commands that
+        /// starts with the typed part of the word.
+        struct Words(&'static [&'static str]);
+
+        impl Execute for Words {
+            type Context = Vec<String>;
+
+            fn prompt(&self, _: &Self::Context) -> String {
+                "w>".to_string()
+            }
+
+            fn completion_result(
+                &self,
+                _: &Self::Context,
+                buffer: &str,
+                cursor: usize,
+            ) -> anyhow::Result<CompletionResult> {
+                let start = buffer[..cursor].rfind(' ').map_or(0, |space| space + 1);
+                let end = buffer[cursor..]
+                    .find(' ')
+                    .map_or(buffer.len(), |space| cursor + space);
+                let insert = self
+                    .0
+                    .iter()
+                    .find(|word| word.starts_with(&buffer[start..cursor]))
+                    .map_or(&buffer[start..end], |word| word);
+                Ok(CompletionResult {
+                    range: start..end,
+                    insert: insert.to_string(),
+                    candidates: Vec::new(),
+                })
+            }
+
+            fn prepare(&self, cmd: &str) -> Prepare {
+                Prepare {
+                    command: cmd.to_string(),
+                    ..Default::default()
+                }
+            }
+
+            fn execute(
+                &self,
+                _: &mut Self::Context,
+                _: CommandInput,
+            ) -> anyhow::Result<OutputAction> {
+                Ok(OutputAction::Exit)
+            }
+        }
+
+        let router = RoutingExecutor::new(Tagged("sh", &["help"]))
+            .route("db:", Words(&["show", "select"]))
+            .route("db:select ", Words(&["from", "where"]));
+        let complete = |buffer: &str, cursor: usize| -> anyhow::Result<_> {
+            let result = router.completion_result(&Vec::new(), buffer, cursor)?;
+            Ok(result.apply(buffer))
+        };
+
+        // only `db:` is before the cursor, so `select` is completed by its executor.
+        assert_eq!(
+            complete("db:select id", 4)?,
+            Some(("db:show id".to_string(), 7))
+        );
+        Ok(())
+    }
+
+    /// Without a matching prefix yet, the prefixes are completed along with the commands of the
+    /// fallback: a single match is completed, and several are listed.
+    #[test]
+    fn prefixes_complete_with_fallback() -> anyhow::Result<()> {
+        let router = RoutingExecutor::new(Tagged("sh", &["status", "stop"]))
+            .route("sql:", Tagged("sql", &["select"]));
+        let complete = |typed: &str| router.completion(&Vec::new(), typed);
+
+        assert_eq!(
+            complete("s")?,
+            (
+                String::new(),
+                vec!["tatus".into(), "top".into(), "ql:".into()]
+            )
+        );
+        assert_eq!(complete("sq")?, ("l:".to_string(), Vec::new()));
+        assert_eq!(
+            complete("st")?,
+            (String::new(), vec!["atus".into(), "op".into()])
+        );
+        assert_eq!(complete("sql:s")?, ("elect".to_string(), Vec::new()));
+        Ok(())
+    }
+}
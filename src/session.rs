@@ -0,0 +1,32 @@
+//! Persistence of the structured session (the full history, including the output) as JSON.
+//!
+//! The session is stored as an array of the [`CommandOutput`]s, as they are serialized with
+//! `serde`, e.g.:
+//!
+//! ```json
+//! [{"prompt": "$", "command": "echo hi", "stdin": [], "stdout": ["hi"], "stderr": [], ...}]
+//! ```
+//!
+//! The missing fields are defaulted while loading, and the unknown ones are ignored, so that the
+//! sessions written by other versions can still be read.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::command::CommandOutput;
+
+/// Save the history to the file at `path`.
+pub(crate) fn save(path: &Path, history: &[CommandOutput]) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, history)?;
+    writeln!(file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Load the history from the file at `path`.
+pub(crate) fn load(path: &Path) -> anyhow::Result<Vec<CommandOutput>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(file)?)
+}